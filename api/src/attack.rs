@@ -0,0 +1,12 @@
+//! The kinds of attacks given by `{@atk}` tags and `attack` entries, and the numbers of
+//! creature actions which make attacks.
+
+mod analyze;
+mod error;
+mod format;
+mod kind;
+
+pub use analyze::{analyze_attack, analyze_entry_attack, damage_rolls, AttackProfile, DamageRoll};
+pub use error::{Error, Result};
+pub use format::{format_attack_kinds, AttackLabels, EnglishLabels};
+pub use kind::{AttackKind, AttackKinds, AttackRange, AttackSource};
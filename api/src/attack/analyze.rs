@@ -0,0 +1,227 @@
+use super::AttackKinds;
+use crate::dice::{trailing_number, Formula};
+use crate::entry::kinds::EntryAttack;
+use crate::entry::Entry;
+use crate::string::{tokenize, DefaultStringRenderer, Lexeme, RenderString};
+
+/// The numbers of an attack, as read from the text of a creature's action.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct AttackProfile {
+    pub kinds: Option<AttackKinds>,
+    pub to_hit: Option<i64>,
+    /// In feet.
+    pub reach: Option<u32>,
+    /// The normal and long range in feet.
+    pub range: Option<(u32, Option<u32>)>,
+    /// E.g. "one target".
+    pub targets: Option<String>,
+    pub damage: Vec<DamageRoll>,
+}
+
+/// A `{@damage}` tag with the average printed before it and the damage type after it,
+/// e.g. `7 ({@damage 1d8 + 3}) slashing damage`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageRoll {
+    pub average: Option<i64>,
+    pub formula: Formula,
+    pub damage_type: Option<String>,
+}
+
+/// Reads the attack made by an action, given its entries.
+/// Returns `None` if the entries have neither an `{@atk}` nor a `{@hit}` tag.
+pub fn analyze_attack(entries: &[Entry]) -> Option<AttackProfile> {
+    match read_attack(entries) {
+        (profile, true) => Some(profile),
+        (_, false) => None,
+    }
+}
+
+/// Every `{@damage}` roll in the entries, whether or not they make an attack.
+pub fn damage_rolls(entries: &[Entry]) -> Vec<DamageRoll> {
+    read_attack(entries).0.damage
+}
+
+/// Reads what it can of an attack, and whether the entries have `{@atk}` or `{@hit}` tags.
+fn read_attack(entries: &[Entry]) -> (AttackProfile, bool) {
+    let strings = entries
+        .iter()
+        .flat_map(|entry| entry.strings())
+        .collect::<Vec<_>>();
+
+    let mut profile = AttackProfile::default();
+    let mut is_attack = false;
+
+    for text in &strings {
+        let lexemes = match tokenize(text) {
+            Ok(lexemes) => lexemes.collect::<Vec<_>>(),
+            Err(_) => continue,
+        };
+
+        for (i, lexeme) in lexemes.iter().enumerate() {
+            let tag = match lexeme {
+                Lexeme::Tag(tag) => tag,
+                Lexeme::Text(_) => continue,
+            };
+            let first = tag.args.first().map(|s| s.trim()).unwrap_or_default();
+
+            match tag.name {
+                "atk" => {
+                    is_attack = true;
+                    if profile.kinds.is_none() {
+                        profile.kinds = first.parse().ok();
+                    }
+                }
+                "hit" => {
+                    is_attack = true;
+                    if profile.to_hit.is_none() {
+                        profile.to_hit = first.parse().ok();
+                    }
+                }
+                "damage" => {
+                    let formula = match first.parse() {
+                        Ok(formula) => formula,
+                        Err(_) => continue,
+                    };
+                    let average = match i.checked_sub(1).map(|i| &lexemes[i]) {
+                        Some(Lexeme::Text(before)) => before
+                            .trim_end()
+                            .strip_suffix('(')
+                            .and_then(trailing_number),
+                        _ => None,
+                    };
+                    let damage_type = match lexemes.get(i + 1) {
+                        Some(Lexeme::Text(after)) => damage_type(after),
+                        _ => None,
+                    };
+
+                    profile.damage.push(DamageRoll {
+                        average,
+                        formula,
+                        damage_type,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if let Ok(plain) = DefaultStringRenderer.render(text) {
+            read_distances(&plain, &mut profile);
+        }
+    }
+
+    (profile, is_attack)
+}
+
+/// Like [`analyze_attack`], for an `attack` entry.
+pub fn analyze_entry_attack(attack: &EntryAttack) -> AttackProfile {
+    let entries = attack
+        .attack_entries
+        .iter()
+        .chain(&attack.hit_entries)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let (mut profile, _) = read_attack(&entries);
+    profile.kinds = Some(attack.attack_type.into());
+    if profile.to_hit.is_none() {
+        let to_hit = regex!(r"([+-]\d+) to hit");
+        profile.to_hit = entries
+            .iter()
+            .flat_map(|entry| entry.strings())
+            .find_map(|s| to_hit.captures(&s)?[1].parse().ok());
+    }
+    profile
+}
+
+/// The damage type in the text following a `{@damage}` tag, e.g. `) fire damage`.
+fn damage_type(after: &str) -> Option<String> {
+    let pattern = regex!(r"^\)?\s*([a-z]+(?:(?:,\s*|,?\s+or\s+)[a-z]+)*)\s+damage");
+    Some(pattern.captures(after)?[1].to_owned())
+}
+
+fn read_distances(text: &str, profile: &mut AttackProfile) {
+    let reach = regex!(r"reach (\d+) ft\.");
+    let range = regex!(r"range (\d+)(?:/(\d+))? ft\.");
+    let targets = regex!(r"ft\.,\s*([^.]+?)\.");
+
+    if let Some(captures) = reach.captures(text) {
+        profile.reach = profile.reach.or_else(|| captures[1].parse().ok());
+    }
+    if let Some(captures) = range.captures(text) {
+        if profile.range.is_none() {
+            if let Ok(normal) = captures[1].parse() {
+                let long = captures.get(2).and_then(|m| m.as_str().parse().ok());
+                profile.range = Some((normal, long));
+            }
+        }
+    }
+    if let Some(captures) = targets.captures(text) {
+        if profile.targets.is_none() {
+            profile.targets = Some(captures[1].trim().to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attack::{AttackKind, AttackRange, AttackSource};
+    use crate::entry::EntryKind;
+
+    #[test]
+    fn analyze_action() {
+        let entries = vec![Entry::String(
+            "{@atk mw,rw} {@hit 5} to hit, reach 5 ft. or range 20/60 ft., one target. \
+            {@h}6 ({@damage 1d6 + 3}) piercing damage plus 7 ({@damage 2d6}) cold damage.",
+        )];
+
+        let profile = analyze_attack(&entries).unwrap();
+        assert_eq!(profile.kinds.as_ref().map(|k| k.0.len()), Some(2));
+        assert_eq!(profile.to_hit, Some(5));
+        assert_eq!(profile.reach, Some(5));
+        assert_eq!(profile.range, Some((20, Some(60))));
+        assert_eq!(profile.targets.as_deref(), Some("one target"));
+        assert_eq!(
+            profile
+                .damage
+                .iter()
+                .map(|d| (d.average, d.formula.to_string(), d.damage_type.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(6), "1d6 + 3".to_owned(), Some("piercing")),
+                (Some(7), "2d6".to_owned(), Some("cold")),
+            ]
+        );
+
+        assert_eq!(analyze_attack(&[Entry::String("The dragon roars.")]), None);
+    }
+
+    #[test]
+    fn analyze_attack_entry() {
+        let attack: Entry = serde_json::from_str(
+            r#"{
+                "type": "attack",
+                "attackType": "RW",
+                "attackEntries": ["+4 to hit, range 80/320 ft., one target."],
+                "hitEntries": ["5 ({@damage 1d8 + 1}) piercing damage."]
+            }"#,
+        )
+        .unwrap();
+        let attack = match &attack {
+            Entry::Entry(EntryKind::Attack(attack)) => attack,
+            entry => panic!("not an attack: {:?}", entry),
+        };
+
+        let profile = analyze_entry_attack(attack);
+        assert_eq!(
+            profile.kinds.map(|k| k.0),
+            Some(vec![AttackKind::new(
+                AttackRange::Ranged,
+                AttackSource::Weapon
+            )])
+        );
+        assert_eq!(profile.to_hit, Some(4));
+        assert_eq!(profile.range, Some((80, Some(320))));
+        assert_eq!(profile.damage[0].average, Some(5));
+    }
+}
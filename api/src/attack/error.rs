@@ -0,0 +1,11 @@
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("unknown attack code `{0}`")]
+    UnknownCode(char),
+    #[error("empty attack code")]
+    Empty,
+}
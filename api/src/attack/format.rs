@@ -0,0 +1,89 @@
+use super::{AttackKinds, AttackRange, AttackSource};
+
+/// The words used to describe an attack, so that attacks can be described in other languages.
+pub trait AttackLabels {
+    fn range(&self, range: AttackRange) -> &str;
+    fn source(&self, source: AttackSource) -> &str;
+    /// Joins the alternatives, e.g. "or".
+    fn or(&self) -> &str;
+    fn attack(&self) -> &str;
+}
+
+pub struct EnglishLabels;
+
+impl AttackLabels for EnglishLabels {
+    fn range(&self, range: AttackRange) -> &str {
+        match range {
+            AttackRange::Melee => "Melee",
+            AttackRange::Ranged => "Ranged",
+            AttackRange::Magical => "Magical",
+            AttackRange::Area => "Area",
+        }
+    }
+
+    fn source(&self, source: AttackSource) -> &str {
+        match source {
+            AttackSource::Weapon => "Weapon",
+            AttackSource::Spell => "Spell",
+        }
+    }
+
+    fn or(&self) -> &str {
+        "or"
+    }
+
+    fn attack(&self) -> &str {
+        "Attack"
+    }
+}
+
+/// Describes the kinds of an attack, e.g. "Melee or Ranged Weapon Attack" for `mw,rw`.
+///
+/// As in the `{@atk}` renderer, a range or source is only given once, in the last alternative
+/// which has it, so `mw,ms` is "Weapon or Melee Spell Attack".
+pub fn format_attack_kinds<L: AttackLabels>(kinds: &AttackKinds, labels: &L) -> String {
+    let mut ranges = Vec::new();
+    let mut sources = Vec::new();
+    let mut groups = Vec::new();
+
+    for kind in kinds.0.iter().rev() {
+        let mut words = Vec::new();
+        if let Some(range) = kind.range.filter(|range| !ranges.contains(range)) {
+            ranges.push(range);
+            words.push(labels.range(range));
+        }
+        if let Some(source) = kind.source.filter(|source| !sources.contains(source)) {
+            sources.push(source);
+            words.push(labels.source(source));
+        }
+        if !words.is_empty() {
+            groups.push(words.join(" "));
+        }
+    }
+    groups.reverse();
+
+    let or = format!(" {} ", labels.or());
+    match groups.is_empty() {
+        true => labels.attack().to_owned(),
+        false => format!("{} {}", groups.join(&or), labels.attack()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(codes: &str) -> String {
+        format_attack_kinds(&codes.parse().unwrap(), &EnglishLabels)
+    }
+
+    #[test]
+    fn format_english() {
+        assert_eq!(format("mw"), "Melee Weapon Attack");
+        assert_eq!(format("mw,rw"), "Melee or Ranged Weapon Attack");
+        assert_eq!(format("mw,rs"), "Melee Weapon or Ranged Spell Attack");
+        assert_eq!(format("mw,ms"), "Weapon or Melee Spell Attack");
+        assert_eq!(format("ms,rs"), "Melee or Ranged Spell Attack");
+        assert_eq!(format("g"), "Magical Attack");
+    }
+}
@@ -0,0 +1,193 @@
+use super::{Error, Result};
+use crate::entry::kinds::EntryAttackType;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AttackRange {
+    Melee,
+    Ranged,
+    Magical,
+    Area,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AttackSource {
+    Weapon,
+    Spell,
+}
+
+/// One kind of attack, e.g. `mw` for a melee weapon attack. Either half may be left out,
+/// as in `{@atk m}`.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AttackKind {
+    pub range: Option<AttackRange>,
+    pub source: Option<AttackSource>,
+}
+
+/// The alternative kinds of an attack, e.g. `mw,rw` for a weapon which can be thrown.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AttackKinds(pub Vec<AttackKind>);
+
+impl AttackRange {
+    pub fn code(&self) -> char {
+        match self {
+            AttackRange::Melee => 'm',
+            AttackRange::Ranged => 'r',
+            AttackRange::Magical => 'g',
+            AttackRange::Area => 'a',
+        }
+    }
+}
+
+impl AttackSource {
+    pub fn code(&self) -> char {
+        match self {
+            AttackSource::Weapon => 'w',
+            AttackSource::Spell => 's',
+        }
+    }
+}
+
+impl AttackKind {
+    pub fn new(range: AttackRange, source: AttackSource) -> Self {
+        Self {
+            range: Some(range),
+            source: Some(source),
+        }
+    }
+
+    pub fn is_melee(&self) -> bool {
+        self.range == Some(AttackRange::Melee)
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        self.range == Some(AttackRange::Ranged)
+    }
+}
+
+impl FromStr for AttackKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut kind = Self::default();
+
+        for c in s.trim().chars().map(|c| c.to_ascii_lowercase()) {
+            match c {
+                // As on the site, the first code of each half wins
+                'm' | 'r' | 'g' | 'a' if kind.range.is_some() => {}
+                'm' => kind.range = Some(AttackRange::Melee),
+                'r' => kind.range = Some(AttackRange::Ranged),
+                'g' => kind.range = Some(AttackRange::Magical),
+                'a' => kind.range = Some(AttackRange::Area),
+                'w' | 's' if kind.source.is_some() => {}
+                'w' => kind.source = Some(AttackSource::Weapon),
+                's' => kind.source = Some(AttackSource::Spell),
+                c => return Err(Error::UnknownCode(c)),
+            }
+        }
+
+        if kind == Self::default() {
+            Err(Error::Empty)
+        } else {
+            Ok(kind)
+        }
+    }
+}
+
+impl fmt::Display for AttackKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(range) = self.range {
+            write!(f, "{}", range.code())?;
+        }
+        if let Some(source) = self.source {
+            write!(f, "{}", source.code())?;
+        }
+        Ok(())
+    }
+}
+
+impl AttackKinds {
+    /// The kinds given by the arguments of an `{@atk}` tag.
+    pub fn from_tag(args: &[&str]) -> Result<Self> {
+        args.first().ok_or(Error::Empty)?.parse()
+    }
+
+    pub fn is_melee(&self) -> bool {
+        self.0.iter().any(AttackKind::is_melee)
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        self.0.iter().any(AttackKind::is_ranged)
+    }
+}
+
+impl FromStr for AttackKinds {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let kinds = s
+            .split(',')
+            .filter(|part| !part.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<_>>>()?;
+
+        if kinds.is_empty() {
+            Err(Error::Empty)
+        } else {
+            Ok(Self(kinds))
+        }
+    }
+}
+
+impl fmt::Display for AttackKinds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let codes = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "{}", codes.join(","))
+    }
+}
+
+impl From<EntryAttackType> for AttackKind {
+    fn from(value: EntryAttackType) -> Self {
+        match value {
+            EntryAttackType::MW => Self::new(AttackRange::Melee, AttackSource::Weapon),
+            EntryAttackType::RW => Self::new(AttackRange::Ranged, AttackSource::Weapon),
+        }
+    }
+}
+
+impl From<EntryAttackType> for AttackKinds {
+    fn from(value: EntryAttackType) -> Self {
+        Self(vec![value.into()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_attack_kinds() {
+        let kinds = "mw,RS".parse::<AttackKinds>().unwrap();
+        assert_eq!(
+            kinds.0,
+            vec![
+                AttackKind::new(AttackRange::Melee, AttackSource::Weapon),
+                AttackKind::new(AttackRange::Ranged, AttackSource::Spell),
+            ]
+        );
+        assert!(kinds.is_melee() && kinds.is_ranged());
+        assert_eq!(kinds.to_string(), "mw,rs");
+
+        assert_eq!(
+            "m".parse::<AttackKind>(),
+            Ok(AttackKind {
+                range: Some(AttackRange::Melee),
+                source: None
+            })
+        );
+        assert_eq!("mx".parse::<AttackKinds>(), Err(Error::UnknownCode('x')));
+        assert_eq!(" , ".parse::<AttackKinds>(), Err(Error::Empty));
+        assert_eq!(AttackKinds::from(EntryAttackType::RW).to_string(), "rw");
+    }
+}
@@ -0,0 +1,276 @@
+//! Creatures from the bestiary, e.g. `bestiary/bestiary-mm.json`, and the numbers behind them.
+
+mod cr;
+mod dpr;
+mod error;
+mod scale;
+mod summon;
+
+pub use cr::{
+    cr_index, cr_row, Cr, CrEstimate, CrInputs, CrRow, Resilience, TraitAdjustment, CR_TABLE,
+};
+pub use dpr::{
+    average_damage_per_round, damage_per_round, ActionEstimate, DamagePerRound, Target,
+    DEFAULT_ROUNDS,
+};
+pub use error::{Error, Result};
+pub use scale::scale_to_cr;
+pub use summon::{summon, Summoner};
+
+use crate::entry::Entries;
+use crate::string::{tokenize, Lexeme};
+use crate::util::ability::Ability;
+use crate::util::conditions::ConditionImmunityArray;
+use crate::util::damage_types::{DamageImmunityArray, DamageResistArray, DamageVulnerabilityArray};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::skip_serializing_none;
+use std::collections::BTreeMap;
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Creature<'a> {
+    pub name: &'a str,
+    pub source: &'a str,
+    pub page: Option<i64>,
+    pub size: Option<Vec<&'a str>>,
+    /// Either a string such as `"dragon"` or an object with tags.
+    #[serde(rename = "type")]
+    pub creature_type: Option<Value>,
+    pub alignment: Option<Value>,
+    #[serde(borrow)]
+    pub ac: Option<Vec<ArmorClass<'a>>>,
+    pub hp: Option<HitPoints<'a>>,
+    pub speed: Option<Value>,
+    pub str: Option<i64>,
+    pub dex: Option<i64>,
+    pub con: Option<i64>,
+    pub int: Option<i64>,
+    pub wis: Option<i64>,
+    pub cha: Option<i64>,
+    /// Saving throw bonuses by ability, e.g. `"dex": "+5"`.
+    pub save: Option<BTreeMap<&'a str, &'a str>>,
+    pub skill: Option<BTreeMap<&'a str, Value>>,
+    pub senses: Option<Vec<&'a str>>,
+    pub passive: Option<Value>,
+    pub languages: Option<Vec<&'a str>>,
+    pub cr: Option<ChallengeRating<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub immune: DamageImmunityArray<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resist: DamageResistArray<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vulnerable: DamageVulnerabilityArray<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition_immune: ConditionImmunityArray<'a>,
+    pub spellcasting: Option<Vec<Value>>,
+    #[serde(rename = "trait")]
+    pub traits: Option<Vec<CreatureAction<'a>>>,
+    pub action: Option<Vec<CreatureAction<'a>>>,
+    pub bonus: Option<Vec<CreatureAction<'a>>>,
+    pub reaction: Option<Vec<CreatureAction<'a>>>,
+    pub legendary: Option<Vec<CreatureAction<'a>>>,
+    /// The number of legendary actions per round, 3 if not given.
+    pub legendary_actions: Option<i64>,
+    pub mythic: Option<Vec<CreatureAction<'a>>>,
+    /// The spell which summons the creature, e.g. `Summon Beast|TCE`.
+    pub summoned_by_spell: Option<&'a str>,
+    /// The lowest level at which the spell can be cast.
+    pub summoned_by_spell_level: Option<u8>,
+    /// Properties not modelled here, kept so that they survive a round trip.
+    #[serde(flatten)]
+    pub extra: BTreeMap<&'a str, Value>,
+}
+
+/// A trait, action, bonus action, reaction or legendary action.
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreatureAction<'a> {
+    /// May contain tags, e.g. `Fire Breath {@recharge 5}`.
+    pub name: Option<&'a str>,
+    #[serde(borrow)]
+    pub entries: Entries<'a>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ArmorClass<'a> {
+    Simple(i64),
+    Detailed {
+        ac: i64,
+        #[serde(borrow)]
+        from: Option<Vec<&'a str>>,
+        condition: Option<&'a str>,
+        braces: Option<bool>,
+    },
+    Special {
+        special: &'a str,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HitPoints<'a> {
+    Rolled { average: i64, formula: &'a str },
+    Special { special: &'a str },
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChallengeRating<'a> {
+    Simple(&'a str),
+    Detailed {
+        cr: &'a str,
+        lair: Option<&'a str>,
+        coven: Option<&'a str>,
+        xp: Option<i64>,
+    },
+}
+
+impl<'a> Creature<'a> {
+    pub fn ability_score(&self, ability: Ability) -> Option<i64> {
+        match ability {
+            Ability::Strength => self.str,
+            Ability::Dexterity => self.dex,
+            Ability::Constitution => self.con,
+            Ability::Intelligence => self.int,
+            Ability::Wisdom => self.wis,
+            Ability::Charisma => self.cha,
+        }
+    }
+
+    /// The ability modifier, treating a missing score as 10.
+    pub fn ability_modifier(&self, ability: Ability) -> i64 {
+        (self.ability_score(ability).unwrap_or(10) - 10).div_euclid(2)
+    }
+
+    /// The saving throw bonus, or the ability modifier if the creature is not proficient.
+    pub fn save_bonus(&self, ability: Ability) -> i64 {
+        self.save
+            .as_ref()
+            .and_then(|saves| saves.get(ability.name_abbrev()))
+            .and_then(|bonus| bonus.trim().trim_start_matches('+').parse().ok())
+            .unwrap_or_else(|| self.ability_modifier(ability))
+    }
+
+    /// The first numeric armor class.
+    pub fn armor_class(&self) -> Option<i64> {
+        self.ac.iter().flatten().find_map(|ac| match ac {
+            ArmorClass::Simple(ac) | ArmorClass::Detailed { ac, .. } => Some(*ac),
+            ArmorClass::Special { .. } => None,
+        })
+    }
+
+    pub fn average_hp(&self) -> Option<i64> {
+        match self.hp {
+            Some(HitPoints::Rolled { average, .. }) => Some(average),
+            _ => None,
+        }
+    }
+
+    /// The challenge rating as a number, e.g. `0.5` for `1/2`.
+    pub fn challenge_rating(&self) -> Option<f64> {
+        self.cr.as_ref().and_then(ChallengeRating::value)
+    }
+
+    pub fn legendary_action_count(&self) -> i64 {
+        match &self.legendary {
+            Some(legendary) if !legendary.is_empty() => self.legendary_actions.unwrap_or(3),
+            _ => 0,
+        }
+    }
+}
+
+impl<'a> ChallengeRating<'a> {
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            ChallengeRating::Simple(cr) | ChallengeRating::Detailed { cr, .. } => cr,
+        }
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        parse_cr(self.as_str())
+    }
+}
+
+/// Parses a challenge rating such as `5`, `1/2` or `1/8`.
+pub fn parse_cr(cr: &str) -> Option<f64> {
+    match cr.trim().split_once('/') {
+        Some((n, d)) => Some(n.trim().parse::<f64>().ok()? / d.trim().parse::<f64>().ok()?),
+        None => cr.trim().parse().ok(),
+    }
+}
+
+impl<'a> CreatureAction<'a> {
+    /// The name without tags, e.g. `Fire Breath` for `Fire Breath {@recharge 5}`.
+    pub fn plain_name(&self) -> String {
+        let name = self.name.unwrap_or_default();
+        match tokenize(name) {
+            Ok(lexemes) => lexemes
+                .filter_map(|lexeme| match lexeme {
+                    Lexeme::Text(text) => Some(text),
+                    Lexeme::Tag(_) => None,
+                })
+                .collect::<String>()
+                .trim()
+                .to_owned(),
+            Err(_) => name.to_owned(),
+        }
+    }
+
+    /// The arguments of the first tag with the given name in the action's name.
+    pub fn name_tag(&self, tag: &str) -> Option<Vec<&'a str>> {
+        tokenize(self.name?).ok()?.find_map(|lexeme| match lexeme {
+            Lexeme::Tag(t) if t.name == tag => Some(t.args),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) const GOBLIN_BOSS: &str = r#"{
+        "name": "Goblin Boss",
+        "source": "MM",
+        "page": 166,
+        "size": ["S"],
+        "type": {"type": "humanoid", "tags": ["goblinoid"]},
+        "alignment": ["N", "E"],
+        "ac": [{"ac": 17, "from": ["{@item chain shirt|phb}", "{@item shield|phb}"]}],
+        "hp": {"average": 21, "formula": "6d6"},
+        "speed": {"walk": 30},
+        "str": 10, "dex": 14, "con": 10, "int": 10, "wis": 8, "cha": 10,
+        "save": {"dex": "+4"},
+        "cr": "1",
+        "action": [
+            {"name": "Multiattack", "entries": ["The goblin makes two attacks with its scimitar."]},
+            {"name": "Scimitar", "entries": ["{@atk mw} {@hit 4} to hit, reach 5 ft., one target. {@h}5 ({@damage 1d6 + 2}) slashing damage."]},
+            {"name": "Javelin", "entries": ["{@atk mw,rw} {@hit 2} to hit, reach 5 ft. or range 30/120 ft., one target. {@h}3 ({@damage 1d6}) piercing damage."]}
+        ],
+        "reaction": [{"name": "Redirect Attack", "entries": ["When a creature the goblin can see targets it..."]}],
+        "environment": ["forest"]
+    }"#;
+
+    #[test]
+    fn serde_creature() {
+        let creature: Creature = serde_json::from_str(GOBLIN_BOSS).unwrap();
+        assert_eq!(creature.armor_class(), Some(17));
+        assert_eq!(creature.average_hp(), Some(21));
+        assert_eq!(creature.challenge_rating(), Some(1.0));
+        assert_eq!(creature.save_bonus(Ability::Dexterity), 4);
+        assert_eq!(creature.save_bonus(Ability::Wisdom), -1);
+        assert_eq!(creature.legendary_action_count(), 0);
+        assert!(creature.extra.contains_key("environment"));
+
+        let json = serde_json::to_value(&creature).unwrap();
+        let expected: Value = serde_json::from_str(GOBLIN_BOSS).unwrap();
+        assert_eq!(json, expected);
+
+        assert_eq!(parse_cr("1/8"), Some(0.125));
+    }
+}
@@ -0,0 +1,491 @@
+use super::dpr::{average_damage_per_round, save_dc, DEFAULT_ROUNDS};
+use super::{parse_cr, Creature};
+use crate::attack::analyze_attack;
+use crate::util::damage_types::{DamageImmunity, DamageResistance, DamageType};
+use enumflags2::BitFlags;
+use std::fmt;
+
+/// A row of the DMG's Monster Statistics by Challenge Rating table.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrRow {
+    pub cr: f64,
+    pub proficiency_bonus: i64,
+    pub ac: i64,
+    pub hp: (i64, i64),
+    pub attack_bonus: i64,
+    pub dpr: (i64, i64),
+    pub save_dc: i64,
+}
+
+const fn row(
+    cr: f64,
+    prof: i64,
+    ac: i64,
+    hp: (i64, i64),
+    atk: i64,
+    dpr: (i64, i64),
+    dc: i64,
+) -> CrRow {
+    CrRow {
+        cr,
+        proficiency_bonus: prof,
+        ac,
+        hp,
+        attack_bonus: atk,
+        dpr,
+        save_dc: dc,
+    }
+}
+
+pub const CR_TABLE: [CrRow; 34] = [
+    row(0.0, 2, 13, (1, 6), 3, (0, 1), 13),
+    row(0.125, 2, 13, (7, 35), 3, (2, 3), 13),
+    row(0.25, 2, 13, (36, 49), 3, (4, 5), 13),
+    row(0.5, 2, 13, (50, 70), 3, (6, 8), 13),
+    row(1.0, 2, 13, (71, 85), 3, (9, 14), 13),
+    row(2.0, 2, 13, (86, 100), 3, (15, 20), 13),
+    row(3.0, 2, 13, (101, 115), 4, (21, 26), 13),
+    row(4.0, 2, 14, (116, 130), 5, (27, 32), 14),
+    row(5.0, 3, 15, (131, 145), 6, (33, 38), 15),
+    row(6.0, 3, 15, (146, 160), 6, (39, 44), 15),
+    row(7.0, 3, 15, (161, 175), 6, (45, 50), 15),
+    row(8.0, 3, 16, (176, 190), 7, (51, 56), 16),
+    row(9.0, 4, 16, (191, 205), 7, (57, 62), 16),
+    row(10.0, 4, 17, (206, 220), 7, (63, 68), 16),
+    row(11.0, 4, 17, (221, 235), 8, (69, 74), 17),
+    row(12.0, 4, 17, (236, 250), 8, (75, 80), 17),
+    row(13.0, 5, 18, (251, 265), 8, (81, 86), 18),
+    row(14.0, 5, 18, (266, 280), 8, (87, 92), 18),
+    row(15.0, 5, 18, (281, 295), 8, (93, 98), 18),
+    row(16.0, 5, 18, (296, 310), 9, (99, 104), 18),
+    row(17.0, 6, 19, (311, 325), 10, (105, 110), 19),
+    row(18.0, 6, 19, (326, 340), 10, (111, 116), 19),
+    row(19.0, 6, 19, (341, 355), 10, (117, 122), 19),
+    row(20.0, 6, 19, (356, 400), 10, (123, 140), 19),
+    row(21.0, 7, 19, (401, 445), 11, (141, 158), 20),
+    row(22.0, 7, 19, (446, 490), 11, (159, 176), 20),
+    row(23.0, 7, 19, (491, 535), 11, (177, 194), 20),
+    row(24.0, 7, 19, (536, 580), 12, (195, 212), 21),
+    row(25.0, 8, 19, (581, 625), 12, (213, 230), 21),
+    row(26.0, 8, 19, (626, 670), 12, (231, 248), 21),
+    row(27.0, 8, 19, (671, 715), 13, (249, 266), 22),
+    row(28.0, 8, 19, (716, 760), 13, (267, 284), 22),
+    row(29.0, 9, 19, (761, 805), 13, (285, 302), 22),
+    row(30.0, 9, 19, (806, 850), 14, (303, 320), 23),
+];
+
+/// How well a creature shrugs off weapon damage, which raises its effective hit points.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Resilience {
+    None,
+    /// Resistant to bludgeoning, piercing and slashing damage.
+    Resistant,
+    /// Immune to bludgeoning, piercing and slashing damage.
+    Immune,
+}
+
+/// The effect of a trait on a creature's effective statistics, from the DMG's Monster Features table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitAdjustment {
+    pub name: String,
+    pub ac: i64,
+    pub hp: f64,
+    pub attack_bonus: i64,
+    pub dpr: f64,
+}
+
+/// The statistics which determine a challenge rating. These can be read from a [`Creature`]
+/// or filled in by hand, e.g. for homebrew which is still being designed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrInputs {
+    pub hp: f64,
+    pub ac: i64,
+    pub dpr: f64,
+    pub attack_bonus: Option<i64>,
+    pub save_dc: Option<i64>,
+    pub resilience: Resilience,
+    pub saving_throw_proficiencies: usize,
+    pub traits: Vec<TraitAdjustment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrEstimate {
+    pub defensive: f64,
+    pub offensive: f64,
+    /// The average of the defensive and offensive challenge ratings.
+    pub cr: f64,
+    pub printed: Option<f64>,
+    /// How the challenge rating was derived, one step per line.
+    pub steps: Vec<String>,
+}
+
+impl TraitAdjustment {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ac: 0,
+            hp: 0.0,
+            attack_bonus: 0,
+            dpr: 0.0,
+        }
+    }
+
+    /// The adjustment for a trait from the DMG's Monster Features table, by name.
+    /// Legendary Resistance depends on the expected challenge rating and the uses per day.
+    pub fn known(name: &str, expected_cr: f64) -> Option<Self> {
+        let lower = name.to_lowercase();
+        let mut adjustment = Self::new(name);
+
+        match lower.as_str() {
+            "avoidance" | "parry" | "stench" => adjustment.ac = 1,
+            "magic resistance" | "superior invisibility" => adjustment.ac = 2,
+            "pack tactics" => adjustment.attack_bonus = 1,
+            "blood frenzy" => adjustment.attack_bonus = 4,
+            "nimble escape" => {
+                adjustment.ac = 4;
+                adjustment.attack_bonus = 4;
+            }
+            "aggressive" => adjustment.dpr = 2.0,
+            _ if lower.starts_with("legendary resistance") => {
+                let uses = lower
+                    .split(|c: char| !c.is_ascii_digit())
+                    .find_map(|n| n.parse::<u32>().ok())
+                    .unwrap_or(3);
+                let per_use = match expected_cr {
+                    cr if cr <= 4.0 => 10.0,
+                    cr if cr <= 10.0 => 20.0,
+                    _ => 30.0,
+                };
+                adjustment.hp = per_use * f64::from(uses);
+            }
+            _ => return None,
+        }
+
+        Some(adjustment)
+    }
+}
+
+impl CrInputs {
+    /// Reads the inputs from a statblock. Damage is averaged over the first three rounds,
+    /// assuming every attack hits, and the best attack bonus and save DC are used.
+    pub fn from_creature(creature: &Creature) -> Self {
+        let hp = creature.average_hp().unwrap_or_default() as f64;
+        let actions = creature
+            .action
+            .iter()
+            .chain(creature.bonus.iter())
+            .chain(creature.legendary.iter())
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let attack_bonus = actions
+            .iter()
+            .filter_map(|action| analyze_attack(&action.entries)?.to_hit)
+            .max();
+        let save_dc = actions
+            .iter()
+            .filter_map(|action| save_dc(&action.entries))
+            .max();
+
+        let expected_cr = CR_TABLE[hp_index(hp)].cr;
+        let traits = creature
+            .traits
+            .iter()
+            .flatten()
+            .filter_map(|t| TraitAdjustment::known(&t.plain_name(), expected_cr))
+            .collect();
+
+        Self {
+            hp,
+            ac: creature.armor_class().unwrap_or(10),
+            dpr: average_damage_per_round(creature, DEFAULT_ROUNDS).total,
+            attack_bonus,
+            save_dc,
+            resilience: resilience(creature),
+            saving_throw_proficiencies: creature.save.as_ref().map_or(0, |saves| saves.len()),
+            traits,
+        }
+    }
+
+    /// Follows the DMG's steps for creating a quick monster, in reverse.
+    pub fn estimate(&self) -> CrEstimate {
+        let mut steps = Vec::new();
+
+        // Defensive challenge rating
+        let expected_cr = CR_TABLE[hp_index(self.hp)].cr;
+        let multiplier = hp_multiplier(self.resilience, expected_cr);
+        let trait_hp = self.traits.iter().fold(0.0, |hp, t| hp + t.hp);
+        let effective_hp = self.hp * multiplier + trait_hp;
+        steps.push(format!(
+            "Effective HP {} = {} HP x {} for {:?} + {} from traits",
+            effective_hp, self.hp, multiplier, self.resilience, trait_hp
+        ));
+
+        let save_ac = match self.saving_throw_proficiencies {
+            0..=2 => 0,
+            3..=4 => 2,
+            _ => 4,
+        };
+        let trait_ac = self.traits.iter().map(|t| t.ac).sum::<i64>();
+        let effective_ac = self.ac + save_ac + trait_ac;
+        steps.push(format!(
+            "Effective AC {} = {} AC + {} for {} saving throw proficiencies + {} from traits",
+            effective_ac, self.ac, save_ac, self.saving_throw_proficiencies, trait_ac
+        ));
+
+        let index = hp_index(effective_hp);
+        let row = CR_TABLE[index];
+        let adjustment = (effective_ac - row.ac) / 2;
+        let defensive = step(index, adjustment);
+        steps.push(format!(
+            "Defensive CR {}: HP suggests CR {} with AC {}, adjusted by {} for AC {}",
+            Cr(CR_TABLE[defensive].cr),
+            Cr(row.cr),
+            row.ac,
+            adjustment,
+            effective_ac
+        ));
+
+        // Offensive challenge rating
+        let trait_dpr = self.traits.iter().fold(0.0, |dpr, t| dpr + t.dpr);
+        let effective_dpr = self.dpr + trait_dpr;
+        let index = dpr_index(effective_dpr);
+        let row = CR_TABLE[index];
+        steps.push(format!(
+            "Effective DPR {:.1} = {:.1} DPR + {} from traits",
+            effective_dpr, self.dpr, trait_dpr
+        ));
+
+        let trait_attack = self.traits.iter().map(|t| t.attack_bonus).sum::<i64>();
+        let (adjustment, compared) = match (self.attack_bonus, self.save_dc) {
+            (Some(bonus), _) => {
+                let bonus = bonus + trait_attack;
+                (
+                    (bonus - row.attack_bonus) / 2,
+                    format!("attack bonus {:+}", bonus),
+                )
+            }
+            (None, Some(dc)) => ((dc - row.save_dc) / 2, format!("save DC {}", dc)),
+            (None, None) => (0, "no attack bonus or save DC".to_owned()),
+        };
+        let offensive = step(index, adjustment);
+        steps.push(format!(
+            "Offensive CR {}: DPR suggests CR {} with attack bonus {:+} and save DC {}, adjusted by {} for {}",
+            Cr(CR_TABLE[offensive].cr),
+            Cr(row.cr),
+            row.attack_bonus,
+            row.save_dc,
+            adjustment,
+            compared
+        ));
+
+        let (defensive, offensive) = (CR_TABLE[defensive].cr, CR_TABLE[offensive].cr);
+        let cr = nearest_cr((defensive + offensive) / 2.0);
+        steps.push(format!(
+            "CR {}: the average of CR {} and CR {}",
+            Cr(cr),
+            Cr(defensive),
+            Cr(offensive)
+        ));
+
+        CrEstimate {
+            defensive,
+            offensive,
+            cr,
+            printed: None,
+            steps,
+        }
+    }
+}
+
+impl CrEstimate {
+    /// Estimates the challenge rating of a statblock and compares it with the printed one.
+    pub fn of_creature(creature: &Creature) -> Self {
+        let mut estimate = CrInputs::from_creature(creature).estimate();
+        estimate.printed = creature.challenge_rating();
+        if let Some(printed) = estimate.printed {
+            estimate.steps.push(format!(
+                "Printed CR {}, {:+} rows from the estimate",
+                Cr(printed),
+                estimate.difference().unwrap_or_default()
+            ));
+        }
+        estimate
+    }
+
+    /// The number of rows of the CR table between the printed and estimated challenge ratings,
+    /// positive if the creature is printed with a higher one than estimated.
+    pub fn difference(&self) -> Option<i64> {
+        let printed = cr_index(self.printed?)?;
+        let estimated = cr_index(self.cr)?;
+        Some(printed as i64 - estimated as i64)
+    }
+}
+
+/// Displays a challenge rating the way statblocks do, e.g. `1/2`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cr(pub f64);
+
+impl fmt::Display for Cr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            0.125 => write!(f, "1/8"),
+            0.25 => write!(f, "1/4"),
+            0.5 => write!(f, "1/2"),
+            cr => write!(f, "{}", cr),
+        }
+    }
+}
+
+/// The index of a challenge rating in [`CR_TABLE`], e.g. from [`parse_cr`].
+pub fn cr_index(cr: f64) -> Option<usize> {
+    CR_TABLE.iter().position(|row| row.cr == cr)
+}
+
+/// The row for a challenge rating given as text, e.g. `1/4`.
+pub fn cr_row(cr: &str) -> Option<&'static CrRow> {
+    CR_TABLE.get(cr_index(parse_cr(cr)?)?)
+}
+
+fn hp_index(hp: f64) -> usize {
+    let hp = hp.round() as i64;
+    CR_TABLE
+        .iter()
+        .position(|row| hp <= row.hp.1)
+        .unwrap_or(CR_TABLE.len() - 1)
+}
+
+fn dpr_index(dpr: f64) -> usize {
+    let dpr = dpr.round() as i64;
+    CR_TABLE
+        .iter()
+        .position(|row| dpr <= row.dpr.1)
+        .unwrap_or(CR_TABLE.len() - 1)
+}
+
+fn step(index: usize, by: i64) -> usize {
+    (index as i64 + by).clamp(0, CR_TABLE.len() as i64 - 1) as usize
+}
+
+fn nearest_cr(value: f64) -> f64 {
+    CR_TABLE.iter().map(|row| row.cr).fold(0.0, |best, cr| {
+        // Halfway between two challenge ratings rounds up
+        if (cr - value).abs() <= (best - value).abs() {
+            cr
+        } else {
+            best
+        }
+    })
+}
+
+fn hp_multiplier(resilience: Resilience, expected_cr: f64) -> f64 {
+    let (resistant, immune) = match expected_cr {
+        cr if cr <= 4.0 => (2.0, 2.0),
+        cr if cr <= 10.0 => (1.5, 2.0),
+        cr if cr <= 16.0 => (1.25, 1.5),
+        _ => (1.0, 1.25),
+    };
+
+    match resilience {
+        Resilience::None => 1.0,
+        Resilience::Resistant => resistant,
+        Resilience::Immune => immune,
+    }
+}
+
+const WEAPON_DAMAGE: [DamageType; 3] = [
+    DamageType::Bludgeoning,
+    DamageType::Piercing,
+    DamageType::Slashing,
+];
+
+fn resilience(creature: &Creature) -> Resilience {
+    fn immune(list: &[DamageImmunity], out: &mut BitFlags<DamageType>) {
+        for item in list {
+            match item {
+                DamageImmunity::Simple(t) => *out |= *t,
+                DamageImmunity::Annotated {
+                    immune: Some(inner),
+                    ..
+                } => immune(inner, out),
+                _ => {}
+            }
+        }
+    }
+    fn resist(list: &[DamageResistance], out: &mut BitFlags<DamageType>) {
+        for item in list {
+            match item {
+                DamageResistance::Simple(t) => *out |= *t,
+                DamageResistance::Annotated {
+                    resist: Some(inner),
+                    ..
+                } => resist(inner, out),
+                _ => {}
+            }
+        }
+    }
+
+    let mut immunities = BitFlags::empty();
+    immune(
+        creature.immune.as_deref().unwrap_or_default(),
+        &mut immunities,
+    );
+    let mut resistances = BitFlags::empty();
+    resist(
+        creature.resist.as_deref().unwrap_or_default(),
+        &mut resistances,
+    );
+
+    let weapons = WEAPON_DAMAGE.iter().copied().collect::<BitFlags<_>>();
+    if immunities.contains(weapons) {
+        Resilience::Immune
+    } else if (immunities | resistances).contains(weapons) {
+        Resilience::Resistant
+    } else {
+        Resilience::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::GOBLIN_BOSS;
+    use super::*;
+
+    #[test]
+    fn estimate_inputs() {
+        let inputs = CrInputs {
+            hp: 60.0,
+            ac: 17,
+            dpr: 24.0,
+            attack_bonus: Some(4),
+            save_dc: None,
+            resilience: Resilience::Resistant,
+            saving_throw_proficiencies: 0,
+            traits: vec![TraitAdjustment::known("Magic Resistance", 1.0).unwrap()],
+        };
+        let estimate = inputs.estimate();
+
+        // 60 HP is CR 1/2, doubled to 120 for resistances: CR 4 with AC 14, +2 for AC 19
+        assert_eq!(estimate.defensive, 6.0);
+        // 24 DPR is CR 3 with +4 to hit
+        assert_eq!(estimate.offensive, 3.0);
+        assert_eq!(estimate.cr, 5.0);
+        assert_eq!(estimate.steps.len(), 6);
+    }
+
+    #[test]
+    fn estimate_creature() {
+        let creature: Creature = serde_json::from_str(GOBLIN_BOSS).unwrap();
+        let estimate = CrEstimate::of_creature(&creature);
+
+        // 21 HP is CR 1/8 with AC 13, +2 for AC 17
+        assert_eq!(estimate.defensive, 0.5);
+        // Two scimitar attacks deal 11 damage, CR 1 with +3 to hit
+        assert_eq!(estimate.offensive, 1.0);
+        assert_eq!(estimate.cr, 1.0);
+        assert_eq!(estimate.printed, Some(1.0));
+        assert_eq!(estimate.difference(), Some(0));
+        assert_eq!(Cr(estimate.defensive).to_string(), "1/2");
+        assert_eq!(cr_row("1/2").map(|row| row.hp), Some((50, 70)));
+    }
+}
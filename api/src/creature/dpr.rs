@@ -0,0 +1,390 @@
+use super::{Creature, CreatureAction};
+use crate::attack::{
+    analyze_attack, analyze_entry_attack, damage_rolls, AttackProfile, DamageRoll,
+};
+use crate::dice::{chance_to_crit, chance_to_hit, chance_to_succeed, Advantage};
+use crate::entry::{Entry, EntryKind};
+use crate::mechanics::Recharge;
+use crate::string::{tokenize, Lexeme};
+
+/// The number of rounds an encounter is assumed to last when averaging recharge abilities,
+/// as suggested by the DMG.
+pub const DEFAULT_ROUNDS: usize = 3;
+
+/// What a creature's attacks and saving throws are rolled against.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Target {
+    pub ac: i64,
+    pub save_bonus: i64,
+    /// The number of rounds over which recharge abilities are averaged.
+    pub rounds: usize,
+}
+
+/// The expected damage of a single action against a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionEstimate {
+    pub name: String,
+    pub expected: f64,
+    pub recharge: Option<Recharge>,
+}
+
+/// The expected damage a creature deals each round.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamagePerRound {
+    /// Every action and bonus action with its expected damage when used.
+    pub actions: Vec<ActionEstimate>,
+    /// The expected damage of the multiattack, if the creature has one.
+    pub multiattack: Option<f64>,
+    /// The expected damage of the best choice of action each round.
+    pub action: f64,
+    pub bonus_action: f64,
+    pub legendary: f64,
+    pub total: f64,
+}
+
+impl Target {
+    pub fn new(ac: i64, save_bonus: i64) -> Self {
+        Self {
+            ac,
+            save_bonus,
+            rounds: DEFAULT_ROUNDS,
+        }
+    }
+}
+
+/// Estimates the damage a creature deals each round against a target, using its best action
+/// (or multiattack), its best bonus action and its legendary actions. Recharge abilities are
+/// used whenever available, and their damage is averaged over the target's rounds.
+pub fn damage_per_round(creature: &Creature, target: &Target) -> DamagePerRound {
+    per_round(creature, Some(target), target.rounds)
+}
+
+/// Like [`damage_per_round`], but assuming every attack hits and every saving throw fails,
+/// as the DMG does when calculating challenge ratings.
+pub fn average_damage_per_round(creature: &Creature, rounds: usize) -> DamagePerRound {
+    per_round(creature, None, rounds)
+}
+
+fn per_round(creature: &Creature, target: Option<&Target>, rounds: usize) -> DamagePerRound {
+    let actions = creature.action.as_deref().unwrap_or_default();
+    let bonus = creature.bonus.as_deref().unwrap_or_default();
+
+    let estimates = actions
+        .iter()
+        .filter(|action| !is_multiattack(action))
+        .map(|action| estimate(action, target))
+        .collect::<Vec<_>>();
+    let bonus_estimates = bonus
+        .iter()
+        .map(|action| estimate(action, target))
+        .collect::<Vec<_>>();
+
+    let multiattack = actions
+        .iter()
+        .find(|action| is_multiattack(action))
+        .map(|action| multiattack_damage(action, &estimates));
+
+    let action = best_per_round(&estimates, multiattack, rounds);
+    let bonus_action = best_per_round(&bonus_estimates, None, rounds);
+    let legendary = legendary_damage(creature, &estimates, target);
+
+    DamagePerRound {
+        actions: estimates.into_iter().chain(bonus_estimates).collect(),
+        multiattack,
+        action,
+        bonus_action,
+        legendary,
+        total: action + bonus_action + legendary,
+    }
+}
+
+fn is_multiattack(action: &CreatureAction) -> bool {
+    action.plain_name().eq_ignore_ascii_case("multiattack")
+}
+
+fn estimate(action: &CreatureAction, target: Option<&Target>) -> ActionEstimate {
+    let recharge = action
+        .name_tag("recharge")
+        .and_then(|args| Recharge::from_tag(&args).ok());
+
+    ActionEstimate {
+        name: action.plain_name(),
+        expected: expected_damage(&action.entries, target),
+        recharge,
+    }
+}
+
+/// The expected damage of entries which make an attack, force a saving throw, or simply deal damage.
+/// Without a target, attacks always hit and saving throws always fail.
+fn expected_damage(entries: &[Entry], target: Option<&Target>) -> f64 {
+    let attack = entries.iter().find_map(|entry| match entry {
+        Entry::Entry(EntryKind::Attack(attack)) => Some(analyze_entry_attack(attack)),
+        _ => None,
+    });
+
+    match (attack.or_else(|| analyze_attack(entries)), target) {
+        (Some(AttackProfile { damage, .. }), None) => mean(&damage, 1),
+        (
+            Some(AttackProfile {
+                to_hit: Some(to_hit),
+                damage,
+                ..
+            }),
+            Some(target),
+        ) => {
+            let hit = chance_to_hit(to_hit, target.ac, Advantage::Normal);
+            let crit = chance_to_crit(Advantage::Normal);
+            (hit - crit) * mean(&damage, 1) + crit * mean(&damage, 2)
+        }
+        _ => {
+            let damage = damage_rolls(entries);
+            match (save_dc(entries), target) {
+                (Some(dc), Some(target)) => {
+                    let success = chance_to_succeed(target.save_bonus, dc, Advantage::Normal);
+                    let on_success = if halved_on_success(entries) { 0.5 } else { 0.0 };
+                    mean(&damage, 1) * ((1.0 - success) + success * on_success)
+                }
+                _ => mean(&damage, 1),
+            }
+        }
+    }
+}
+
+/// The mean total of the damage rolls, with the dice multiplied e.g. for critical hits.
+fn mean(damage: &[DamageRoll], dice_factor: i64) -> f64 {
+    damage
+        .iter()
+        .flat_map(|roll| &roll.formula.parts)
+        .filter_map(|part| part.multiply_dice(dice_factor).mean().ok())
+        .sum()
+}
+
+pub(super) fn save_dc(entries: &[Entry]) -> Option<i64> {
+    tags(entries, "dc").find_map(|args| args.first()?.trim().parse().ok())
+}
+
+fn halved_on_success(entries: &[Entry]) -> bool {
+    entries
+        .iter()
+        .flat_map(Entry::strings)
+        .any(|s| s.contains("half as much damage") || s.contains("half damage"))
+}
+
+/// The arguments of every tag with the given name in the entries.
+fn tags<'e>(entries: &'e [Entry], name: &'e str) -> impl Iterator<Item = Vec<String>> + 'e {
+    entries.iter().flat_map(Entry::strings).flat_map(move |s| {
+        tokenize(&s)
+            .map(|lexemes| {
+                lexemes
+                    .filter_map(|lexeme| match lexeme {
+                        Lexeme::Tag(tag) if tag.name == name => {
+                            Some(tag.args.iter().map(|a| (*a).to_owned()).collect())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    })
+}
+
+/// Reads which attacks a multiattack makes, e.g. "one with its bite and two with its claws",
+/// falling back to the best attack if it only says how many attacks are made.
+fn multiattack_damage(action: &CreatureAction, estimates: &[ActionEstimate]) -> f64 {
+    let text = action
+        .entries
+        .iter()
+        .flat_map(Entry::strings)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    let with = regex!(
+        r"\b(one|two|three|four|five|six|\d+)(?: [a-z]+)?(?: attacks?)? with its ([a-z' -]+?)(?:,|\.| and\b| or\b|$)"
+    );
+    let mut total = 0.0;
+    let mut matched = false;
+    for captures in with.captures_iter(&text) {
+        let count = number_word(&captures[1]).unwrap_or(1);
+        if let Some(estimate) = find_estimate(&captures[2], estimates) {
+            total += count as f64 * estimate.expected;
+            matched = true;
+        }
+    }
+    if matched {
+        return total;
+    }
+
+    let count = regex!(r"makes (one|two|three|four|five|six|\d+)\b")
+        .captures(&text)
+        .and_then(|captures| number_word(&captures[1]))
+        .unwrap_or(1);
+    let best = estimates
+        .iter()
+        .filter(|estimate| estimate.recharge.is_none())
+        .map(|estimate| estimate.expected)
+        .fold(0.0, f64::max);
+    count as f64 * best
+}
+
+/// Finds the action named in text such as "claws" or "longsword".
+fn find_estimate<'e>(text: &str, estimates: &'e [ActionEstimate]) -> Option<&'e ActionEstimate> {
+    let text = text.trim();
+    let singular = text.strip_suffix('s').unwrap_or(text);
+
+    estimates.iter().find(|estimate| {
+        let name = estimate.name.to_lowercase();
+        !name.is_empty() && (name == text || name == singular || text.starts_with(&name))
+    })
+}
+
+fn number_word(word: &str) -> Option<u32> {
+    match word {
+        "one" | "once" => Some(1),
+        "two" | "twice" => Some(2),
+        "three" | "thrice" => Some(3),
+        "four" => Some(4),
+        "five" => Some(5),
+        "six" => Some(6),
+        n => n.parse().ok(),
+    }
+}
+
+/// The best expected damage of one action each round. A recharge ability is used whenever
+/// available, and the best other action otherwise.
+fn best_per_round(estimates: &[ActionEstimate], multiattack: Option<f64>, rounds: usize) -> f64 {
+    let fallback = estimates
+        .iter()
+        .filter(|estimate| estimate.recharge.is_none())
+        .map(|estimate| estimate.expected)
+        .chain(multiattack)
+        .fold(0.0, f64::max);
+
+    let rounds = rounds.max(1);
+    estimates
+        .iter()
+        .filter_map(|estimate| {
+            let recharge = estimate.recharge?;
+            let available = recharge.expected_uses(rounds) / rounds as f64;
+            Some(available * estimate.expected + (1.0 - available) * fallback)
+        })
+        .fold(fallback, f64::max)
+}
+
+/// The most damage of any combination of legendary actions whose costs fit in the number of
+/// legendary actions. Legendary actions which name one of the creature's actions, e.g.
+/// "The dragon makes a tail attack", deal that action's damage.
+fn legendary_damage(
+    creature: &Creature,
+    estimates: &[ActionEstimate],
+    target: Option<&Target>,
+) -> f64 {
+    let count = creature.legendary_action_count();
+    let legendary = creature.legendary.as_deref().unwrap_or_default();
+    let cost = regex!(r"(?i)costs (\d+) actions");
+    let makes = regex!(r"makes (?:a|an|one) ([a-z' -]+?) attack");
+
+    let options = legendary
+        .iter()
+        .filter_map(|action| {
+            let name = action.name.unwrap_or_default();
+            let cost = cost
+                .captures(name)
+                .and_then(|captures| captures[1].parse::<i64>().ok())
+                .unwrap_or(1)
+                .max(1);
+            if cost > count {
+                return None;
+            }
+
+            let mut damage = expected_damage(&action.entries, target);
+            if damage == 0.0 {
+                let text = action
+                    .entries
+                    .iter()
+                    .flat_map(Entry::strings)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .to_lowercase();
+                damage = makes
+                    .captures(&text)
+                    .and_then(|captures| find_estimate(&captures[1], estimates))
+                    .map_or(0.0, |estimate| estimate.expected);
+            }
+            Some((cost as usize, damage))
+        })
+        .collect::<Vec<_>>();
+
+    // The best damage for each number of legendary actions spent, where actions may be repeated
+    let count = count.max(0) as usize;
+    let mut best = vec![0.0; count + 1];
+    for spent in 1..=count {
+        best[spent] = options
+            .iter()
+            .filter(|(cost, _)| *cost <= spent)
+            .map(|(cost, damage)| best[spent - cost] + damage)
+            .fold(best[spent - 1], f64::max);
+    }
+    best[count]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::GOBLIN_BOSS;
+    use super::*;
+
+    fn approx(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn dpr_multiattack() {
+        let creature: Creature = serde_json::from_str(GOBLIN_BOSS).unwrap();
+        let dpr = damage_per_round(&creature, &Target::new(14, 0));
+
+        // +4 against AC 14 hits on 10 or higher, and crits on a 20
+        let scimitar = 0.5 * 5.5 + 0.05 * 9.0;
+        approx(dpr.actions[0].expected, scimitar);
+        approx(dpr.multiattack.unwrap(), 2.0 * scimitar);
+        approx(dpr.total, 2.0 * scimitar);
+    }
+
+    #[test]
+    fn dpr_recharge_and_legendary() {
+        let json = r#"{
+            "name": "Test Dragon",
+            "source": "TST",
+            "action": [
+                {"name": "Multiattack", "entries": ["The dragon makes three attacks: one with its bite and two with its claws."]},
+                {"name": "Bite", "entries": ["{@atk mw} {@hit 10} to hit, reach 10 ft., one target. {@h}17 ({@damage 2d10 + 6}) piercing damage."]},
+                {"name": "Claw", "entries": ["{@atk mw} {@hit 10} to hit, reach 5 ft., one target. {@h}13 ({@damage 2d6 + 6}) slashing damage."]},
+                {"name": "Fire Breath {@recharge 5}", "entries": ["Each creature in a cone must make a {@dc 18} Dexterity saving throw, taking 56 ({@damage 16d6}) fire damage on a failed save, or half as much damage on a successful one."]}
+            ],
+            "legendary": [
+                {"name": "Tail Attack", "entries": ["The dragon makes a tail attack."]},
+                {"name": "Wing Attack (Costs 2 Actions)", "entries": ["Each creature must succeed on a {@dc 19} Dexterity saving throw or take 13 ({@damage 2d6 + 6}) bludgeoning damage."]}
+            ]
+        }"#;
+        let creature: Creature = serde_json::from_str(json).unwrap();
+        let target = Target::new(30, 3);
+        let dpr = damage_per_round(&creature, &target);
+
+        // Only natural 20s hit AC 30
+        let bite = 0.05 * 28.0;
+        let claw = 0.05 * 20.0;
+        approx(dpr.multiattack.unwrap(), bite + 2.0 * claw);
+
+        // DC 18 against +3 succeeds on 15 or higher
+        let breath = 56.0 * (0.7 + 0.3 * 0.5);
+        let available = Recharge { threshold: 5 }.expected_uses(3) / 3.0;
+        approx(
+            dpr.action,
+            available * breath + (1.0 - available) * (bite + 2.0 * claw),
+        );
+
+        // No tail attack is listed, so the wing attack is the only legendary damage, and only one
+        // fits in three legendary actions
+        approx(dpr.legendary, 13.0 * 0.75);
+        assert_eq!(dpr.actions.len(), 3);
+    }
+}
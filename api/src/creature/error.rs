@@ -0,0 +1,23 @@
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug)]
+pub enum Error {
+    #[error("the creature has no challenge rating")]
+    MissingCr,
+    #[error("`{0}` is not a challenge rating between 0 and 30")]
+    UnknownCr(String),
+    #[error("the creature is not summoned by a spell")]
+    NotASummon,
+    #[error("{spell} cannot be cast with a level {level} slot, the lowest is {minimum}")]
+    SpellLevel {
+        spell: String,
+        level: u8,
+        minimum: u8,
+    },
+    #[error("unknown placeholder `{0}`")]
+    UnknownPlaceholder(String),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
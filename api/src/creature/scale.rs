@@ -0,0 +1,489 @@
+use super::{cr_row, CrRow, Creature, Error, Result};
+use crate::dice::{correct_printed_averages, Expression, Formula};
+use crate::entry::kinds::{EntrySpellcasting, EntrySpellcastingLevels, SpellsByLevel};
+use crate::string::{rewrite_tags, LexemeTag};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::{Number, Value};
+
+/// The properties of a statblock whose strings may contain `{@hit}`, `{@dc}` or `{@damage}` tags.
+pub(super) const ENTRY_PROPERTIES: [&str; 9] = [
+    "trait",
+    "action",
+    "bonus",
+    "reaction",
+    "legendary",
+    "legendaryHeader",
+    "mythic",
+    "mythicHeader",
+    "spellcasting",
+];
+
+/// Spell slots per spell level for a full caster of each level, e.g. a wizard.
+const FULL_CASTER_SLOTS: [[u8; 9]; 20] = [
+    [2, 0, 0, 0, 0, 0, 0, 0, 0],
+    [3, 0, 0, 0, 0, 0, 0, 0, 0],
+    [4, 2, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 2, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 1, 0, 0, 0, 0, 0],
+    [4, 3, 3, 2, 0, 0, 0, 0, 0],
+    [4, 3, 3, 3, 1, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 2, 1, 1],
+];
+
+/// Rescales a creature to another challenge rating, following the DMG's Monster Statistics by
+/// Challenge Rating table: hit points, armor class, proficient saves and skills, `{@hit}`, `{@dc}`
+/// and `{@damage}` tags, caster levels and spell slots all move by the difference between the
+/// two rows. Printed averages are recalculated to match the new dice.
+///
+/// The result is a statblock in the same JSON shape as the input, which can be read back with
+/// `Creature::deserialize(&value)`.
+pub fn scale_to_cr(creature: &Creature, cr: &str) -> Result<Value> {
+    let from_cr = creature.cr.as_ref().ok_or(Error::MissingCr)?.as_str();
+    let from = cr_row(from_cr).ok_or_else(|| Error::UnknownCr(from_cr.to_owned()))?;
+    let to = cr_row(cr).ok_or_else(|| Error::UnknownCr(cr.to_owned()))?;
+    let scaling = Scaling::new(from, to);
+
+    let mut statblock = serde_json::to_value(creature)?;
+    let object = match statblock.as_object_mut() {
+        Some(object) => object,
+        None => return Ok(statblock),
+    };
+
+    match object.get_mut("cr") {
+        Some(Value::Object(detailed)) => {
+            detailed.insert("cr".into(), cr.trim().into());
+            detailed.remove("xp");
+        }
+        _ => {
+            object.insert("cr".into(), cr.trim().into());
+        }
+    }
+
+    if let Some(hp) = object.get_mut("hp") {
+        scale_hp(hp, midpoint(to.hp) / midpoint(from.hp));
+    }
+
+    for ac in object
+        .get_mut("ac")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        let ac = match ac {
+            Value::Object(detailed) => detailed.get_mut("ac"),
+            ac => Some(ac),
+        };
+        if let Some(ac) = ac {
+            shift_number(ac, scaling.ac);
+        }
+    }
+
+    for property in ["save", "skill"] {
+        let bonuses = object.get_mut(property).and_then(Value::as_object_mut);
+        for bonus in bonuses.into_iter().flat_map(|bonuses| bonuses.values_mut()) {
+            shift_bonus(bonus, scaling.proficiency);
+        }
+    }
+    if object
+        .get("skill")
+        .and_then(|skill| skill.get("perception"))
+        .is_some()
+    {
+        if let Some(passive) = object.get_mut("passive") {
+            shift_number(passive, scaling.proficiency);
+        }
+    }
+
+    let caster_levels = (to.cr - from.cr).round() as i64;
+    for spellcasting in object
+        .get_mut("spellcasting")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        scale_spellcasting(spellcasting, caster_levels)?;
+    }
+
+    for property in ENTRY_PROPERTIES {
+        if let Some(entries) = object.get_mut(property) {
+            rewrite_strings(entries, &mut |text| scaling.rewrite(text));
+        }
+    }
+
+    Ok(statblock)
+}
+
+/// How much each number in a statblock moves between two rows of the CR table.
+struct Scaling {
+    ac: i64,
+    proficiency: i64,
+    attack_bonus: i64,
+    save_dc: i64,
+    damage: f64,
+}
+
+impl Scaling {
+    fn new(from: &CrRow, to: &CrRow) -> Self {
+        Self {
+            ac: to.ac - from.ac,
+            proficiency: to.proficiency_bonus - from.proficiency_bonus,
+            attack_bonus: to.attack_bonus - from.attack_bonus,
+            save_dc: to.save_dc - from.save_dc,
+            damage: midpoint(to.dpr) / midpoint(from.dpr),
+        }
+    }
+
+    fn rewrite(&self, text: &str) -> String {
+        let rewritten = rewrite_tags(text, |tag| match tag.name {
+            "hit" => shift_tag(tag, self.attack_bonus),
+            "dc" => shift_tag(tag, self.save_dc),
+            "damage" => {
+                let formula = tag.args.first()?.parse::<Formula>().ok()?;
+                let parts = formula
+                    .parts
+                    .iter()
+                    .map(|part| scale_damage(part, self.damage))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(with_first_arg(tag, Formula { parts }.to_string()))
+            }
+            _ => None,
+        });
+
+        match rewritten {
+            Ok(rewritten) => correct_printed_averages(&rewritten),
+            Err(_) => text.to_owned(),
+        }
+    }
+}
+
+/// Applies `f` to every string in a JSON value.
+pub(super) fn rewrite_strings<F: FnMut(&str) -> String>(value: &mut Value, f: &mut F) {
+    match value {
+        Value::String(text) => *text = f(text),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| rewrite_strings(value, f)),
+        Value::Object(object) => object
+            .values_mut()
+            .for_each(|value| rewrite_strings(value, f)),
+        _ => {}
+    }
+}
+
+/// The tag written back out with its first argument replaced.
+pub(super) fn with_first_arg(tag: &LexemeTag, first: String) -> String {
+    let mut args = vec![first.as_str()];
+    args.extend(tag.args.iter().skip(1));
+    LexemeTag {
+        name: tag.name,
+        args,
+    }
+    .to_string()
+}
+
+fn shift_tag(tag: &LexemeTag, delta: i64) -> Option<String> {
+    let n = tag.args.first()?.trim().parse::<i64>().ok()?;
+    Some(with_first_arg(tag, (n + delta).to_string()))
+}
+
+/// Scales the dice of an expression so that its mean is multiplied by `factor`,
+/// leaving constant bonuses as they are. Every group keeps at least one die.
+fn scale_damage(expression: &Expression, factor: f64) -> Option<Expression> {
+    let mean = expression.mean().ok()?;
+    let constant = expression.multiply_dice(0).mean().ok()?;
+    if mean - constant <= 0.0 {
+        return Some(expression.clone());
+    }
+
+    let dice_factor = (mean * factor - constant) / (mean - constant);
+    let mut scaled = expression.clone();
+    scale_dice_counts(&mut scaled, dice_factor);
+    Some(scaled)
+}
+
+fn scale_dice_counts(expression: &mut Expression, factor: f64) {
+    match expression {
+        Expression::Number(_) | Expression::Prompt(_) => {}
+        Expression::Dice(dice) => {
+            if let Expression::Number(count) = &mut *dice.count {
+                *count = ((*count as f64 * factor).round() as i64).max(1);
+            }
+        }
+        Expression::Neg(inner) => scale_dice_counts(inner, factor),
+        Expression::Binary { lhs, rhs, .. } => {
+            scale_dice_counts(lhs, factor);
+            scale_dice_counts(rhs, factor);
+        }
+    }
+}
+
+/// Changes the number of hit dice so that the average is multiplied by `factor`,
+/// keeping the same bonus per hit die.
+fn scale_hp(hp: &mut Value, factor: f64) {
+    let (average, formula) = match (
+        hp.get("average").and_then(Value::as_i64),
+        hp.get("formula").and_then(Value::as_str),
+    ) {
+        (Some(average), Some(formula)) => (average, formula),
+        _ => return,
+    };
+
+    let scaled = formula.parse::<Expression>().ok().and_then(|formula| {
+        let (count, faces) = match formula.dice_groups().as_slice() {
+            [dice] => match *dice.count {
+                Expression::Number(count) if count > 0 => (count, dice.faces),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let bonus = formula.multiply_dice(0).mean().ok()? / count as f64;
+        let per_die = (faces as f64 + 1.0) / 2.0 + bonus;
+        if per_die <= 0.0 {
+            return None;
+        }
+
+        let count = ((average as f64 * factor / per_die).round() as i64).max(1);
+        let scaled = Expression::dice(count, faces).plus((bonus * count as f64).round() as i64);
+        let average = scaled.distribution().ok()?.printed_average();
+        Some((average, scaled.to_string()))
+    });
+
+    match scaled {
+        Some((average, formula)) => {
+            hp["average"] = average.into();
+            hp["formula"] = formula.into();
+        }
+        None => hp["average"] = ((average as f64 * factor).round() as i64).max(1).into(),
+    }
+}
+
+/// Moves the caster level in the header by `levels` and gives the spells the slots of the new
+/// level, dropping spell levels the caster can no longer cast and adding empty ones for the spell
+/// levels it gains. Innate spellcasting is untouched.
+fn scale_spellcasting(spellcasting: &mut Value, levels: i64) -> Result<()> {
+    let level_text = regex!(r"\b(\d+)(?:st|nd|rd|th)-level spellcaster");
+
+    let caster_level = spellcasting
+        .get("headerEntries")
+        .and_then(|header| find_number(header, level_text));
+    let caster_level = match caster_level {
+        Some(level) => (level + levels).clamp(1, 20),
+        None => return Ok(()),
+    };
+
+    let scaled = {
+        let mut entry = EntrySpellcasting::deserialize(&*spellcasting)?;
+        if let Some(spells) = &mut entry.spells {
+            scale_slots(spells, caster_level);
+        }
+        serde_json::to_value(&entry)?
+    };
+    *spellcasting = scaled;
+
+    if let Some(header) = spellcasting.get_mut("headerEntries") {
+        rewrite_strings(header, &mut |text| {
+            level_text
+                .replace_all(text, |_: &Captures| {
+                    format!("{}-level spellcaster", ordinal(caster_level))
+                })
+                .into_owned()
+        });
+    }
+    Ok(())
+}
+
+/// The number captured by the first match of `pattern` in any string of a JSON value.
+fn find_number(value: &Value, pattern: &Regex) -> Option<i64> {
+    match value {
+        Value::String(text) => pattern.captures(text)?[1].parse().ok(),
+        Value::Array(values) => values.iter().find_map(|value| find_number(value, pattern)),
+        Value::Object(object) => object
+            .values()
+            .find_map(|value| find_number(value, pattern)),
+        _ => None,
+    }
+}
+
+fn scale_slots(spells: &mut SpellsByLevel, caster_level: i64) {
+    let pact_magic = spells.0.values().any(|level| level.lower.is_some());
+    if pact_magic {
+        // A warlock's slots are all of the same level, which rises with the caster
+        let slot_level = ((caster_level + 1) / 2).min(5) as u8;
+        let slots: u8 = match caster_level {
+            1 => 1,
+            2..=10 => 2,
+            11..=16 => 3,
+            _ => 4,
+        };
+
+        let pact = spells
+            .0
+            .iter()
+            .filter(|(_, level)| level.lower.is_some())
+            .map(|(level, _)| *level)
+            .collect::<Vec<_>>();
+        for level in pact {
+            if let Some(mut spells_at) = spells.0.remove(&level) {
+                let lower = spells_at
+                    .lower
+                    .as_ref()
+                    .and_then(Number::as_u64)
+                    .unwrap_or(1);
+                spells_at.lower = Some(Number::from(lower.min(slot_level as u64)));
+                spells_at.slots = Some(Number::from(slots));
+                spells.0.insert(slot_level, spells_at);
+            }
+        }
+        return;
+    }
+
+    let slots = FULL_CASTER_SLOTS[caster_level as usize - 1];
+    let highest = spells.0.keys().copied().max().unwrap_or_default();
+    spells
+        .0
+        .retain(|level, _| *level == 0 || slots.get(*level as usize - 1).is_some_and(|n| *n > 0));
+
+    // Levels above the highest one listed are newly unlocked; they get their slots but no spells,
+    // which can still be used to cast the lower level spells at a higher level
+    if highest > 0 {
+        for (level, count) in (1..).zip(slots.iter()) {
+            if level > highest && *count > 0 {
+                spells.0.insert(
+                    level,
+                    EntrySpellcastingLevels {
+                        lower: None,
+                        slots: None,
+                        spells: Vec::new(),
+                    },
+                );
+            }
+        }
+    }
+    for (level, spells_at) in spells.0.iter_mut().filter(|(level, _)| **level > 0) {
+        spells_at.slots = Some(Number::from(slots[*level as usize - 1]));
+    }
+}
+
+fn ordinal(n: i64) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+fn midpoint((low, high): (i64, i64)) -> f64 {
+    // CR 0 creatures deal 0-1 damage, which would make every ratio from them infinite
+    ((low + high) as f64 / 2.0).max(0.5)
+}
+
+fn shift_number(value: &mut Value, delta: i64) {
+    if let Some(n) = value.as_i64() {
+        *value = (n + delta).into();
+    }
+}
+
+/// Shifts a bonus written as text, e.g. `"+4"`.
+fn shift_bonus(value: &mut Value, delta: i64) {
+    let bonus = value
+        .as_str()
+        .and_then(|bonus| bonus.trim().trim_start_matches('+').parse::<i64>().ok());
+    if let Some(bonus) = bonus {
+        *value = format!("{:+}", bonus + delta).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::GOBLIN_BOSS;
+    use super::*;
+    use crate::util::ability::Ability;
+
+    #[test]
+    fn scale_goblin_boss() {
+        let creature: Creature = serde_json::from_str(GOBLIN_BOSS).unwrap();
+        let scaled = scale_to_cr(&creature, "5").unwrap();
+        let scaled = Creature::deserialize(&scaled).unwrap();
+
+        assert_eq!(scaled.challenge_rating(), Some(5.0));
+        assert_eq!(scaled.armor_class(), Some(19));
+        assert_eq!(
+            scaled.hp,
+            Some(super::super::HitPoints::Rolled {
+                average: 38,
+                formula: "11d6"
+            })
+        );
+        assert_eq!(scaled.save_bonus(Ability::Dexterity), 5);
+
+        let scimitar = &scaled.action.as_ref().unwrap()[1].entries[0];
+        assert_eq!(
+            scimitar.strings(),
+            vec![
+                "{@atk mw} {@hit 7} to hit, reach 5 ft., one target. \
+                {@h}16 ({@damage 4d6 + 2}) slashing damage."
+            ]
+        );
+
+        let back = scale_to_cr(&scaled, "1").unwrap();
+        assert_eq!(back["ac"][0]["ac"], 17);
+        assert_eq!(back["hp"]["average"], 21);
+    }
+
+    #[test]
+    fn scale_spellcaster() {
+        let mut spellcasting = serde_json::json!({
+            "name": "Spellcasting",
+            "type": "spellcasting",
+            "headerEntries": ["The mage is a 9th-level spellcaster. Its spellcasting ability is Intelligence (spell save {@dc 14}, {@hit 6} to hit with spell attacks)."],
+            "spells": {
+                "0": {"spells": ["{@spell fire bolt}"]},
+                "1": {"slots": 4, "spells": ["{@spell shield}"]},
+                "3": {"slots": 3, "spells": ["{@spell fireball}"]},
+                "5": {"slots": 1, "spells": ["{@spell cone of cold}"]}
+            },
+            "ability": "int"
+        });
+
+        scale_spellcasting(&mut spellcasting, -4).unwrap();
+        assert!(spellcasting["headerEntries"][0]
+            .as_str()
+            .unwrap()
+            .starts_with("The mage is a 5th-level spellcaster."));
+        assert_eq!(spellcasting["spells"]["3"]["slots"], 2);
+        assert!(spellcasting["spells"].get("5").is_none());
+        assert_eq!(spellcasting["type"], "spellcasting");
+
+        scale_spellcasting(&mut spellcasting, 4).unwrap();
+        assert_eq!(spellcasting["spells"]["3"]["slots"], 3);
+        assert_eq!(spellcasting["spells"]["4"]["slots"], 3);
+        assert_eq!(spellcasting["spells"]["5"]["slots"], 1);
+        assert_eq!(spellcasting["spells"]["5"]["spells"], serde_json::json!([]));
+        assert!(spellcasting["spells"].get("6").is_none());
+
+        let mut warlock = serde_json::json!({
+            "name": "Spellcasting",
+            "headerEntries": ["The warlock is a 7th-level spellcaster."],
+            "spells": {"4": {"lower": 1, "slots": 2, "spells": ["{@spell banishment}"]}}
+        });
+        scale_spellcasting(&mut warlock, 4).unwrap();
+        assert_eq!(warlock["spells"]["5"]["slots"], 3);
+        assert_eq!(warlock["spells"]["5"]["lower"], 1);
+    }
+}
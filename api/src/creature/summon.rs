@@ -0,0 +1,371 @@
+use super::scale::{rewrite_strings, with_first_arg, ENTRY_PROPERTIES};
+use super::{Creature, Error, Result};
+use crate::dice::{correct_printed_averages, BinaryOp, Expression, Formula};
+use crate::string::rewrite_tags;
+use regex::Captures;
+use serde_json::{Map, Value};
+
+/// The caster of a summoning spell, whose numbers fill in the summon's statblock.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Summoner {
+    pub spellcasting_modifier: i64,
+    pub proficiency_bonus: i64,
+}
+
+impl Summoner {
+    pub fn new(spellcasting_modifier: i64, proficiency_bonus: i64) -> Self {
+        Self {
+            spellcasting_modifier,
+            proficiency_bonus,
+        }
+    }
+
+    pub fn spell_attack_bonus(&self) -> i64 {
+        self.spellcasting_modifier + self.proficiency_bonus
+    }
+
+    pub fn spell_save_dc(&self) -> i64 {
+        8 + self.spell_attack_bonus()
+    }
+}
+
+/// Fills in the statblock of a creature summoned by a spell such as Summon Beast, cast with a
+/// slot of `spell_level` by `summoner`.
+///
+/// `summonSpellLevel` in dice, `<$spell_dc$>`, `<$spell_attack$>`, `<$spell_mod$>`,
+/// `<$prof_bonus$>` and `<$spell_level$>` are replaced everywhere, `{@hitYourSpellAttack}`
+/// becomes a `{@hit}` tag, and hit points and armor class such as
+/// `30 + 10 for each spell level above 3rd` are worked out.
+///
+/// The result is a statblock in the same JSON shape as the input, which can be read back with
+/// `Creature::deserialize(&value)`.
+pub fn summon(creature: &Creature, spell_level: u8, summoner: &Summoner) -> Result<Value> {
+    let spell = creature.summoned_by_spell.ok_or(Error::NotASummon)?;
+    let minimum = creature.summoned_by_spell_level.unwrap_or(1);
+    if spell_level < minimum || spell_level > 9 {
+        return Err(Error::SpellLevel {
+            spell: spell.to_owned(),
+            level: spell_level,
+            minimum,
+        });
+    }
+
+    let placeholders = Placeholders {
+        summoner,
+        spell_level: spell_level as i64,
+    };
+    let mut statblock = serde_json::to_value(creature)?;
+    let object = match statblock.as_object_mut() {
+        Some(object) => object,
+        None => return Ok(statblock),
+    };
+
+    if let Some(Value::Object(hp)) = object.get_mut("hp") {
+        if let Some(Value::String(special)) = hp.get_mut("special") {
+            let special = placeholders.replace_in_stats(special)?;
+            let special = match evaluate(&special) {
+                Some(hp) => hp.to_string(),
+                None => special,
+            };
+            hp.insert("special".into(), special.into());
+        }
+    }
+
+    for ac in object
+        .get_mut("ac")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(special) = ac.get("special").and_then(Value::as_str) {
+            *ac = placeholders.armor_class(special)?;
+        }
+    }
+
+    let mut error = None;
+    for property in ENTRY_PROPERTIES {
+        if let Some(entries) = object.get_mut(property) {
+            rewrite_strings(entries, &mut |text| match placeholders.rewrite(text) {
+                Ok(text) => text,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    text.to_owned()
+                }
+            });
+        }
+    }
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(statblock),
+    }
+}
+
+struct Placeholders<'s> {
+    summoner: &'s Summoner,
+    spell_level: i64,
+}
+
+impl Placeholders<'_> {
+    fn variable(&self, name: &str) -> Option<i64> {
+        match name {
+            "spell_dc" => Some(self.summoner.spell_save_dc()),
+            "spell_attack" => Some(self.summoner.spell_attack_bonus()),
+            "spell_mod" => Some(self.summoner.spellcasting_modifier),
+            "prof_bonus" => Some(self.summoner.proficiency_bonus),
+            "spell_level" => Some(self.spell_level),
+            _ => None,
+        }
+    }
+
+    /// Replaces `<$variable$>` and `summonSpellLevel`.
+    fn replace(&self, text: &str) -> Result<String> {
+        let placeholder = regex!(r"<\$(\w+)\$>|\bsummonSpellLevel\b");
+
+        let mut unknown = None;
+        let replaced = placeholder.replace_all(text, |captures: &Captures| {
+            let value = match captures.get(1) {
+                Some(name) => self.variable(name.as_str()),
+                None => Some(self.spell_level),
+            };
+            match value {
+                Some(value) => value.to_string(),
+                None => {
+                    unknown.get_or_insert_with(|| captures[0].to_owned());
+                    captures[0].to_owned()
+                }
+            }
+        });
+
+        match unknown {
+            Some(placeholder) => Err(Error::UnknownPlaceholder(placeholder)),
+            None => Ok(replaced.into_owned()),
+        }
+    }
+
+    /// Also replaces the wording used for hit points and armor class, such as
+    /// `the level of the spell` and `10 for each spell level above 3rd`.
+    fn replace_in_stats(&self, text: &str) -> Result<String> {
+        let level = regex!(r"\bthe (?:level of the spell|spell's level)\b");
+        let per_level = regex!(r"(\d+) (?:for each|per) spell level above (\d+)(?:st|nd|rd|th)");
+
+        let text = self.replace(text)?;
+        let text = level.replace_all(&text, self.spell_level.to_string().as_str());
+        let text = per_level.replace_all(&text, |captures: &Captures| {
+            let per = captures[1].parse::<i64>().unwrap_or_default();
+            let above = captures[2].parse::<i64>().unwrap_or_default();
+            (per * (self.spell_level - above).max(0)).to_string()
+        });
+        Ok(text.into_owned())
+    }
+
+    /// An armor class such as `11 + the level of the spell (natural armor)`, as a number with
+    /// the parenthesized text as where it comes from.
+    fn armor_class(&self, special: &str) -> Result<Value> {
+        let special = self.replace_in_stats(special)?;
+        let (ac, from) = match special.split_once('(') {
+            Some((ac, from)) => (ac, Some(from.trim_end().trim_end_matches(')').trim())),
+            None => (special.as_str(), None),
+        };
+
+        Ok(match (evaluate(ac), from) {
+            (Some(ac), Some(from)) => {
+                let mut detailed = Map::new();
+                detailed.insert("ac".into(), ac.into());
+                detailed.insert("from".into(), vec![from].into());
+                detailed.into()
+            }
+            (Some(ac), None) => ac.into(),
+            (None, _) => {
+                let mut detailed = Map::new();
+                detailed.insert("special".into(), special.into());
+                detailed.into()
+            }
+        })
+    }
+
+    fn rewrite(&self, text: &str) -> Result<String> {
+        let text = self.replace(text)?;
+        let attack_bonus = self.summoner.spell_attack_bonus();
+
+        let rewritten = rewrite_tags(&text, |tag| match tag.name {
+            "hitYourSpellAttack" => Some(format!("{{@hit {}}}", attack_bonus)),
+            "damage" | "dice" => {
+                let formula = tag.args.first()?.parse::<Formula>().ok()?;
+                let folded = Formula {
+                    parts: formula.parts.iter().map(fold_constants).collect(),
+                };
+                if folded == formula {
+                    return None;
+                }
+                Some(with_first_arg(tag, folded.to_string()))
+            }
+            _ => None,
+        });
+
+        Ok(match rewritten {
+            Ok(rewritten) => correct_printed_averages(&rewritten),
+            Err(_) => text,
+        })
+    }
+}
+
+/// The value of plain arithmetic such as `20 + 10`, which has no dice.
+fn evaluate(text: &str) -> Option<i64> {
+    let expression = text.trim().parse::<Expression>().ok()?;
+    if !expression.dice_groups().is_empty() {
+        return None;
+    }
+    Some(expression.mean().ok()?.round() as i64)
+}
+
+/// Adds up the numbers of a sum, so that `1d8 + 4 + 3` becomes `1d8 + 7`.
+fn fold_constants(expression: &Expression) -> Expression {
+    let mut terms = Vec::new();
+    additive_terms(expression, 1, &mut terms);
+    if terms
+        .iter()
+        .filter(|(_, term)| matches!(term, Expression::Number(_)))
+        .count()
+        < 2
+    {
+        return expression.clone();
+    }
+
+    let mut constant = 0;
+    let mut folded: Option<Expression> = None;
+    for (sign, term) in terms {
+        if let Expression::Number(n) = term {
+            constant += sign * n;
+            continue;
+        }
+        let op = if sign > 0 {
+            BinaryOp::Add
+        } else {
+            BinaryOp::Sub
+        };
+        folded = Some(match folded {
+            Some(lhs) => Expression::binary(op, lhs, term.clone()),
+            None if sign > 0 => term.clone(),
+            None => Expression::Neg(Box::new(term.clone())),
+        });
+    }
+
+    match folded {
+        Some(folded) => folded.plus(constant),
+        None => Expression::Number(constant),
+    }
+}
+
+fn additive_terms<'e>(
+    expression: &'e Expression,
+    sign: i64,
+    terms: &mut Vec<(i64, &'e Expression)>,
+) {
+    match expression {
+        Expression::Binary {
+            op: BinaryOp::Add,
+            lhs,
+            rhs,
+        } => {
+            additive_terms(lhs, sign, terms);
+            additive_terms(rhs, sign, terms);
+        }
+        Expression::Binary {
+            op: BinaryOp::Sub,
+            lhs,
+            rhs,
+        } => {
+            additive_terms(lhs, sign, terms);
+            additive_terms(rhs, -sign, terms);
+        }
+        Expression::Neg(inner) => additive_terms(inner, -sign, terms),
+        term => terms.push((sign, term)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::HitPoints;
+    use super::*;
+    use serde::Deserialize;
+
+    const BESTIAL_SPIRIT: &str = r#"{
+        "name": "Bestial Spirit",
+        "source": "TCE",
+        "size": ["S"],
+        "type": "beast",
+        "ac": [{"special": "11 + the level of the spell (natural armor)"}],
+        "hp": {"special": "20 + 5 for each spell level above 2nd"},
+        "speed": {"walk": 30, "climb": 30},
+        "str": 18, "dex": 11, "con": 16, "int": 4, "wis": 14, "cha": 5,
+        "trait": [{"name": "Pack Tactics", "entries": ["The beast has advantage on an attack roll against a creature if at least one of the beast's allies is within 5 feet of the creature."]}],
+        "action": [
+            {"name": "Multiattack", "entries": ["The beast makes a number of attacks equal to half this spell's level (rounded down)."]},
+            {"name": "Maul", "entries": ["{@atk mw} {@hitYourSpellAttack Bonus equals your spell attack modifier} to hit, reach 5 ft., one target. {@h}{@damage 1d8 + 4 + summonSpellLevel} piercing damage."]},
+            {"name": "Howl", "entries": ["Each creature within 10 feet must succeed on a {@dc <$spell_dc$>} Wisdom saving throw or take {@damage 2d6 + summonSpellLevel} psychic damage."]}
+        ],
+        "summonedBySpell": "Summon Beast|TCE",
+        "summonedBySpellLevel": 2
+    }"#;
+
+    #[test]
+    fn summon_beast() {
+        let creature: Creature = serde_json::from_str(BESTIAL_SPIRIT).unwrap();
+        let summoner = Summoner::new(4, 3);
+        let summoned = summon(&creature, 4, &summoner).unwrap();
+        let summoned = Creature::deserialize(&summoned).unwrap();
+
+        assert_eq!(summoned.armor_class(), Some(15));
+        assert_eq!(summoned.hp, Some(HitPoints::Special { special: "30" }));
+
+        let actions = summoned.action.as_ref().unwrap();
+        assert_eq!(
+            actions[1].entries[0].strings(),
+            vec![
+                "{@atk mw} {@hit 7} to hit, reach 5 ft., one target. \
+                {@h}{@damage 1d8 + 8} piercing damage."
+            ]
+        );
+        assert_eq!(
+            actions[2].entries[0].strings(),
+            vec![
+                "Each creature within 10 feet must succeed on a {@dc 15} Wisdom saving throw \
+                or take {@damage 2d6 + 4} psychic damage."
+            ]
+        );
+    }
+
+    #[test]
+    fn summon_errors() {
+        let creature: Creature = serde_json::from_str(BESTIAL_SPIRIT).unwrap();
+        let summoner = Summoner::new(4, 3);
+        assert!(matches!(
+            summon(&creature, 1, &summoner),
+            Err(Error::SpellLevel { minimum: 2, .. })
+        ));
+
+        let placeholders = Placeholders {
+            summoner: &summoner,
+            spell_level: 3,
+        };
+        assert!(matches!(
+            placeholders.replace("<$unknown$>"),
+            Err(Error::UnknownPlaceholder(_))
+        ));
+
+        let goblin: Creature = serde_json::from_str(super::super::tests::GOBLIN_BOSS).unwrap();
+        assert!(matches!(
+            summon(&goblin, 3, &summoner),
+            Err(Error::NotASummon)
+        ));
+    }
+
+    #[test]
+    fn fold() {
+        let fold = |s: &str| fold_constants(&s.parse().unwrap()).to_string();
+        assert_eq!(fold("1d8 + 4 + 3"), "1d8 + 7");
+        assert_eq!(fold("2d6 - 1 + 3 + 1d4"), "2d6 + 1d4 + 2");
+        assert_eq!(fold("1d6 + 2"), "1d6 + 2");
+    }
+}
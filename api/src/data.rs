@@ -0,0 +1,30 @@
+mod cache;
+pub mod copy;
+mod dataset;
+mod error;
+mod feature;
+pub mod fluff;
+pub mod homebrew;
+pub mod layer;
+mod reference;
+mod source;
+mod spell_source;
+mod store;
+mod stream;
+mod uid;
+mod watch;
+
+pub use cache::StoreSnapshot;
+pub use dataset::Dataset;
+pub use error::{Error, Result};
+pub use feature::{ClassFeatureUid, SubclassFeatureUid};
+pub use fluff::FluffStore;
+pub use homebrew::{HomebrewCollection, HomebrewFile, HomebrewLoader};
+pub use layer::{Layer, LayeredStore, Provenance, ResolvedRecord};
+pub use reference::{BrokenReference, ReferenceResolver};
+pub use source::{SourceGroup, SourceInfo, SourceRegistry};
+pub use spell_source::SpellSources;
+pub use store::{DataStore, StoreRecord, TagTarget, UidFormat};
+pub use stream::{stream_file, stream_records, LoadedRecord, ParallelLoader};
+pub use uid::Uid;
+pub use watch::{ChangeEvent, StoreWatcher};
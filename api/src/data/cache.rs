@@ -0,0 +1,391 @@
+use super::spell_source::SPELL_SOURCES_FILE;
+use super::{DataStore, Dataset, Error, Result, SpellSources, Uid};
+use crate::search::{self, SearchIndex};
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+const MAGIC: [u8; 4] = *b"5etc";
+
+/// Bumped whenever the layout of the cache changes, so that older caches are rebuilt.
+const VERSION: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 4],
+    version: u32,
+    /// The version of the [SearchIndex] layout, which changes independently of the cache's.
+    search_version: u32,
+    hash: [u8; 32],
+}
+
+/// A JSON value in a form bincode can read back, as it cannot read [Value] itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Packed {
+    Null,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    String(String),
+    Array(Vec<Packed>),
+    Object(Vec<(String, Packed)>),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Payload {
+    records: Vec<(String, Vec<(Uid, Packed)>)>,
+    search: SearchIndex,
+}
+
+/// The same layout as [Payload], borrowing what it can from a snapshot.
+#[derive(Serialize)]
+struct PayloadRef<'a> {
+    records: Vec<(&'a str, Vec<(&'a Uid, Packed)>)>,
+    search: &'a SearchIndex,
+}
+
+/// A data directory loaded into a [DataStore], with `_copy` resolved and a [SearchIndex] built,
+/// which can be cached in a binary file.
+///
+/// The cache records a hash of the contents of the files the store was loaded from, and
+/// [StoreSnapshot::load_or_build] rebuilds it when they change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreSnapshot {
+    pub store: DataStore,
+    pub search: SearchIndex,
+    /// The hash of the files the store was loaded from, see [StoreSnapshot::content_hash].
+    pub hash: [u8; 32],
+}
+
+impl StoreSnapshot {
+    /// Loads the directory as [DataStore::from_dir] does and indexes it.
+    pub fn build<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let files = read_data_files(dir)?;
+        let hash = hash_files(dir, &files);
+        Self::from_files(files, hash)
+    }
+
+    /// Loads the cache if it is up to date with the directory, and otherwise builds the snapshot
+    /// and writes it to the cache. Caches which cannot be read are rebuilt rather than reported.
+    ///
+    /// A cache which cannot be written does not stop the snapshot from being returned,
+    /// along with the error of writing it.
+    pub fn load_or_build<P: AsRef<Path>, C: AsRef<Path>>(
+        dir: P,
+        cache: C,
+    ) -> Result<(Self, Option<Error>)> {
+        let (dir, cache) = (dir.as_ref(), cache.as_ref());
+        // Each file is only read once, whether or not the cache turns out to be stale
+        let files = read_data_files(dir)?;
+        let hash = hash_files(dir, &files);
+
+        if let Ok(mut reader) = File::open(cache).map(BufReader::new) {
+            let header = bincode::deserialize_from::<_, Header>(&mut reader);
+            if header.is_ok_and(|header| header == Header::new(hash)) {
+                if let Ok(snapshot) = Self::read_payload(reader, hash) {
+                    return Ok((snapshot, None));
+                }
+            }
+        }
+
+        let snapshot = Self::from_files(files, hash)?;
+        let cache_error = snapshot.save(cache).err();
+        Ok((snapshot, cache_error))
+    }
+
+    /// Loads a cache without checking whether it is up to date.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path).map_err(|e| Error::io(path, e))?);
+        let header: Header =
+            bincode::deserialize_from(&mut reader).map_err(|e| Error::cache(path, e))?;
+
+        if header.magic != MAGIC
+            || header.version != VERSION
+            || header.search_version != search::VERSION
+        {
+            return Err(Error::CacheVersion(path.to_owned()));
+        }
+        Self::read_payload(reader, header.hash).map_err(|e| Error::cache(path, e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let payload = PayloadRef {
+            records: self
+                .store
+                .records
+                .iter()
+                .map(|(category, records)| {
+                    let records = records
+                        .iter()
+                        .map(|(uid, value)| (uid, Packed::from(value)))
+                        .collect();
+                    (category.as_str(), records)
+                })
+                .collect(),
+            search: &self.search,
+        };
+
+        let mut writer = BufWriter::new(File::create(path).map_err(|e| Error::io(path, e))?);
+        bincode::serialize_into(&mut writer, &Header::new(self.hash))
+            .and_then(|_| bincode::serialize_into(&mut writer, &payload))
+            .map_err(|e| Error::cache(path, e))
+    }
+
+    /// A BLAKE3 hash of the paths, relative to the directory, and contents of the files
+    /// [DataStore::from_dir] loads from it.
+    pub fn content_hash<P: AsRef<Path>>(dir: P) -> Result<[u8; 32]> {
+        let dir = dir.as_ref();
+        Ok(hash_files(dir, &read_data_files(dir)?))
+    }
+
+    /// Loads files read by [read_data_files], as [DataStore::from_dir] would load them.
+    fn from_files(files: Vec<(PathBuf, Vec<u8>)>, hash: [u8; 32]) -> Result<Self> {
+        let mut dataset = Dataset::new();
+        let mut spell_sources = None;
+        for (path, contents) in files {
+            let value = serde_json::from_slice(&contents).map_err(|e| Error::json(&path, e))?;
+            match path.ends_with(SPELL_SOURCES_FILE) {
+                true => spell_sources = Some(SpellSources::from_value(value)),
+                false => dataset.extend(Dataset::from_value(value)),
+            }
+        }
+        let mut store = DataStore::from_dataset(dataset)?;
+        if let Some(sources) = &spell_sources {
+            store.add_spell_sources(sources);
+        }
+        let search = SearchIndex::build(&store);
+
+        Ok(Self {
+            store,
+            search,
+            hash,
+        })
+    }
+
+    fn read_payload<R: Read>(reader: R, hash: [u8; 32]) -> bincode::Result<Self> {
+        let payload: Payload = bincode::deserialize_from(reader)?;
+        let records = payload
+            .records
+            .into_iter()
+            .map(|(category, records)| {
+                let records = records
+                    .into_iter()
+                    .map(|(uid, value)| (uid, Value::from(value)))
+                    .collect::<BTreeMap<_, _>>();
+                (category, records)
+            })
+            .collect();
+
+        Ok(Self {
+            store: DataStore { records },
+            search: payload.search,
+            hash,
+        })
+    }
+}
+
+impl Header {
+    fn new(hash: [u8; 32]) -> Self {
+        Self {
+            magic: MAGIC,
+            version: VERSION,
+            search_version: search::VERSION,
+            hash,
+        }
+    }
+}
+
+/// The paths and contents of the files [DataStore::from_dir] loads from a directory.
+fn read_data_files(dir: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut paths = DataStore::data_files(dir)?;
+    let spell_sources = SpellSources::path_in(dir);
+    if spell_sources.is_file() {
+        paths.push(spell_sources);
+    }
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let contents = fs::read(&path).map_err(|e| Error::io(&path, e))?;
+            Ok((path, contents))
+        })
+        .collect()
+}
+
+fn hash_files(dir: &Path, files: &[(PathBuf, Vec<u8>)]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+
+    for (path, contents) in files {
+        let name = path.strip_prefix(dir).unwrap_or(path).to_string_lossy();
+        // Lengths keep the boundaries between files unambiguous
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update(&(contents.len() as u64).to_le_bytes());
+        hasher.update(contents);
+    }
+
+    *hasher.finalize().as_bytes()
+}
+
+impl From<&Value> for Packed {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(b) => Self::Bool(*b),
+            Value::Number(n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+                (Some(n), _, _) => Self::U64(n),
+                (_, Some(n), _) => Self::I64(n),
+                (_, _, n) => Self::F64(n.unwrap_or_default()),
+            },
+            Value::String(s) => Self::String(s.clone()),
+            Value::Array(values) => Self::Array(values.iter().map(Self::from).collect()),
+            Value::Object(object) => Self::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Self::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Packed> for Value {
+    fn from(packed: Packed) -> Self {
+        match packed {
+            Packed::Null => Value::Null,
+            Packed::Bool(b) => Value::Bool(b),
+            Packed::U64(n) => Value::from(n),
+            Packed::I64(n) => Value::from(n),
+            Packed::F64(n) => Number::from_f64(n).map_or(Value::Null, Value::Number),
+            Packed::String(s) => Value::String(s),
+            Packed::Array(values) => Value::Array(values.into_iter().map(Value::from).collect()),
+            Packed::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Query;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, file: &str, value: Value) {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, value.to_string()).unwrap();
+    }
+
+    fn data_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        write(
+            dir.path(),
+            "spells/index.json",
+            json!({ "PHB": "spells-phb.json" }),
+        );
+        write(
+            dir.path(),
+            "spells/spells-phb.json",
+            json!({ "spell": [
+                { "name": "Fireball", "source": "PHB", "level": 3, "entries": ["A bright streak flashes."] },
+                { "name": "Delayed Blast Fireball", "source": "PHB", "_copy": { "name": "Fireball", "source": "PHB" }, "level": 7 }
+            ] }),
+        );
+        write(
+            dir.path(),
+            "items.json",
+            json!({ "item": [{ "name": "Bag of Holding", "source": "DMG", "weight": 15, "value": 40000.5, "bonusAc": -1 }] }),
+        );
+        dir
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = data_dir();
+        let cache = dir.path().join("data.cache");
+
+        let built = StoreSnapshot::load_or_build(dir.path(), &cache).unwrap().0;
+        assert!(cache.is_file());
+        let delayed = built
+            .store
+            .get("spell", &Uid::new("Delayed Blast Fireball", "PHB"))
+            .unwrap();
+        assert_eq!(delayed["entries"], json!(["A bright streak flashes."]));
+
+        let loaded = StoreSnapshot::load(&cache).unwrap();
+        assert_eq!(loaded, built);
+        assert_eq!(loaded.search.search(&Query::parse("streak")).hits.len(), 2);
+    }
+
+    #[test]
+    fn unwritable_cache() {
+        let dir = data_dir();
+        let cache = dir.path().join("missing").join("data.cache");
+
+        let (snapshot, error) = StoreSnapshot::load_or_build(dir.path(), &cache).unwrap();
+        assert!(matches!(error, Some(Error::Io { .. })));
+        assert_eq!(snapshot, StoreSnapshot::build(dir.path()).unwrap());
+        assert!(!cache.exists());
+    }
+
+    #[test]
+    fn invalidate_on_change() {
+        let dir = data_dir();
+        let cache = dir.path().join("data.cache");
+        let first = StoreSnapshot::load_or_build(dir.path(), &cache).unwrap().0;
+        assert_eq!(
+            StoreSnapshot::load_or_build(dir.path(), &cache).unwrap().0,
+            first
+        );
+
+        write(
+            dir.path(),
+            "items.json",
+            json!({ "item": [{ "name": "Bag of Tricks", "source": "DMG" }] }),
+        );
+        let second = StoreSnapshot::load_or_build(dir.path(), &cache).unwrap().0;
+        assert_ne!(second.hash, first.hash);
+        assert!(second
+            .store
+            .get("item", &Uid::new("Bag of Tricks", "DMG"))
+            .is_some());
+        assert_eq!(StoreSnapshot::load(&cache).unwrap(), second);
+
+        let mut header = Header::new(second.hash);
+        header.search_version += 1;
+        let mut stale = bincode::serialize(&header).unwrap();
+        stale.extend(&fs::read(&cache).unwrap()[stale.len()..]);
+        fs::write(&cache, stale).unwrap();
+        assert!(matches!(
+            StoreSnapshot::load(&cache),
+            Err(Error::CacheVersion(_))
+        ));
+        assert_eq!(
+            StoreSnapshot::load_or_build(dir.path(), &cache).unwrap().0,
+            second
+        );
+
+        fs::write(&cache, b"not a cache").unwrap();
+        assert!(matches!(
+            StoreSnapshot::load(&cache),
+            Err(Error::Cache { .. })
+        ));
+        assert_eq!(
+            StoreSnapshot::load_or_build(dir.path(), &cache).unwrap().0,
+            second
+        );
+    }
+}
@@ -0,0 +1,839 @@
+//! Applies `_copy` blocks to raw records, following the semantics of the 5etools data loader.
+
+use crate::util::copy::{CopyBlockMod, CopyModifier, CopyModifierReplaceKind};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use thiserror::Error as ErrorDerive;
+
+/// Properties which are specific to a record, and so are not inherited through `_copy`
+/// unless they are listed in `_preserve`.
+const NOT_COPIED: [&str; 10] = [
+    "page",
+    "otherSources",
+    "additionalSources",
+    "srd",
+    "basicRules",
+    "reprintedAs",
+    "hasFluff",
+    "hasFluffImages",
+    "hasToken",
+    "_versions",
+];
+
+/// Keys whose values are never touched by text replacement.
+const TEXT_KEY_BLOCKLIST: [&str; 11] = [
+    "caption",
+    "type",
+    "colLabels",
+    "colLabelGroups",
+    "name",
+    "colStyles",
+    "style",
+    "shortName",
+    "subclassShortName",
+    "id",
+    "path",
+];
+
+#[derive(ErrorDerive, Debug, Clone, PartialEq)]
+pub enum CopyError {
+    #[error("the record does not have a `_copy` block")]
+    NoCopyBlock,
+    #[error("invalid `_copy` block: {0}")]
+    InvalidBlock(String),
+    #[error("`{mode}` on `{prop}`: could not find `{needle}`")]
+    NotFound {
+        mode: &'static str,
+        prop: String,
+        needle: String,
+    },
+    #[error("`{mode}` on `{prop}`: expected {expected}")]
+    WrongType {
+        mode: &'static str,
+        prop: String,
+        expected: &'static str,
+    },
+    #[error("invalid regex `{0}`")]
+    Regex(String),
+    #[error("could not evaluate formula `{0}`")]
+    Formula(String),
+}
+
+pub type CopyResult<T> = std::result::Result<T, CopyError>;
+
+/// Builds the record described by `copy_to`'s `_copy` block, using `copy_from` as its base.
+///
+/// `template` is the (already resolved) monster template referenced by `_copy._trait`, if any.
+/// The returned record no longer has a `_copy` block.
+pub fn apply_copy(
+    copy_to: &Value,
+    copy_from: &Value,
+    template: Option<&Value>,
+) -> CopyResult<Value> {
+    let block = copy_to.get("_copy").ok_or(CopyError::NoCopyBlock)?;
+    let preserve = block
+        .get("_preserve")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    let preserve_all = preserve.get("*").and_then(Value::as_bool).unwrap_or(false);
+
+    let mut out = match copy_to {
+        Value::Object(map) => map.clone(),
+        _ => {
+            return Err(CopyError::InvalidBlock(
+                "the record is not an object".into(),
+            ))
+        }
+    };
+    out.remove("_copy");
+
+    if let Value::Object(from) = copy_from {
+        for (key, value) in from {
+            if out.contains_key(key) {
+                continue;
+            }
+            let preserved = preserve.get(key).and_then(Value::as_bool).unwrap_or(false);
+            if NOT_COPIED.contains(&key.as_str()) && !preserve_all && !preserved {
+                continue;
+            }
+            out.insert(key.clone(), value.clone());
+        }
+    }
+    // A `null` value explicitly removes an inherited property.
+    out.retain(|_, value| !value.is_null());
+
+    if let Some(template) = template {
+        apply_template(&mut out, template)?;
+    }
+    if let Some(mods) = block.get("_mod") {
+        apply_mods(&mut out, mods)?;
+    }
+
+    Ok(Value::Object(out))
+}
+
+/// Applies the `apply` block of a monster template (`_copy._trait`).
+fn apply_template(out: &mut Map<String, Value>, template: &Value) -> CopyResult<()> {
+    let apply = match template.get("apply") {
+        Some(apply) => apply,
+        None => return Ok(()),
+    };
+
+    if let Some(Value::Object(root)) = apply.get("_root") {
+        for (key, value) in root {
+            out.insert(key.clone(), value.clone());
+        }
+    }
+    if let Some(mods) = apply.get("_mod") {
+        apply_mods(out, mods)?;
+    }
+
+    Ok(())
+}
+
+/// Applies a `_mod` object, keyed by property, to a record.
+pub fn apply_mods(out: &mut Map<String, Value>, mods: &Value) -> CopyResult<()> {
+    let mods = match mods {
+        Value::Object(mods) => mods,
+        _ => return Err(CopyError::InvalidBlock("`_mod` is not an object".into())),
+    };
+
+    for (prop, value) in mods {
+        let block = CopyBlockMod::deserialize(value)
+            .map_err(|e| CopyError::InvalidBlock(format!("`_mod.{}`: {}", prop, e)))?;
+
+        let modifiers = match block {
+            CopyBlockMod::String("remove") => {
+                out.remove(prop);
+                continue;
+            }
+            CopyBlockMod::String(other) => {
+                return Err(CopyError::InvalidBlock(format!(
+                    "`_mod.{}`: unknown mode `{}`",
+                    prop, other
+                )))
+            }
+            CopyBlockMod::Single(modifier) => vec![modifier],
+            CopyBlockMod::Multiple(modifiers) => modifiers,
+        };
+
+        for modifier in modifiers {
+            match prop.as_str() {
+                "*" => {
+                    let keys = out
+                        .keys()
+                        .filter(|k| !matches!(k.as_str(), "name" | "source" | "page"))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    for key in keys {
+                        apply_modifier(out, &key, &modifier)?;
+                    }
+                }
+                _ => apply_modifier(out, prop, &modifier)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_modifier(
+    out: &mut Map<String, Value>,
+    prop: &str,
+    modifier: &CopyModifier,
+) -> CopyResult<()> {
+    use CopyModifier::*;
+
+    match modifier {
+        ReplaceTxt {
+            replace,
+            with,
+            flags,
+        } => {
+            let regex = build_regex(replace, *flags)?;
+            if let Some(value) = out.get_mut(prop) {
+                replace_text(value, &regex, &js_replacement(with));
+            }
+        }
+        AppendStr { str, joiner } => {
+            let value = out
+                .entry(prop.to_owned())
+                .or_insert_with(|| Value::String(String::new()));
+            match value {
+                Value::String(s) if s.is_empty() => s.push_str(str),
+                Value::String(s) => {
+                    s.push_str(joiner.unwrap_or(""));
+                    s.push_str(str);
+                }
+                _ => return Err(wrong_type("appendStr", prop, "a string")),
+            }
+        }
+        PrependArr { items } => {
+            let arr = array_mut(out, prop, "prependArr")?;
+            for (i, item) in as_items(items).into_iter().enumerate() {
+                arr.insert(i, item);
+            }
+        }
+        AppendArr { items } => {
+            array_mut(out, prop, "appendArr")?.extend(as_items(items));
+        }
+        AppendIfNotExistsArr { items } => {
+            let arr = array_mut(out, prop, "appendIfNotExistsArr")?;
+            for item in as_items(items) {
+                if !arr.contains(&item) {
+                    arr.push(item);
+                }
+            }
+        }
+        ReplaceArr { replace, items } => {
+            let arr = array_mut(out, prop, "replaceArr")?;
+            let index = find_index(arr, replace)?.ok_or_else(|| CopyError::NotFound {
+                mode: "replaceArr",
+                prop: prop.to_owned(),
+                needle: describe(replace),
+            })?;
+            arr.splice(index..=index, as_items(items));
+        }
+        ReplaceOrAppendArr { replace, items } => {
+            let arr = array_mut(out, prop, "replaceOrAppendArr")?;
+            match find_index(arr, replace)? {
+                Some(index) => {
+                    arr.splice(index..=index, as_items(items));
+                }
+                None => arr.extend(as_items(items)),
+            }
+        }
+        InsertArr { index, items } => {
+            let arr = array_mut(out, prop, "insertArr")?;
+            let index = (*index).min(arr.len());
+            arr.splice(index..index, as_items(items));
+        }
+        RemoveArr {
+            names,
+            items,
+            force,
+        } => {
+            let force = force.unwrap_or(false);
+            let arr = array_mut(out, prop, "removeArr")?;
+
+            for name in names.iter().flat_map(as_items) {
+                let name = name.as_str().unwrap_or_default().to_lowercase();
+                let pos = arr.iter().position(|it| {
+                    it.get("name")
+                        .and_then(Value::as_str)
+                        .is_some_and(|n| n.to_lowercase() == name)
+                });
+                match pos {
+                    Some(pos) => {
+                        arr.remove(pos);
+                    }
+                    None if force => (),
+                    None => return Err(not_found("removeArr", prop, &name)),
+                }
+            }
+            for item in items.iter().flat_map(as_items) {
+                match arr.iter().position(|it| *it == item) {
+                    Some(pos) => {
+                        arr.remove(pos);
+                    }
+                    None if force => (),
+                    None => return Err(not_found("removeArr", prop, &item.to_string())),
+                }
+            }
+        }
+        CalculateProp {
+            prop: target,
+            formula,
+        } => {
+            let value = evaluate_formula(out, formula)?;
+            let object = object_mut(out, prop, "calculateProp")?;
+            object.insert((*target).to_owned(), Value::from(value));
+        }
+        ScalarAddProp {
+            prop: target,
+            scalar,
+        } => {
+            let object = object_mut(out, prop, "scalarAddProp")?;
+            for_each_scalar(object, target, |n| n + scalar);
+        }
+        ScalarMultProp {
+            prop: target,
+            scalar,
+            floor,
+        } => {
+            let floor = floor.unwrap_or(false);
+            let object = object_mut(out, prop, "scalarMultProp")?;
+            for_each_scalar(object, target, |n| {
+                let n = n * scalar;
+                if floor {
+                    n.floor()
+                } else {
+                    n
+                }
+            });
+        }
+        AddSkills { skills } => {
+            let object = object_mut(out, "skill", "addSkills")?;
+            if let Some(Value::Object(skills)) = skills {
+                for (skill, value) in skills {
+                    object.insert(skill.clone(), value.clone());
+                }
+            }
+        }
+        AddSpells {
+            spells,
+            will,
+            daily,
+        } => {
+            let spellcasting = first_spellcasting(out, "addSpells")?;
+
+            if let Some(Value::Object(spells)) = spells {
+                let by_level = spellcasting
+                    .entry("spells")
+                    .or_insert_with(|| Value::Object(Map::new()));
+                for (level, new) in spells {
+                    let level = by_level
+                        .as_object_mut()
+                        .ok_or_else(|| wrong_type("addSpells", "spells", "an object"))?
+                        .entry(level.clone())
+                        .or_insert_with(|| serde_json::json!({ "spells": [] }));
+                    if let Some(Value::Array(new)) = new.get("spells") {
+                        if let Some(Value::Array(existing)) = level.get_mut("spells") {
+                            existing.extend(new.iter().cloned());
+                        }
+                    }
+                    for key in &["slots", "lower"] {
+                        if let (Some(n), Some(level)) = (new.get(*key), level.as_object_mut()) {
+                            level.insert((*key).to_owned(), n.clone());
+                        }
+                    }
+                }
+            }
+            if let Some(will) = will {
+                let existing = spellcasting
+                    .entry("will")
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                if let Value::Array(existing) = existing {
+                    existing.extend(will.iter().cloned());
+                }
+            }
+            if let Some(Value::Object(daily)) = daily {
+                let existing = spellcasting
+                    .entry("daily")
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(existing) = existing {
+                    for (freq, new) in daily {
+                        let arr = existing
+                            .entry(freq.clone())
+                            .or_insert_with(|| Value::Array(Vec::new()));
+                        if let Value::Array(arr) = arr {
+                            arr.extend(as_items(new));
+                        }
+                    }
+                }
+            }
+        }
+        ReplaceSpells { spells, daily } => {
+            let spellcasting = first_spellcasting(out, "replaceSpells")?;
+
+            if let Some(Value::Object(spells)) = spells {
+                for (level, replacements) in spells {
+                    let existing = spellcasting
+                        .get_mut("spells")
+                        .and_then(|s| s.get_mut(level))
+                        .and_then(|l| l.get_mut("spells"))
+                        .and_then(Value::as_array_mut);
+                    if let Some(existing) = existing {
+                        replace_spells(existing, replacements);
+                    }
+                }
+            }
+            if let Some(Value::Object(daily)) = daily {
+                for (freq, replacements) in daily {
+                    let existing = spellcasting
+                        .get_mut("daily")
+                        .and_then(|d| d.get_mut(freq))
+                        .and_then(Value::as_array_mut);
+                    if let Some(existing) = existing {
+                        replace_spells(existing, replacements);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn build_regex(pattern: &str, flags: Option<&str>) -> CopyResult<Regex> {
+    let flags = flags.unwrap_or("");
+
+    RegexBuilder::new(pattern)
+        .case_insensitive(flags.contains('i'))
+        .multi_line(flags.contains('m'))
+        .build()
+        .map_err(|_| CopyError::Regex(pattern.to_owned()))
+}
+
+/// Converts a JavaScript replacement string to its `regex` equivalent: `$&` and `$1` become
+/// `${0}` and `${1}`, so that `$1st` is not read as a group named `1st`, and any other `$` is
+/// kept as it is.
+fn js_replacement(with: &str) -> String {
+    let mut out = String::with_capacity(with.len());
+    let mut rest = with;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        // As in JavaScript, at most two digits make up the group number
+        let digits = digits.min(2);
+
+        rest = if let Some(after) = after.strip_prefix('&') {
+            out.push_str("${0}");
+            after
+        } else if let Some(after) = after.strip_prefix('$') {
+            out.push_str("$$");
+            after
+        } else if digits > 0 {
+            out.push_str(&format!("${{{}}}", &after[..digits]));
+            &after[digits..]
+        } else {
+            out.push_str("$$");
+            after
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+fn replace_text(value: &mut Value, regex: &Regex, with: &str) {
+    match value {
+        Value::String(s) => {
+            *s = regex.replace_all(s, with).into_owned();
+        }
+        Value::Array(arr) => {
+            for item in arr {
+                replace_text(item, regex, with);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if !TEXT_KEY_BLOCKLIST.contains(&key.as_str()) {
+                    replace_text(item, regex, with);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+fn find_index(arr: &[Value], replace: &CopyModifierReplaceKind) -> CopyResult<Option<usize>> {
+    fn text(value: &Value) -> Option<&str> {
+        value
+            .as_str()
+            .or_else(|| value.get("name").and_then(Value::as_str))
+    }
+
+    Ok(match replace {
+        CopyModifierReplaceKind::String(needle) => {
+            let needle = needle.to_lowercase();
+            arr.iter()
+                .position(|it| text(it).is_some_and(|t| t.to_lowercase() == needle))
+        }
+        CopyModifierReplaceKind::Index { index } => Some(*index).filter(|i| *i < arr.len()),
+        CopyModifierReplaceKind::Regex { regex, flags } => {
+            let regex = build_regex(regex, *flags)?;
+            arr.iter()
+                .position(|it| text(it).is_some_and(|t| regex.is_match(t)))
+        }
+    })
+}
+
+fn describe(replace: &CopyModifierReplaceKind) -> String {
+    match replace {
+        CopyModifierReplaceKind::String(s) => (*s).to_owned(),
+        CopyModifierReplaceKind::Index { index } => format!("index {}", index),
+        CopyModifierReplaceKind::Regex { regex, .. } => format!("/{}/", regex),
+    }
+}
+
+fn replace_spells(existing: &mut [Value], replacements: &Value) {
+    for replacement in replacements.as_array().into_iter().flatten() {
+        let (from, to) = match (replacement.get("replace"), replacement.get("with")) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        for spell in existing.iter_mut() {
+            if spell == from {
+                *spell = to.clone();
+            }
+        }
+    }
+}
+
+/// Items may be given as a single value or as an array of values.
+fn as_items(items: &Value) -> Vec<Value> {
+    match items {
+        Value::Array(items) => items.clone(),
+        item => vec![item.clone()],
+    }
+}
+
+fn array_mut<'m>(
+    out: &'m mut Map<String, Value>,
+    prop: &str,
+    mode: &'static str,
+) -> CopyResult<&'m mut Vec<Value>> {
+    out.entry(prop.to_owned())
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .ok_or_else(|| wrong_type(mode, prop, "an array"))
+}
+
+fn object_mut<'m>(
+    out: &'m mut Map<String, Value>,
+    prop: &str,
+    mode: &'static str,
+) -> CopyResult<&'m mut Map<String, Value>> {
+    if prop == "_" {
+        return Ok(out);
+    }
+    out.entry(prop.to_owned())
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| wrong_type(mode, prop, "an object"))
+}
+
+fn first_spellcasting<'m>(
+    out: &'m mut Map<String, Value>,
+    mode: &'static str,
+) -> CopyResult<&'m mut Map<String, Value>> {
+    out.get_mut("spellcasting")
+        .and_then(Value::as_array_mut)
+        .and_then(|arr| arr.first_mut())
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| wrong_type(mode, "spellcasting", "a non-empty array"))
+}
+
+/// Applies `f` to the numeric value of `prop` (or every property, if `prop` is "*").
+/// Bonuses written as strings (e.g. "+5") keep their format.
+fn for_each_scalar<F: Fn(f64) -> f64>(object: &mut Map<String, Value>, prop: &str, f: F) {
+    for (key, value) in object.iter_mut() {
+        if prop != "*" && key != prop {
+            continue;
+        }
+        match value {
+            Value::Number(n) => {
+                if let Some(n) = n.as_f64() {
+                    let n = f(n);
+                    *value = if n.fract() == 0.0 {
+                        Value::from(n as i64)
+                    } else {
+                        Value::from(n)
+                    };
+                }
+            }
+            Value::String(s) => {
+                if let Ok(n) = s.trim().trim_start_matches('+').parse::<f64>() {
+                    *s = format!("{:+}", f(n) as i64);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Evaluates a `calculateProp` formula, e.g. `<$prof_bonus$> + <$dex_mod$>`.
+fn evaluate_formula(record: &Map<String, Value>, formula: &str) -> CopyResult<i64> {
+    let error = || CopyError::Formula(formula.to_owned());
+
+    let mut expanded = String::new();
+    let mut rest = formula;
+    while let Some(start) = rest.find("<$") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..].find("$>").ok_or_else(error)? + start;
+        let variable = &rest[start + 2..end];
+
+        let value = match variable {
+            "prof_bonus" => record.get("cr").and_then(prof_bonus_for_cr),
+            _ => variable
+                .strip_suffix("_mod")
+                .and_then(|ability| record.get(ability))
+                .and_then(Value::as_i64)
+                .and_then(|score| score.checked_sub(10))
+                .map(|score| score.div_euclid(2)),
+        };
+        expanded.push_str(&value.ok_or_else(error)?.to_string());
+        rest = &rest[end + 2..];
+    }
+    expanded.push_str(rest);
+
+    arithmetic::evaluate(&expanded).ok_or_else(error)
+}
+
+fn prof_bonus_for_cr(cr: &Value) -> Option<i64> {
+    let cr = match cr {
+        Value::Object(map) => map.get("cr")?.as_str()?,
+        other => other.as_str()?,
+    };
+    let cr = match cr.split_once('/') {
+        Some(_) => return Some(2),
+        None => cr.trim().parse::<i64>().ok()?,
+    };
+
+    Some(if cr < 1 { 2 } else { (cr - 1) / 4 + 2 })
+}
+
+fn wrong_type(mode: &'static str, prop: &str, expected: &'static str) -> CopyError {
+    CopyError::WrongType {
+        mode,
+        prop: prop.to_owned(),
+        expected,
+    }
+}
+
+fn not_found(mode: &'static str, prop: &str, needle: &str) -> CopyError {
+    CopyError::NotFound {
+        mode,
+        prop: prop.to_owned(),
+        needle: needle.to_owned(),
+    }
+}
+
+/// A tiny evaluator for integer arithmetic (`+ - * /` and parentheses).
+mod arithmetic {
+    pub fn evaluate(input: &str) -> Option<i64> {
+        let tokens = input
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<Vec<_>>();
+        let mut pos = 0;
+        let value = expr(&tokens, &mut pos)?;
+
+        if pos == tokens.len() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn expr(tokens: &[char], pos: &mut usize) -> Option<i64> {
+        let mut value = term(tokens, pos)?;
+        while let Some(op) = tokens.get(*pos).copied().filter(|c| *c == '+' || *c == '-') {
+            *pos += 1;
+            let rhs = term(tokens, pos)?;
+            value = if op == '+' {
+                value.checked_add(rhs)?
+            } else {
+                value.checked_sub(rhs)?
+            };
+        }
+        Some(value)
+    }
+
+    fn term(tokens: &[char], pos: &mut usize) -> Option<i64> {
+        let mut value = factor(tokens, pos)?;
+        while let Some(op) = tokens.get(*pos).copied().filter(|c| *c == '*' || *c == '/') {
+            *pos += 1;
+            let rhs = factor(tokens, pos)?;
+            value = if op == '*' {
+                value.checked_mul(rhs)?
+            } else {
+                value.checked_div(rhs)?
+            };
+        }
+        Some(value)
+    }
+
+    fn factor(tokens: &[char], pos: &mut usize) -> Option<i64> {
+        match tokens.get(*pos)? {
+            '-' => {
+                *pos += 1;
+                factor(tokens, pos)?.checked_neg()
+            }
+            '+' => {
+                *pos += 1;
+                factor(tokens, pos)
+            }
+            '(' => {
+                *pos += 1;
+                let value = expr(tokens, pos)?;
+                if tokens.get(*pos) != Some(&')') {
+                    return None;
+                }
+                *pos += 1;
+                Some(value)
+            }
+            c if c.is_ascii_digit() => {
+                let start = *pos;
+                while tokens.get(*pos).is_some_and(char::is_ascii_digit) {
+                    *pos += 1;
+                }
+                tokens[start..*pos].iter().collect::<String>().parse().ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn goblin() -> Value {
+        json!({
+            "name": "Goblin",
+            "source": "MM",
+            "page": 166,
+            "dex": 14,
+            "cr": "1/4",
+            "trait": [
+                { "name": "Nimble Escape", "entries": ["The goblin can take the Disengage action."] }
+            ],
+            "action": [
+                { "name": "Scimitar", "entries": ["The goblin hits with its scimitar."] },
+                { "name": "Shortbow", "entries": ["The goblin fires its shortbow."] }
+            ]
+        })
+    }
+
+    #[test]
+    fn copy_inherits_and_overrides() {
+        let copy_to = json!({
+            "name": "Goblin Boss",
+            "source": "HB",
+            "_copy": { "name": "Goblin", "source": "MM" },
+            "dex": 16
+        });
+
+        let out = apply_copy(&copy_to, &goblin(), None).unwrap();
+
+        assert_eq!(out["name"], "Goblin Boss");
+        assert_eq!(out["dex"], 16);
+        assert_eq!(out["trait"], goblin()["trait"]);
+        assert!(out.get("page").is_none());
+        assert!(out.get("_copy").is_none());
+    }
+
+    #[test]
+    fn copy_mods() {
+        let copy_to = json!({
+            "name": "Goblin Sneak",
+            "source": "HB",
+            "_copy": {
+                "name": "Goblin",
+                "source": "MM",
+                "_mod": {
+                    "*": { "mode": "replaceTxt", "replace": "the goblin", "with": "the sneak", "flags": "i" },
+                    "action": [
+                        { "mode": "removeArr", "names": "Shortbow" },
+                        { "mode": "appendArr", "items": { "name": "Dagger", "entries": [] } }
+                    ],
+                    "trait": "remove",
+                    "save": { "mode": "calculateProp", "prop": "dex", "formula": "<$prof_bonus$> + <$dex_mod$>" }
+                },
+                "_preserve": { "page": true }
+            }
+        });
+
+        let out = apply_copy(&copy_to, &goblin(), None).unwrap();
+
+        assert_eq!(out["page"], 166);
+        assert!(out.get("trait").is_none());
+        assert_eq!(
+            out["action"],
+            json!([
+                { "name": "Scimitar", "entries": ["the sneak hits with its scimitar."] },
+                { "name": "Dagger", "entries": [] }
+            ])
+        );
+        assert_eq!(out["save"], json!({ "dex": 4 }));
+    }
+
+    #[test]
+    fn copy_missing_array_item() {
+        let copy_to = json!({
+            "name": "Goblin Sneak",
+            "source": "HB",
+            "_copy": {
+                "name": "Goblin",
+                "source": "MM",
+                "_mod": { "action": { "mode": "replaceArr", "replace": "Longbow", "items": [] } }
+            }
+        });
+
+        assert_eq!(
+            apply_copy(&copy_to, &goblin(), None),
+            Err(CopyError::NotFound {
+                mode: "replaceArr",
+                prop: "action".into(),
+                needle: "Longbow".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn formula() {
+        assert_eq!(arithmetic::evaluate("2 + 3 * (4 - 1)"), Some(11));
+        assert_eq!(arithmetic::evaluate("-2 + 1"), Some(-1));
+        assert_eq!(arithmetic::evaluate("2 +"), None);
+        assert_eq!(arithmetic::evaluate("9223372036854775807 + 1"), None);
+        assert_eq!(arithmetic::evaluate("4611686018427387904 * 2"), None);
+    }
+
+    #[test]
+    fn replacement_strings() {
+        assert_eq!(js_replacement("$1st"), "${1}st");
+        assert_eq!(js_replacement("[$&]"), "[${0}]");
+        assert_eq!(js_replacement("$$5 and $x"), "$$5 and $$x");
+        assert_eq!(js_replacement("$123"), "${12}3");
+
+        let mut value = json!(["1 level", { "name": "2 level" }]);
+        let regex = build_regex("(\\d) level", None).unwrap();
+        replace_text(&mut value, &regex, &js_replacement("$1st level"));
+        assert_eq!(value, json!(["1st level", { "name": "2 level" }]));
+    }
+}
@@ -0,0 +1,103 @@
+use super::{Error, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// A collection of raw 5etools records, grouped by their top-level category (e.g. "monster", "spell").
+///
+/// Records are kept in the order they were added, so data loaded later follows data loaded earlier.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Dataset {
+    categories: BTreeMap<String, Vec<Value>>,
+}
+
+impl Dataset {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let value = serde_json::from_str(&text).map_err(|e| Error::json(path, e))?;
+
+        Ok(Self::from_value(value))
+    }
+
+    pub fn from_json(s: &str) -> Result<Self> {
+        let value = serde_json::from_str(s)?;
+
+        Ok(Self::from_value(value))
+    }
+
+    /// Keys beginning with an underscore (e.g. `_meta`) and values which are not arrays are ignored.
+    pub fn from_value(value: Value) -> Self {
+        let mut dataset = Self::new();
+
+        if let Value::Object(map) = value {
+            for (category, records) in map {
+                if category.starts_with('_') {
+                    continue;
+                }
+                if let Value::Array(records) = records {
+                    dataset
+                        .categories
+                        .entry(category)
+                        .or_default()
+                        .extend(records);
+                }
+            }
+        }
+
+        dataset
+    }
+
+    pub fn insert(&mut self, category: &str, record: Value) {
+        self.categories
+            .entry(category.to_owned())
+            .or_default()
+            .push(record);
+    }
+
+    /// Appends every record of `other` after the records already present.
+    pub fn extend(&mut self, other: Dataset) {
+        for (category, records) in other.categories {
+            self.categories.entry(category).or_default().extend(records);
+        }
+    }
+
+    pub fn get(&self, category: &str) -> &[Value] {
+        self.categories
+            .get(category)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        self.categories.keys().map(String::as_str)
+    }
+
+    /// Iterates over every record along with the category it belongs to.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.categories.iter().flat_map(|(category, records)| {
+            records
+                .iter()
+                .map(move |record| (category.as_str(), record))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.categories.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<Value> for Dataset {
+    fn from(value: Value) -> Self {
+        Self::from_value(value)
+    }
+}
@@ -0,0 +1,68 @@
+use super::copy::CopyError;
+use serde_json::Error as SerdeError;
+use std::io::Error as IoError;
+use std::path::PathBuf;
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug)]
+pub enum Error {
+    #[error("{}: {source}", .path.display())]
+    Io { path: PathBuf, source: IoError },
+    #[error("{}: {source}", .path.display())]
+    Json { path: PathBuf, source: SerdeError },
+    #[error("{0}")]
+    SerdeError(#[from] SerdeError),
+    #[error("{}: the file does not have a `_meta` block", .0.display())]
+    MissingMeta(PathBuf),
+    #[error("{}: dependency `{dependency}` could not be found", .required_by.display())]
+    MissingDependency {
+        dependency: String,
+        required_by: PathBuf,
+    },
+    #[error("circular dependency: {}", .0.join(" -> "))]
+    CircularDependency(Vec<String>),
+    #[error("{category} `{uid}`: `_copy` target `{target}` could not be found")]
+    MissingCopySource {
+        category: String,
+        uid: String,
+        target: String,
+    },
+    #[error("circular `_copy`: {}", .0.join(" -> "))]
+    CircularCopy(Vec<String>),
+    #[error("`{uid}`: {source}")]
+    Copy { uid: String, source: CopyError },
+    #[error("`{0}` is not a valid class or subclass feature reference")]
+    InvalidFeatureUid(String),
+    #[error("{}: {source}", .path.display())]
+    Cache {
+        path: PathBuf,
+        source: bincode::Error,
+    },
+    #[error("{}: the cache was written by another version", .0.display())]
+    CacheVersion(PathBuf),
+}
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: IoError) -> Self {
+        Self::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn cache(path: impl Into<PathBuf>, source: bincode::Error) -> Self {
+        Self::Cache {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn json(path: impl Into<PathBuf>, source: SerdeError) -> Self {
+        Self::Json {
+            path: path.into(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use super::{Error, Result, Uid};
+use std::fmt;
+use std::str::FromStr;
+
+/// The source of a class or subclass when a reference leaves it empty.
+const DEFAULT_SOURCE: &str = "PHB";
+
+/// A reference to a class feature in the pipe format of `refClassFeature` entries and
+/// `{@classFeature}` tags: `name|className|classSource|level|source`.
+///
+/// The class source defaults to the PHB, and the source of the feature to the class source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassFeatureUid {
+    pub name: String,
+    pub class_name: String,
+    pub class_source: String,
+    pub level: u8,
+    pub source: String,
+}
+
+/// A reference to a subclass feature in the pipe format of `refSubclassFeature` entries and
+/// `{@subclassFeature}` tags:
+/// `name|className|classSource|subclassShortName|subclassSource|level|source`.
+///
+/// The class and subclass sources default to the PHB, and the source of the feature to the
+/// subclass source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubclassFeatureUid {
+    pub name: String,
+    pub class_name: String,
+    pub class_source: String,
+    pub subclass_short_name: String,
+    pub subclass_source: String,
+    pub level: u8,
+    pub source: String,
+}
+
+impl ClassFeatureUid {
+    /// Parses the arguments of a `{@classFeature}` tag. Arguments after the source are ignored.
+    pub fn from_args(args: &[&str]) -> Result<Self> {
+        let invalid = || Error::InvalidFeatureUid(args.join("|"));
+        let arg = |i| non_empty(args, i);
+
+        let class_source = arg(2).unwrap_or(DEFAULT_SOURCE);
+        Ok(Self {
+            name: arg(0).ok_or_else(invalid)?.to_owned(),
+            class_name: arg(1).ok_or_else(invalid)?.to_owned(),
+            class_source: class_source.to_owned(),
+            level: parse_level(arg(3)).ok_or_else(invalid)?,
+            source: arg(4).unwrap_or(class_source).to_owned(),
+        })
+    }
+
+    /// The UID the feature is stored under in a [DataStore](super::DataStore).
+    pub fn uid(&self) -> Uid {
+        Uid::from_parts(&[
+            &self.name,
+            &self.class_name,
+            &self.class_source,
+            &self.level.to_string(),
+            &self.source,
+        ])
+    }
+}
+
+impl SubclassFeatureUid {
+    /// Parses the arguments of a `{@subclassFeature}` tag. Arguments after the source are ignored.
+    pub fn from_args(args: &[&str]) -> Result<Self> {
+        let invalid = || Error::InvalidFeatureUid(args.join("|"));
+        let arg = |i| non_empty(args, i);
+
+        let subclass_source = arg(4).unwrap_or(DEFAULT_SOURCE);
+        Ok(Self {
+            name: arg(0).ok_or_else(invalid)?.to_owned(),
+            class_name: arg(1).ok_or_else(invalid)?.to_owned(),
+            class_source: arg(2).unwrap_or(DEFAULT_SOURCE).to_owned(),
+            subclass_short_name: arg(3).ok_or_else(invalid)?.to_owned(),
+            subclass_source: subclass_source.to_owned(),
+            level: parse_level(arg(5)).ok_or_else(invalid)?,
+            source: arg(6).unwrap_or(subclass_source).to_owned(),
+        })
+    }
+
+    /// The UID the feature is stored under in a [DataStore](super::DataStore).
+    pub fn uid(&self) -> Uid {
+        Uid::from_parts(&[
+            &self.name,
+            &self.class_name,
+            &self.class_source,
+            &self.subclass_short_name,
+            &self.subclass_source,
+            &self.level.to_string(),
+            &self.source,
+        ])
+    }
+}
+
+impl FromStr for ClassFeatureUid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_args(&s.split('|').collect::<Vec<_>>())
+    }
+}
+
+impl FromStr for SubclassFeatureUid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_args(&s.split('|').collect::<Vec<_>>())
+    }
+}
+
+/// Writes every part out, including the defaulted sources.
+impl fmt::Display for ClassFeatureUid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}|{}",
+            self.name, self.class_name, self.class_source, self.level, self.source
+        )
+    }
+}
+
+/// Writes every part out, including the defaulted sources.
+impl fmt::Display for SubclassFeatureUid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}|{}|{}|{}",
+            self.name,
+            self.class_name,
+            self.class_source,
+            self.subclass_short_name,
+            self.subclass_source,
+            self.level,
+            self.source
+        )
+    }
+}
+
+fn non_empty<'a>(args: &[&'a str], i: usize) -> Option<&'a str> {
+    args.get(i)
+        .map(|arg| arg.trim())
+        .filter(|arg| !arg.is_empty())
+}
+
+fn parse_level(level: Option<&str>) -> Option<u8> {
+    level?.parse().ok().filter(|level| (1..=20).contains(level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_feature_uid() {
+        let uid: ClassFeatureUid = "Ability Score Improvement|Barbarian||4".parse().unwrap();
+        assert_eq!(uid.class_source, "PHB");
+        assert_eq!(uid.source, "PHB");
+        assert_eq!(
+            uid.to_string(),
+            "Ability Score Improvement|Barbarian|PHB|4|PHB"
+        );
+        assert_eq!(uid.to_string().parse::<ClassFeatureUid>().unwrap(), uid);
+        assert_eq!(
+            uid.uid().as_str(),
+            "ability score improvement|barbarian|phb|4|phb"
+        );
+
+        let uid: ClassFeatureUid = "Infuse Item|Artificer|TCE|2".parse().unwrap();
+        assert_eq!(uid.source, "TCE");
+
+        assert!("Rage|Barbarian".parse::<ClassFeatureUid>().is_err());
+        assert!("Rage|Barbarian||twenty".parse::<ClassFeatureUid>().is_err());
+    }
+
+    #[test]
+    fn subclass_feature_uid() {
+        let uid: SubclassFeatureUid = "Frenzy|Barbarian||Berserker||3".parse().unwrap();
+        assert_eq!(uid.to_string(), "Frenzy|Barbarian|PHB|Berserker|PHB|3|PHB");
+
+        let uid: SubclassFeatureUid = "Arcane Armor|Artificer|TCE|Armorer|TCE|3".parse().unwrap();
+        assert_eq!(uid.class_source, "TCE");
+        assert_eq!(uid.source, "TCE");
+        assert_eq!(
+            uid.uid().as_str(),
+            "arcane armor|artificer|tce|armorer|tce|3|tce"
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use super::{Dataset, Error, Layer, LayeredStore, Result, Uid};
+use crate::util::fluff::FluffObject;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Fluff categories are named after the category of the entities they describe, e.g. "monsterFluff".
+const FLUFF_SUFFIX: &str = "Fluff";
+
+/// The fluff (descriptive text and images) of every loaded entity, with `_copy` and `_appendCopy` resolved.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct FluffStore {
+    /// Keyed by the category of the entity, e.g. "monster" rather than "monsterFluff".
+    records: BTreeMap<String, BTreeMap<Uid, Value>>,
+}
+
+impl FluffStore {
+    /// Only the categories ending in "Fluff" are used.
+    pub fn from_dataset(dataset: Dataset) -> Result<Self> {
+        let mut fluff = Dataset::new();
+        for (category, record) in dataset.iter() {
+            if category.ends_with(FLUFF_SUFFIX) {
+                fluff.insert(category, record.clone());
+            }
+        }
+
+        let mut layer = Layer::new("fluff", 0);
+        layer.add_dataset(fluff, None);
+
+        let mut store = LayeredStore::new();
+        store.add_layer(layer)?;
+
+        let mut copied = BTreeMap::new();
+        for category in store.categories() {
+            let records = store
+                .iter(category)
+                .map(|record| (record.uid.clone(), record.value.clone()))
+                .collect::<BTreeMap<_, _>>();
+            let entity_category = category.trim_end_matches(FLUFF_SUFFIX).to_owned();
+            copied.insert(entity_category, records);
+        }
+
+        let mut records = BTreeMap::new();
+        for (category, fluff) in &copied {
+            let mut resolved = BTreeMap::new();
+            for uid in fluff.keys() {
+                let value = resolve_append_copy(fluff, uid, &mut Vec::new())?;
+                resolved.insert(uid.clone(), value);
+            }
+            records.insert(category.clone(), resolved);
+        }
+
+        Ok(Self { records })
+    }
+
+    /// Loads every `fluff-*.json` file in a directory (and its subdirectories).
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut dataset = Dataset::new();
+        load_fluff_files(dir.as_ref(), &mut dataset)?;
+
+        Self::from_dataset(dataset)
+    }
+
+    /// The fluff of an entity, e.g. `get("monster", &Uid::new("Goblin", "MM"))`.
+    ///
+    /// Fluff which is not a valid [FluffObject] is an error rather than missing.
+    pub fn get(&self, category: &str, uid: &Uid) -> Result<Option<FluffObject<'_>>> {
+        self.get_raw(category, uid)
+            .map(|value| FluffObject::deserialize(value).map_err(Error::from))
+            .transpose()
+    }
+
+    pub fn get_raw(&self, category: &str, uid: &Uid) -> Option<&Value> {
+        self.records.get(category)?.get(uid)
+    }
+
+    /// Sets the `fluff` property of an entity to its resolved fluff, keeping only `entries` and `images`.
+    /// Entities which already have inline fluff are left unchanged.
+    ///
+    /// Returns whether any fluff was attached.
+    pub fn attach(&self, category: &str, entity: &mut Value) -> bool {
+        if entity.get("fluff").is_some() {
+            return false;
+        }
+        let fluff = match Uid::from_record(entity).and_then(|uid| self.get_raw(category, &uid)) {
+            Some(fluff) => fluff,
+            None => return false,
+        };
+
+        let mut object = serde_json::Map::new();
+        for key in &["entries", "images"] {
+            if let Some(value) = fluff.get(*key) {
+                object.insert((*key).to_owned(), value.clone());
+            }
+        }
+
+        match entity.as_object_mut() {
+            Some(entity) => {
+                entity.insert("fluff".to_owned(), Value::Object(object));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Appends the entries and images of the record referenced by `_appendCopy` to a record's own.
+fn resolve_append_copy(
+    records: &BTreeMap<Uid, Value>,
+    uid: &Uid,
+    visiting: &mut Vec<Uid>,
+) -> Result<Value> {
+    let mut value = records[uid].clone();
+
+    let append = match value.as_object_mut().and_then(|v| v.remove("_appendCopy")) {
+        Some(append) => append,
+        None => return Ok(value),
+    };
+    let target = Uid::from_record(&append).ok_or_else(|| Error::MissingCopySource {
+        category: "fluff".to_owned(),
+        uid: uid.to_string(),
+        target: append.to_string(),
+    })?;
+
+    if visiting.contains(&target) || target == *uid {
+        let mut chain = visiting.iter().map(Uid::to_string).collect::<Vec<_>>();
+        chain.push(uid.to_string());
+        chain.push(target.to_string());
+        return Err(Error::CircularCopy(chain));
+    }
+    if !records.contains_key(&target) {
+        return Err(Error::MissingCopySource {
+            category: "fluff".to_owned(),
+            uid: uid.to_string(),
+            target: target.to_string(),
+        });
+    }
+
+    visiting.push(uid.clone());
+    let appended = resolve_append_copy(records, &target, visiting)?;
+    visiting.pop();
+
+    for key in &["entries", "images"] {
+        let extra = match appended.get(*key).and_then(Value::as_array) {
+            Some(extra) => extra.clone(),
+            None => continue,
+        };
+        let own = value
+            .as_object_mut()
+            .map(|v| v.entry(*key).or_insert_with(|| Value::Array(Vec::new())));
+        if let Some(Value::Array(own)) = own {
+            own.extend(extra);
+        }
+    }
+
+    Ok(value)
+}
+
+fn load_fluff_files(dir: &Path, dataset: &mut Dataset) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::io(dir, e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| Error::io(dir, e))?.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+
+        if path.is_dir() {
+            load_fluff_files(&path, dataset)?;
+        } else if name.starts_with("fluff-") && name.ends_with(".json") {
+            dataset.extend(Dataset::from_file(&path)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::Entry;
+    use serde_json::json;
+
+    fn store() -> FluffStore {
+        FluffStore::from_dataset(Dataset::from_value(json!({
+            "monsterFluff": [
+                {
+                    "name": "Goblin",
+                    "source": "MM",
+                    "entries": ["Goblins are small, black-hearted humanoids."],
+                    "images": [{ "type": "image", "href": { "type": "internal", "path": "bestiary/MM/Goblin.jpg" } }]
+                },
+                {
+                    "name": "Goblin Boss",
+                    "source": "MM",
+                    "_copy": {
+                        "name": "Goblin",
+                        "source": "MM",
+                        "_mod": { "entries": { "mode": "prependArr", "items": "Goblin bosses lead goblin bands." } }
+                    }
+                },
+                {
+                    "name": "Hobgoblin",
+                    "source": "MM",
+                    "entries": ["Hobgoblins are large goblinoids."],
+                    "_appendCopy": { "name": "Goblin", "source": "MM" }
+                }
+            ],
+            "monster": [{ "name": "Goblin", "source": "MM" }]
+        })))
+        .unwrap()
+    }
+
+    #[test]
+    fn fluff_copy() {
+        let store = store();
+        let fluff = store
+            .get("monster", &Uid::new("Goblin Boss", "MM"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            fluff.entries,
+            Some(vec![
+                Entry::String("Goblin bosses lead goblin bands."),
+                Entry::String("Goblins are small, black-hearted humanoids."),
+            ])
+        );
+        assert_eq!(fluff.images.map(|i| i.len()), Some(1));
+    }
+
+    #[test]
+    fn fluff_append_copy() {
+        let store = store();
+        let fluff = store
+            .get("monster", &Uid::new("Hobgoblin", "MM"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            fluff.entries,
+            Some(vec![
+                Entry::String("Hobgoblins are large goblinoids."),
+                Entry::String("Goblins are small, black-hearted humanoids."),
+            ])
+        );
+        assert_eq!(fluff.images.map(|i| i.len()), Some(1));
+    }
+
+    #[test]
+    fn fluff_missing_or_malformed() {
+        let store = FluffStore::from_dataset(Dataset::from_value(json!({
+            "monsterFluff": [{ "name": "Kobold", "source": "MM", "images": 5 }]
+        })))
+        .unwrap();
+
+        assert!(store
+            .get("monster", &Uid::new("Goblin", "MM"))
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            store.get("monster", &Uid::new("Kobold", "MM")),
+            Err(Error::SerdeError(_))
+        ));
+    }
+
+    #[test]
+    fn fluff_attach() {
+        let store = store();
+        let mut goblin = json!({ "name": "Goblin", "source": "MM" });
+
+        assert!(store.attach("monster", &mut goblin));
+        assert_eq!(
+            goblin["fluff"]["entries"],
+            json!(["Goblins are small, black-hearted humanoids."])
+        );
+        assert!(!store.attach("monster", &mut goblin));
+    }
+}
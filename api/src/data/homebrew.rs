@@ -0,0 +1,389 @@
+use super::{Dataset, Error, Result};
+use crate::util::meta_block::MetaBlock;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A single homebrew JSON file, e.g. `creature/Me; My Monsters.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HomebrewFile {
+    pub path: PathBuf,
+    pub value: Value,
+}
+
+impl HomebrewFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let value = serde_json::from_str(&text).map_err(|e| Error::json(path, e))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            value,
+        })
+    }
+
+    pub fn meta(&self) -> Result<MetaBlock<'_>> {
+        let meta = self
+            .value
+            .get("_meta")
+            .ok_or_else(|| Error::MissingMeta(self.path.clone()))?;
+
+        MetaBlock::deserialize(meta).map_err(|e| Error::json(&self.path, e))
+    }
+
+    /// When the file was added to the homebrew repository, as a Unix timestamp (in seconds).
+    pub fn date_added(&self) -> Result<Option<u64>> {
+        Ok(self.meta()?.date_added)
+    }
+
+    /// The records contained in this file, grouped by category.
+    pub fn dataset(&self) -> Dataset {
+        Dataset::from_value(self.value.clone())
+    }
+}
+
+/// Loads homebrew files, resolving their dependencies against a local directory of other homebrew files.
+#[derive(Debug, Clone)]
+pub struct HomebrewLoader {
+    dir: PathBuf,
+    provided: HashSet<String>,
+}
+
+impl HomebrewLoader {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            provided: HashSet::new(),
+        }
+    }
+
+    /// Marks sources as already available (e.g. the official sources),
+    /// so that depending on them does not require a homebrew file.
+    pub fn provide<I, S>(mut self, sources: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.provided
+            .extend(sources.into_iter().map(|s| s.as_ref().to_lowercase()));
+        self
+    }
+
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<HomebrewCollection> {
+        let root = HomebrewFile::load(path)?;
+
+        let mut resolver = Resolver {
+            loader: self,
+            index: self.index()?,
+            stack: Vec::new(),
+            done: HashSet::new(),
+            files: Vec::new(),
+            missing_other_sources: Vec::new(),
+        };
+
+        let name = root
+            .meta()?
+            .source_ids()
+            .next()
+            .map(str::to_owned)
+            .unwrap_or_else(|| root.path.display().to_string());
+        resolver.visit(root, name)?;
+
+        let mut dataset = Dataset::new();
+        for file in &resolver.files {
+            dataset.extend(file.dataset());
+        }
+
+        Ok(HomebrewCollection {
+            files: resolver.files,
+            dataset,
+            missing_other_sources: resolver.missing_other_sources,
+        })
+    }
+
+    /// Maps the (lowercase) `json` identifier of every source declared in the directory to the file declaring it.
+    /// If several files declare the same source, the first one (by path) is used.
+    fn index(&self) -> Result<HashMap<String, PathBuf>> {
+        #[derive(Deserialize)]
+        struct MetaOnly<'a> {
+            #[serde(borrow)]
+            _meta: Option<MetaBlock<'a>>,
+        }
+
+        let mut paths = Vec::new();
+        collect_json_files(&self.dir, &mut paths)?;
+        paths.sort();
+
+        let mut index = HashMap::new();
+        for path in paths {
+            let text = fs::read_to_string(&path).map_err(|e| Error::io(&path, e))?;
+            let meta = match serde_json::from_str::<MetaOnly>(&text) {
+                Ok(MetaOnly { _meta: Some(meta) }) => meta,
+                // Files which are not homebrew files cannot satisfy a dependency.
+                _ => continue,
+            };
+            let path = canonicalize(&path)?;
+
+            for source in meta.source_ids() {
+                index
+                    .entry(source.to_lowercase())
+                    .or_insert_with(|| path.clone());
+            }
+        }
+
+        Ok(index)
+    }
+}
+
+struct Resolver<'l> {
+    loader: &'l HomebrewLoader,
+    index: HashMap<String, PathBuf>,
+    /// The files currently being resolved, along with the source which required them.
+    stack: Vec<(PathBuf, String)>,
+    done: HashSet<PathBuf>,
+    files: Vec<HomebrewFile>,
+    missing_other_sources: Vec<String>,
+}
+
+impl Resolver<'_> {
+    fn visit(&mut self, file: HomebrewFile, name: String) -> Result<()> {
+        let path = canonicalize(&file.path)?;
+
+        let (own, dependencies, other_sources) = {
+            let meta = file.meta()?;
+            let own = meta
+                .source_ids()
+                .map(str::to_lowercase)
+                .collect::<HashSet<_>>();
+            let dependencies = meta
+                .dependencies
+                .iter()
+                .flat_map(|deps| deps.monster.iter().flatten())
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            let mut other_sources = meta
+                .other_sources
+                .iter()
+                .flat_map(|other| other.monster.iter().flat_map(|m| m.keys()))
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            other_sources.sort();
+
+            (own, dependencies, other_sources)
+        };
+
+        self.stack.push((path.clone(), name));
+
+        for dependency in dependencies {
+            if !own.contains(&dependency.to_lowercase()) {
+                self.require(&dependency, &file.path, true)?;
+            }
+        }
+        for source in other_sources {
+            if !own.contains(&source.to_lowercase()) {
+                self.require(&source, &file.path, false)?;
+            }
+        }
+
+        self.stack.pop();
+        self.done.insert(path);
+        self.files.push(file);
+
+        Ok(())
+    }
+
+    fn require(&mut self, source: &str, required_by: &Path, strict: bool) -> Result<()> {
+        let key = source.to_lowercase();
+        if self.loader.provided.contains(&key) {
+            return Ok(());
+        }
+
+        let path = match self.index.get(&key) {
+            Some(path) => path.clone(),
+            None if strict => {
+                return Err(Error::MissingDependency {
+                    dependency: source.to_owned(),
+                    required_by: required_by.to_owned(),
+                })
+            }
+            None => {
+                self.missing_other_sources.push(source.to_owned());
+                return Ok(());
+            }
+        };
+
+        if let Some(pos) = self.stack.iter().position(|(p, _)| *p == path) {
+            let mut chain = self.stack[pos..]
+                .iter()
+                .map(|(_, name)| name.clone())
+                .collect::<Vec<_>>();
+            chain.push(source.to_owned());
+
+            return Err(Error::CircularDependency(chain));
+        }
+        if self.done.contains(&path) {
+            return Ok(());
+        }
+
+        let file = HomebrewFile::load(&path)?;
+        self.visit(file, source.to_owned())
+    }
+}
+
+/// A homebrew file along with every homebrew file it depends on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HomebrewCollection {
+    files: Vec<HomebrewFile>,
+    dataset: Dataset,
+    missing_other_sources: Vec<String>,
+}
+
+impl HomebrewCollection {
+    /// Shorthand for `HomebrewLoader::new(dir).load(path)`.
+    pub fn load<P: AsRef<Path>, D: Into<PathBuf>>(path: P, dir: D) -> Result<Self> {
+        HomebrewLoader::new(dir).load(path)
+    }
+
+    /// The loaded files, with dependencies ordered before the files depending on them.
+    pub fn files(&self) -> &[HomebrewFile] {
+        &self.files
+    }
+
+    /// The records of every loaded file, merged in load order.
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    /// Sources listed in `otherSources` which could not be found. Unlike dependencies, these are optional.
+    pub fn missing_other_sources(&self) -> &[String] {
+        &self.missing_other_sources
+    }
+
+    /// Merges the homebrew records after the records of `official`.
+    pub fn merge_into(self, official: &mut Dataset) {
+        official.extend(self.dataset);
+    }
+}
+
+fn collect_json_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::io(dir, e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| Error::io(dir, e))?.path();
+
+        if path.is_dir() {
+            collect_json_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            out.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf> {
+    fs::canonicalize(path).map_err(|e| Error::io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn brew(dir: &TempDir, file: &str, source: &str, deps: &[&str], monster: &str) -> PathBuf {
+        let json = serde_json::json!({
+            "_meta": {
+                "sources": [{ "json": source, "abbreviation": source, "full": source }],
+                "dependencies": { "monster": deps },
+                "dateAdded": 1_600_000_000u64,
+            },
+            "monster": [{ "name": monster, "source": source }],
+        });
+        let path = dir.path().join(file);
+        fs::write(&path, json.to_string()).unwrap();
+        path
+    }
+
+    fn names(dataset: &Dataset) -> Vec<&str> {
+        dataset
+            .get("monster")
+            .iter()
+            .map(|m| m["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn load_with_dependencies() {
+        let dir = TempDir::new().unwrap();
+        let a = brew(&dir, "a.json", "A", &["B"], "Alpha");
+        brew(&dir, "b.json", "B", &["C"], "Beta");
+        brew(&dir, "c.json", "C", &[], "Gamma");
+
+        let collection = HomebrewCollection::load(&a, dir.path()).unwrap();
+
+        assert_eq!(collection.files().len(), 3);
+        assert_eq!(
+            collection.files()[0].date_added().unwrap(),
+            Some(1_600_000_000)
+        );
+        assert_eq!(names(collection.dataset()), vec!["Gamma", "Beta", "Alpha"]);
+
+        let mut official = Dataset::from_value(serde_json::json!({
+            "monster": [{ "name": "Goblin", "source": "MM" }],
+        }));
+        collection.merge_into(&mut official);
+        assert_eq!(names(&official), vec!["Goblin", "Gamma", "Beta", "Alpha"]);
+    }
+
+    #[test]
+    fn provided_sources_are_not_required() {
+        let dir = TempDir::new().unwrap();
+        let a = brew(&dir, "a.json", "A", &["MM"], "Alpha");
+
+        let collection = HomebrewLoader::new(dir.path())
+            .provide(vec!["MM"])
+            .load(&a)
+            .unwrap();
+
+        assert_eq!(names(collection.dataset()), vec!["Alpha"]);
+    }
+
+    #[test]
+    fn missing_dependency() {
+        let dir = TempDir::new().unwrap();
+        let a = brew(&dir, "a.json", "A", &["B"], "Alpha");
+        let b = brew(&dir, "b.json", "B", &["Nope"], "Beta");
+
+        match HomebrewCollection::load(&a, dir.path()) {
+            Err(Error::MissingDependency {
+                dependency,
+                required_by,
+            }) => {
+                assert_eq!(dependency, "Nope");
+                assert_eq!(
+                    canonicalize(&required_by).unwrap(),
+                    canonicalize(&b).unwrap()
+                );
+            }
+            other => panic!("Expected a missing dependency, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn circular_dependency() {
+        let dir = TempDir::new().unwrap();
+        let a = brew(&dir, "a.json", "A", &["B"], "Alpha");
+        brew(&dir, "b.json", "B", &["C"], "Beta");
+        brew(&dir, "c.json", "C", &["B"], "Gamma");
+
+        match HomebrewCollection::load(&a, dir.path()) {
+            Err(Error::CircularDependency(chain)) => {
+                assert_eq!(chain, vec!["B", "C", "B"]);
+            }
+            other => panic!("Expected a circular dependency, found {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,569 @@
+use super::copy::apply_copy;
+use super::{Dataset, Error, HomebrewCollection, Result, Uid};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The category holding the monster templates referenced by `_copy._trait`.
+pub(super) const TEMPLATE_CATEGORY: &str = "monsterTemplate";
+
+/// One set of records in a [LayeredStore], e.g. the official data, a homebrew pack or a table's house rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    /// Layers with a higher priority override layers with a lower priority.
+    /// Layers with equal priorities are applied in the order they were added.
+    pub priority: i32,
+    records: Vec<LayerRecord>,
+    removals: Vec<(String, Uid)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LayerRecord {
+    category: String,
+    value: Value,
+    file: Option<PathBuf>,
+}
+
+impl Layer {
+    pub fn new<S: Into<String>>(name: S, priority: i32) -> Self {
+        Self {
+            name: name.into(),
+            priority,
+            records: Vec::new(),
+            removals: Vec::new(),
+        }
+    }
+
+    /// Adds every record of a JSON file to the layer.
+    ///
+    /// Besides the usual categories, the file may contain a `_remove` object listing the UIDs of records
+    /// from lower layers to remove, e.g. `{"_remove": {"monster": ["goblin|mm"]}}`.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let value: Value = serde_json::from_str(&text).map_err(|e| Error::json(path, e))?;
+
+        if let Some(Value::Object(removals)) = value.get("_remove") {
+            for (category, uids) in removals {
+                for uid in uids
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                {
+                    let (name, source) = uid.split_once('|').unwrap_or((uid, ""));
+                    self.remove(category, Uid::new(name, source));
+                }
+            }
+        }
+
+        Ok(self.add_dataset(Dataset::from_value(value), Some(path.to_owned())))
+    }
+
+    pub fn add_dataset(&mut self, dataset: Dataset, file: Option<PathBuf>) -> &mut Self {
+        for (category, value) in dataset.iter() {
+            self.records.push(LayerRecord {
+                category: category.to_owned(),
+                value: value.clone(),
+                file: file.clone(),
+            });
+        }
+        self
+    }
+
+    /// Adds the records of every file in a homebrew collection, keeping track of which file each came from.
+    pub fn add_homebrew(&mut self, collection: &HomebrewCollection) -> &mut Self {
+        for file in collection.files() {
+            self.add_dataset(file.dataset(), Some(file.path.clone()));
+        }
+        self
+    }
+
+    /// Removes a record from the layers below this one.
+    pub fn remove(&mut self, category: &str, uid: Uid) -> &mut Self {
+        self.removals.push((category.to_owned(), uid));
+        self
+    }
+}
+
+/// Where a resolved record came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub layer: String,
+    pub file: Option<PathBuf>,
+    /// The records this record was built from through `_copy`, starting with its direct `_copy` target.
+    pub copy_chain: Vec<Uid>,
+    /// The layers whose versions of this record were overridden, from lowest to highest priority.
+    pub overridden: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedRecord {
+    pub uid: Uid,
+    /// The record with its `_copy` block applied.
+    pub value: Value,
+    pub provenance: Provenance,
+}
+
+impl ResolvedRecord {
+    /// Deserializes the record as one of the crate's types, borrowing from the record.
+    pub fn parse<'a, T: Deserialize<'a>>(&'a self) -> Result<T> {
+        T::deserialize(&self.value).map_err(Error::from)
+    }
+}
+
+/// A set of layers merged by priority, with every `_copy` resolved.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct LayeredStore {
+    layers: Vec<Layer>,
+    records: BTreeMap<String, BTreeMap<Uid, ResolvedRecord>>,
+}
+
+impl LayeredStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layer and re-resolves every record. A layer whose records cannot be resolved is
+    /// not added, and the store is left as it was.
+    pub fn add_layer(&mut self, layer: Layer) -> Result<()> {
+        self.layers.push(layer);
+        let result = self.resolve();
+        if result.is_err() {
+            self.layers.pop();
+        }
+        result
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn get(&self, category: &str, uid: &Uid) -> Option<&ResolvedRecord> {
+        self.records.get(category)?.get(uid)
+    }
+
+    /// Looks a record up and deserializes it as one of the crate's types.
+    pub fn get_as<'a, T: Deserialize<'a>>(
+        &'a self,
+        category: &str,
+        uid: &Uid,
+    ) -> Result<Option<T>> {
+        self.get(category, uid)
+            .map(ResolvedRecord::parse)
+            .transpose()
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        self.records.keys().map(String::as_str)
+    }
+
+    /// Iterates over every record of a category, ordered by UID.
+    pub fn iter(&self, category: &str) -> impl Iterator<Item = &ResolvedRecord> {
+        self.records
+            .get(category)
+            .into_iter()
+            .flat_map(|records| records.values())
+    }
+
+    /// Collects every resolved record into a single dataset.
+    pub fn to_dataset(&self) -> Dataset {
+        let mut dataset = Dataset::new();
+        for (category, records) in &self.records {
+            for record in records.values() {
+                dataset.insert(category, record.value.clone());
+            }
+        }
+        dataset
+    }
+
+    fn resolve(&mut self) -> Result<()> {
+        let mut order = (0..self.layers.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| self.layers[*i].priority);
+
+        let mut raw: BTreeMap<String, BTreeMap<Uid, ResolvedRecord>> = BTreeMap::new();
+        let mut shadowed = Shadowed::new();
+
+        for layer in order.into_iter().map(|i| &self.layers[i]) {
+            for (category, uid) in &layer.removals {
+                if let Some(records) = raw.get_mut(category) {
+                    records.remove(uid);
+                }
+                shadowed.remove(&(category.clone(), uid.clone()));
+            }
+
+            for record in &layer.records {
+                let uid = match Uid::from_record(&record.value) {
+                    Some(uid) => uid,
+                    None => continue,
+                };
+                let records = raw.entry(record.category.clone()).or_default();
+                let mut overridden = Vec::new();
+
+                let key = (record.category.clone(), uid.clone());
+                let mut lower = shadowed.remove(&key).unwrap_or_default();
+                if let Some(previous) = records.remove(&uid) {
+                    overridden = previous.provenance.overridden.clone();
+                    if previous.provenance.layer != layer.name {
+                        overridden.push(previous.provenance.layer.clone());
+                    }
+
+                    // A record copying its own UID copies the version it overrides
+                    let copies_itself = record
+                        .value
+                        .get("_copy")
+                        .is_some_and(|copy| copy_target(copy) == uid);
+                    if copies_itself {
+                        lower.push(previous);
+                        shadowed.insert(key, lower);
+                    }
+                }
+
+                records.insert(
+                    uid.clone(),
+                    ResolvedRecord {
+                        uid,
+                        value: record.value.clone(),
+                        provenance: Provenance {
+                            layer: layer.name.clone(),
+                            file: record.file.clone(),
+                            copy_chain: Vec::new(),
+                            overridden,
+                        },
+                    },
+                );
+            }
+        }
+
+        let mut resolved = raw.clone();
+        for (category, records) in &raw {
+            for uid in records.keys() {
+                let mut visiting = Vec::new();
+                resolve_copy(&raw, &shadowed, &mut resolved, category, uid, &mut visiting)?;
+            }
+        }
+
+        self.records = resolved;
+        Ok(())
+    }
+}
+
+pub(super) type Records = BTreeMap<String, BTreeMap<Uid, ResolvedRecord>>;
+
+/// The UID of the record a `_copy` block copies, in the same category as the copying record.
+pub(super) fn copy_target(copy: &Value) -> Uid {
+    Uid::from_record(copy).unwrap_or_else(|| {
+        Uid::new(
+            copy.get("name").and_then(Value::as_str).unwrap_or_default(),
+            copy.get("source")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+        )
+    })
+}
+
+/// The UID of the monster template a `_copy` block applies through `_trait`, if any.
+pub(super) fn template_target(copy: &Value) -> Option<Uid> {
+    copy.get("_trait").map(|t| {
+        Uid::new(
+            t.get("name").and_then(Value::as_str).unwrap_or_default(),
+            t.get("source").and_then(Value::as_str).unwrap_or_default(),
+        )
+    })
+}
+
+/// The versions of each record overridden by a record copying its own UID, from lowest to
+/// highest priority, e.g. the official Goblin for a house rule which copies and modifies it.
+pub(super) type Shadowed = BTreeMap<(String, Uid), Vec<ResolvedRecord>>;
+
+/// Resolves the `_copy` block of a record (and of the records it copies), memoizing the results in `resolved`.
+/// Records in `resolved` without a `_copy` block are taken as already resolved.
+pub(super) fn resolve_copy(
+    raw: &Records,
+    shadowed: &Shadowed,
+    resolved: &mut Records,
+    category: &str,
+    uid: &Uid,
+    visiting: &mut Vec<Uid>,
+) -> Result<()> {
+    let done = resolved[category][uid].value.get("_copy").is_none();
+    if done {
+        return Ok(());
+    }
+
+    let record = &raw[category][uid];
+    let lower = shadowed
+        .get(&(category.to_owned(), uid.clone()))
+        .map_or(&[][..], Vec::as_slice);
+    let (value, copy_chain) =
+        copy_record(raw, shadowed, resolved, category, record, lower, visiting)?;
+
+    let out = resolved.get_mut(category).unwrap().get_mut(uid).unwrap();
+    out.value = value;
+    out.provenance.copy_chain = copy_chain;
+
+    Ok(())
+}
+
+/// Applies the `_copy` block of a record, returning its value and copy chain. A `_copy` of the
+/// record's own UID copies the highest of the `lower` versions it overrides.
+fn copy_record(
+    raw: &Records,
+    shadowed: &Shadowed,
+    resolved: &mut Records,
+    category: &str,
+    record: &ResolvedRecord,
+    lower: &[ResolvedRecord],
+    visiting: &mut Vec<Uid>,
+) -> Result<(Value, Vec<Uid>)> {
+    let uid = &record.uid;
+    let copy = match record.value.get("_copy") {
+        Some(copy) => copy,
+        None => return Ok((record.value.clone(), Vec::new())),
+    };
+    let target = copy_target(copy);
+
+    let (base, base_chain) = match lower.split_last() {
+        Some((below, rest)) if target == *uid => {
+            copy_record(raw, shadowed, resolved, category, below, rest, visiting)?
+        }
+        _ => {
+            if let Some(pos) = visiting.iter().position(|it| *it == target) {
+                let mut chain = visiting[pos..]
+                    .iter()
+                    .map(Uid::to_string)
+                    .collect::<Vec<_>>();
+                chain.push(uid.to_string());
+                chain.push(target.to_string());
+                return Err(Error::CircularCopy(chain));
+            }
+            if !raw[category].contains_key(&target) {
+                return Err(Error::MissingCopySource {
+                    category: category.to_owned(),
+                    uid: uid.to_string(),
+                    target: target.to_string(),
+                });
+            }
+
+            visiting.push(uid.clone());
+            resolve_copy(raw, shadowed, resolved, category, &target, visiting)?;
+            visiting.pop();
+
+            let base = &resolved[category][&target];
+            (base.value.clone(), base.provenance.copy_chain.clone())
+        }
+    };
+
+    let template_uid = template_target(copy);
+    if let Some(template_uid) = &template_uid {
+        let exists = raw
+            .get(TEMPLATE_CATEGORY)
+            .is_some_and(|t| t.contains_key(template_uid));
+        if !exists {
+            return Err(Error::MissingCopySource {
+                category: TEMPLATE_CATEGORY.to_owned(),
+                uid: uid.to_string(),
+                target: template_uid.to_string(),
+            });
+        }
+        visiting.push(uid.clone());
+        resolve_copy(
+            raw,
+            shadowed,
+            resolved,
+            TEMPLATE_CATEGORY,
+            template_uid,
+            visiting,
+        )?;
+        visiting.pop();
+    }
+
+    let template = template_uid.map(|t| resolved[TEMPLATE_CATEGORY][&t].value.clone());
+    let value = apply_copy(&record.value, &base, template.as_ref()).map_err(|e| Error::Copy {
+        uid: uid.to_string(),
+        source: e,
+    })?;
+
+    let mut copy_chain = vec![target];
+    copy_chain.extend(base_chain);
+    Ok((value, copy_chain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layer(name: &str, priority: i32, data: Value) -> Layer {
+        let mut layer = Layer::new(name, priority);
+        layer.add_dataset(
+            Dataset::from_value(data),
+            Some(PathBuf::from(format!("{}.json", name))),
+        );
+        layer
+    }
+
+    #[test]
+    fn later_layers_override() {
+        let mut store = LayeredStore::new();
+        store
+            .add_layer(layer(
+                "official",
+                0,
+                json!({ "monster": [
+                    { "name": "Goblin", "source": "MM", "hp": { "average": 7 } },
+                    { "name": "Orc", "source": "MM", "hp": { "average": 15 } },
+                ]}),
+            ))
+            .unwrap();
+
+        let mut house = layer(
+            "house rules",
+            10,
+            json!({ "monster": [{ "name": "Goblin", "source": "MM", "hp": { "average": 10 } }] }),
+        );
+        house.remove("monster", Uid::new("Orc", "MM"));
+        store.add_layer(house).unwrap();
+
+        let goblin = store.get("monster", &Uid::new("goblin", "mm")).unwrap();
+        assert_eq!(goblin.value["hp"]["average"], 10);
+        assert_eq!(goblin.provenance.layer, "house rules");
+        assert_eq!(
+            goblin.provenance.file,
+            Some(PathBuf::from("house rules.json"))
+        );
+        assert_eq!(goblin.provenance.overridden, vec!["official".to_owned()]);
+        assert!(store.get("monster", &Uid::new("Orc", "MM")).is_none());
+    }
+
+    #[test]
+    fn priority_beats_insertion_order() {
+        let mut store = LayeredStore::new();
+        store
+            .add_layer(layer(
+                "high",
+                5,
+                json!({ "spell": [{ "name": "Light", "source": "PHB", "level": 1 }] }),
+            ))
+            .unwrap();
+        store
+            .add_layer(layer(
+                "low",
+                0,
+                json!({ "spell": [{ "name": "Light", "source": "PHB", "level": 0 }] }),
+            ))
+            .unwrap();
+
+        let light = store.get("spell", &Uid::new("Light", "PHB")).unwrap();
+        assert_eq!(light.value["level"], 1);
+        assert_eq!(light.provenance.overridden, vec!["low".to_owned()]);
+    }
+
+    #[test]
+    fn copy_chain_across_layers() {
+        let mut store = LayeredStore::new();
+        store
+            .add_layer(layer(
+                "official",
+                0,
+                json!({ "monster": [{ "name": "Goblin", "source": "MM", "str": 8, "dex": 14 }] }),
+            ))
+            .unwrap();
+        store
+            .add_layer(layer(
+                "brew",
+                1,
+                json!({ "monster": [
+                    { "name": "Goblin Chief", "source": "HB", "_copy": { "name": "Goblin Boss", "source": "HB" }, "str": 12 },
+                    { "name": "Goblin Boss", "source": "HB", "_copy": { "name": "Goblin", "source": "MM" }, "dex": 16 },
+                ]}),
+            ))
+            .unwrap();
+
+        let chief = store
+            .get("monster", &Uid::new("Goblin Chief", "HB"))
+            .unwrap();
+        assert_eq!(chief.value["str"], 12);
+        assert_eq!(chief.value["dex"], 16);
+        assert_eq!(
+            chief.provenance.copy_chain,
+            vec![Uid::new("Goblin Boss", "HB"), Uid::new("Goblin", "MM")]
+        );
+
+        #[derive(Deserialize)]
+        struct Named<'a> {
+            name: &'a str,
+        }
+        let named = store
+            .get_as::<Named>("monster", &Uid::new("Goblin Chief", "HB"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(named.name, "Goblin Chief");
+    }
+
+    #[test]
+    fn copy_overridden_record() {
+        let mut store = LayeredStore::new();
+        store
+            .add_layer(layer(
+                "official",
+                0,
+                json!({ "monster": [{ "name": "Goblin", "source": "MM", "hp": { "average": 7 }, "trait": [{ "name": "Nimble Escape" }] }] }),
+            ))
+            .unwrap();
+        store
+            .add_layer(layer(
+                "house rules",
+                1,
+                json!({ "monster": [{
+                    "name": "Goblin",
+                    "source": "MM",
+                    "_copy": { "name": "Goblin", "source": "MM", "_mod": { "trait": { "mode": "appendArr", "items": { "name": "Grit" } } } },
+                    "hp": { "average": 10 }
+                }] }),
+            ))
+            .unwrap();
+
+        let goblin = store.get("monster", &Uid::new("Goblin", "MM")).unwrap();
+        assert_eq!(goblin.value["hp"]["average"], 10);
+        assert_eq!(
+            goblin.value["trait"],
+            json!([{ "name": "Nimble Escape" }, { "name": "Grit" }])
+        );
+        assert_eq!(goblin.provenance.layer, "house rules");
+        assert_eq!(goblin.provenance.overridden, vec!["official".to_owned()]);
+        assert_eq!(goblin.provenance.copy_chain, vec![Uid::new("Goblin", "MM")]);
+    }
+
+    #[test]
+    fn circular_copy() {
+        let mut store = LayeredStore::new();
+        let result = store.add_layer(layer(
+            "brew",
+            0,
+            json!({ "monster": [
+                { "name": "A", "source": "HB", "_copy": { "name": "B", "source": "HB" } },
+                { "name": "B", "source": "HB", "_copy": { "name": "A", "source": "HB" } },
+            ]}),
+        ));
+
+        match result {
+            Err(Error::CircularCopy(chain)) => assert_eq!(chain, vec!["a|hb", "b|hb", "a|hb"]),
+            other => panic!("Expected a circular copy, found {:?}", other),
+        }
+
+        // The failed layer is not kept, so later layers can still be added
+        assert!(store.layers().is_empty());
+        store
+            .add_layer(layer(
+                "base",
+                0,
+                json!({ "monster": [{ "name": "A", "source": "HB" }] }),
+            ))
+            .unwrap();
+        assert!(store.get("monster", &Uid::new("A", "HB")).is_some());
+    }
+}
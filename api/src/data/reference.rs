@@ -0,0 +1,435 @@
+use super::{ClassFeatureUid, DataStore, StoreRecord, SubclassFeatureUid, TagTarget, Uid};
+use crate::string::{tokenize, Lexeme, LexemeTag};
+use crate::validate::{Diagnostic, Path, PathSegment, Severity};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// How deeply features referring to other features are expanded.
+const MAX_FEATURE_DEPTH: usize = 8;
+
+/// A tag referring to a record which does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenReference {
+    /// The category and UID of the record containing the tag, when found by [ReferenceResolver::crawl].
+    pub record: Option<(String, Uid)>,
+    /// Where the string containing the tag is, within the record or checked value.
+    pub path: Path,
+    /// The tag as written, e.g. `{@spell fireball|xge}`, or the reference of a
+    /// `refClassFeature` or `refSubclassFeature` entry.
+    pub tag: String,
+    /// The UID the tag refers to, if its arguments make one up.
+    pub uid: Option<Uid>,
+    pub message: String,
+}
+
+impl BrokenReference {
+    fn new(tag: &LexemeTag, uid: Option<Uid>, message: String) -> Self {
+        Self {
+            record: None,
+            path: Path::default(),
+            tag: tag.to_string(),
+            uid,
+            message,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let message = match &self.record {
+            Some((category, uid)) => format!("{} `{}`: {}", category, uid, self.message),
+            None => self.message.clone(),
+        };
+
+        Diagnostic {
+            severity: Severity::Error,
+            path: self.path.clone(),
+            message,
+            expected: Vec::new(),
+            snippet: self.tag.clone(),
+        }
+    }
+}
+
+impl fmt::Display for BrokenReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((category, uid)) = &self.record {
+            write!(f, "{} `{}`: ", category, uid)?;
+        }
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Looks up the records referred to by tags such as `{@spell fireball}` in a [DataStore].
+#[derive(Debug, Copy, Clone)]
+pub struct ReferenceResolver<'s> {
+    store: &'s DataStore,
+}
+
+impl<'s> ReferenceResolver<'s> {
+    pub fn new(store: &'s DataStore) -> Self {
+        Self { store }
+    }
+
+    /// The record a tag refers to, or `None` if the tag does not refer to records, e.g. `{@b}`.
+    pub fn resolve(
+        &self,
+        tag: &LexemeTag,
+    ) -> Option<std::result::Result<StoreRecord<'s>, BrokenReference>> {
+        let target = TagTarget::of(tag.name)?;
+        let uid = match target.uid(&tag.args) {
+            Some(uid) => uid,
+            None => {
+                let message = format!("`{}` does not name a record", tag);
+                return Some(Err(BrokenReference::new(tag, None, message)));
+            }
+        };
+
+        Some(self.store.lookup(tag.name, &tag.args).ok_or_else(|| {
+            let message = format!("`{}` refers to `{}`, which does not exist", tag, uid);
+            BrokenReference::new(tag, Some(uid), message)
+        }))
+    }
+
+    /// The record a tag refers to as one of the crate's types, e.g. a
+    /// [Creature](crate::creature::Creature) for `{@creature goblin}`.
+    /// A record which cannot be deserialized as `T` is reported as broken.
+    pub fn resolve_as<T: Deserialize<'s>>(
+        &self,
+        tag: &LexemeTag,
+    ) -> Option<std::result::Result<T, BrokenReference>> {
+        let record = match self.resolve(tag)? {
+            Ok(record) => record,
+            Err(broken) => return Some(Err(broken)),
+        };
+
+        Some(record.parse().map_err(|e| {
+            let message = format!(
+                "`{}` refers to `{}`, which is invalid: {}",
+                tag, record.uid, e
+            );
+            BrokenReference::new(tag, Some(record.uid.clone()), message)
+        }))
+    }
+
+    /// The feature a `refClassFeature` or `refSubclassFeature` entry refers to,
+    /// or `None` if the value is not such an entry.
+    pub fn resolve_feature(
+        &self,
+        entry: &Value,
+    ) -> Option<std::result::Result<&'s Value, BrokenReference>> {
+        let (category, reference) = match entry.get("type")?.as_str()? {
+            "refClassFeature" => ("classFeature", entry.get("classFeature")?.as_str()?),
+            "refSubclassFeature" => ("subclassFeature", entry.get("subclassFeature")?.as_str()?),
+            _ => return None,
+        };
+        let uid = match category {
+            "classFeature" => reference.parse::<ClassFeatureUid>().map(|uid| uid.uid()),
+            _ => reference.parse::<SubclassFeatureUid>().map(|uid| uid.uid()),
+        };
+        let broken = |uid, message| BrokenReference {
+            record: None,
+            path: Path::default(),
+            tag: reference.to_owned(),
+            uid,
+            message,
+        };
+
+        Some(match uid {
+            Ok(uid) => self.store.get(category, &uid).ok_or_else(|| {
+                let message = format!("`{}` refers to `{}`, which does not exist", reference, uid);
+                broken(Some(uid), message)
+            }),
+            Err(e) => Err(broken(None, e.to_string())),
+        })
+    }
+
+    /// Replaces `refClassFeature` and `refSubclassFeature` entries with the features they refer
+    /// to, as `entries` entries, so that a class page can be shown in full rather than as
+    /// references. Features which refer to other features are expanded in turn.
+    /// References which cannot be resolved are left as they are.
+    pub fn expand_features(&self, value: &Value) -> Value {
+        self.expand(value, 0)
+    }
+
+    fn expand(&self, value: &Value, depth: usize) -> Value {
+        match value {
+            Value::Array(values) => values.iter().map(|v| self.expand(v, depth)).collect(),
+            Value::Object(object) => {
+                if depth < MAX_FEATURE_DEPTH {
+                    if let Some(Ok(feature)) = self.resolve_feature(value) {
+                        return self.expand(&feature_entry(feature), depth + 1);
+                    }
+                }
+                let object = object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.expand(value, depth)))
+                    .collect::<Map<_, _>>();
+                Value::Object(object)
+            }
+            value => value.clone(),
+        }
+    }
+
+    /// Every broken reference in a string, including tags nested in other tags.
+    pub fn check_text(&self, text: &str) -> Vec<BrokenReference> {
+        let mut broken = Vec::new();
+        self.check_text_into(text, &mut broken);
+        broken
+    }
+
+    /// Every broken reference in the strings of a value, e.g. a homebrew file, with their paths.
+    pub fn check_value(&self, value: &Value) -> Vec<BrokenReference> {
+        let mut broken = Vec::new();
+        self.check_value_into(value, &mut Vec::new(), &mut broken);
+        broken
+    }
+
+    /// Every broken reference in every record of the store.
+    pub fn crawl(&self) -> Vec<BrokenReference> {
+        let mut broken = Vec::new();
+        for category in self.store.categories() {
+            for (uid, record) in self.store.iter(category) {
+                let start = broken.len();
+                self.check_value_into(record, &mut Vec::new(), &mut broken);
+                for reference in &mut broken[start..] {
+                    reference.record = Some((category.to_owned(), uid.clone()));
+                }
+            }
+        }
+        broken
+    }
+
+    fn check_value_into(
+        &self,
+        value: &Value,
+        path: &mut Vec<PathSegment>,
+        broken: &mut Vec<BrokenReference>,
+    ) {
+        match value {
+            Value::String(text) => {
+                let start = broken.len();
+                self.check_text_into(text, broken);
+                for reference in &mut broken[start..] {
+                    reference.path = Path::new(path.clone());
+                }
+            }
+            Value::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    self.check_value_into(value, path, broken);
+                    path.pop();
+                }
+            }
+            Value::Object(object) => {
+                if let Some(Err(mut reference)) = self.resolve_feature(value) {
+                    reference.path = Path::new(path.clone());
+                    broken.push(reference);
+                }
+                for (key, value) in object {
+                    path.push(PathSegment::Key(key.clone()));
+                    self.check_value_into(value, path, broken);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_text_into(&self, text: &str, broken: &mut Vec<BrokenReference>) {
+        // Text which cannot be tokenized is the validator's concern, not ours
+        let lexemes = match tokenize(text) {
+            Ok(lexemes) => lexemes,
+            Err(_) => return,
+        };
+
+        for lexeme in lexemes {
+            if let Lexeme::Tag(tag) = lexeme {
+                if let Some(Err(reference)) = self.resolve(&tag) {
+                    broken.push(reference);
+                }
+                for arg in &tag.args {
+                    self.check_text_into(arg, broken);
+                }
+            }
+        }
+    }
+}
+
+/// A feature as an `entries` entry, the way it is shown on a class page.
+fn feature_entry(feature: &Value) -> Value {
+    let mut entry = Map::new();
+    entry.insert("type".to_owned(), "entries".into());
+    for key in ["name", "source", "page", "entries"] {
+        if let Some(value) = feature.get(key) {
+            entry.insert(key.to_owned(), value.clone());
+        }
+    }
+    Value::Object(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::Creature;
+    use crate::data::Dataset;
+    use serde_json::json;
+
+    fn store() -> DataStore {
+        DataStore::from_dataset(Dataset::from_value(json!({
+            "spell": [{ "name": "Fireball", "source": "PHB", "entries": ["See also {@spell lightning bolt}."] }],
+            "monster": [{
+                "name": "Goblin",
+                "source": "MM",
+                "action": [{ "name": "Scimitar", "entries": ["{@note Casts {@spell fireball} or {@spell fireball|xge}.}"] }]
+            }],
+            "classFeature": [
+                { "name": "Extra Attack", "source": "PHB", "className": "Fighter", "classSource": "PHB", "level": 5, "entries": ["You can attack twice."] },
+                { "name": "Extra Attack", "source": "PHB", "className": "Paladin", "classSource": "PHB", "level": 5 },
+                {
+                    "name": "Martial Archetype", "source": "PHB", "className": "Fighter", "classSource": "PHB", "level": 3,
+                    "entries": [
+                        "Choose an archetype.",
+                        { "type": "refSubclassFeature", "subclassFeature": "Champion|Fighter||Champion||3" },
+                        { "type": "refSubclassFeature", "subclassFeature": "Battle Master|Fighter||Battle Master||3" }
+                    ]
+                }
+            ],
+            "baseitem": [{ "name": "Longsword", "source": "PHB", "entries": ["Often a {@item +1 weapon|dmg}, such as a {@item +1 longsword}."] }],
+            "magicvariant": [{ "name": "+1 Weapon", "inherits": { "namePrefix": "+1 ", "source": "DMG" } }],
+            "subclassFeature": [
+                { "name": "Champion", "source": "PHB", "className": "Fighter", "classSource": "PHB", "subclassShortName": "Champion", "subclassSource": "PHB", "level": 3, "entries": ["Raw physical power."] }
+            ]
+        })))
+        .unwrap()
+    }
+
+    fn tag(text: &str) -> LexemeTag<'_> {
+        match tokenize(text).unwrap().next() {
+            Some(Lexeme::Tag(tag)) => tag,
+            _ => panic!("not a tag"),
+        }
+    }
+
+    #[test]
+    fn resolve_tags() {
+        let store = store();
+        let resolver = ReferenceResolver::new(&store);
+
+        let fireball = resolver
+            .resolve(&tag("{@spell Fireball}"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fireball.uid.as_str(), "fireball|phb");
+        assert!(resolver.resolve(&tag("{@b bold}")).is_none());
+
+        let broken = resolver
+            .resolve(&tag("{@spell fireball|xge}"))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(broken.uid, Some(Uid::new("fireball", "xge")));
+
+        let goblin: Creature = resolver
+            .resolve_as(&tag("{@creature goblin||Goblins}"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(goblin.name, "Goblin");
+
+        let paladin = resolver
+            .resolve(&tag("{@classFeature Extra Attack|Paladin||5}"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(paladin.value["className"], "Paladin");
+        assert!(resolver
+            .resolve(&tag("{@classFeature Extra Attack|Rogue||5}"))
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn expand_class_features() {
+        let store = store();
+        let resolver = ReferenceResolver::new(&store);
+        let class = json!({
+            "name": "Fighter",
+            "classFeatures": [],
+            "entries": [
+                { "type": "refClassFeature", "classFeature": "Martial Archetype|Fighter||3" },
+                { "type": "refClassFeature", "classFeature": "Extra Attack|Fighter||5" },
+                { "type": "refClassFeature", "classFeature": "Indomitable|Fighter||9" }
+            ]
+        });
+
+        assert_eq!(
+            resolver.expand_features(&class)["entries"],
+            json!([
+                {
+                    "type": "entries",
+                    "name": "Martial Archetype",
+                    "source": "PHB",
+                    "entries": [
+                        "Choose an archetype.",
+                        { "type": "entries", "name": "Champion", "source": "PHB", "entries": ["Raw physical power."] },
+                        { "type": "refSubclassFeature", "subclassFeature": "Battle Master|Fighter||Battle Master||3" }
+                    ]
+                },
+                { "type": "entries", "name": "Extra Attack", "source": "PHB", "entries": ["You can attack twice."] },
+                { "type": "refClassFeature", "classFeature": "Indomitable|Fighter||9" }
+            ])
+        );
+
+        let broken = resolver.check_value(&class);
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].path.to_string(), "entries[2]");
+        assert_eq!(
+            broken[0].uid,
+            Some(Uid::from_parts(&[
+                "Indomitable",
+                "Fighter",
+                "PHB",
+                "9",
+                "PHB"
+            ]))
+        );
+    }
+
+    #[test]
+    fn crawl_store() {
+        let store = store();
+        let broken = ReferenceResolver::new(&store).crawl();
+
+        let found = broken
+            .iter()
+            .map(|b| {
+                (
+                    b.record.clone().unwrap().1.to_string(),
+                    b.path.to_string(),
+                    b.tag.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "martial archetype|fighter|phb|3|phb".to_owned(),
+                    "entries[2]".to_owned(),
+                    "Battle Master|Fighter||Battle Master||3"
+                ),
+                (
+                    "goblin|mm".to_owned(),
+                    "action[0].entries[0]".to_owned(),
+                    "{@spell fireball|xge}"
+                ),
+                (
+                    "fireball|phb".to_owned(),
+                    "entries[0]".to_owned(),
+                    "{@spell lightning bolt}"
+                ),
+            ]
+        );
+        assert_eq!(
+            broken[1].to_diagnostic().message,
+            "monster `goblin|mm`: `{@spell fireball|xge}` refers to `fireball|xge`, which does not exist"
+        );
+    }
+}
@@ -0,0 +1,620 @@
+use super::{DataStore, HomebrewCollection, Result};
+use crate::util::meta_block::MetaBlock;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// The kind of publication a source is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SourceGroup {
+    Core,
+    Supplement,
+    Adventure,
+    UnearthedArcana,
+    Homebrew,
+}
+
+impl SourceGroup {
+    /// The name of the group as shown in the source filter of 5etools, e.g. `Unearthed Arcana`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceGroup::Core => "Core",
+            SourceGroup::Supplement => "Supplement",
+            SourceGroup::Adventure => "Adventure",
+            SourceGroup::UnearthedArcana => "Unearthed Arcana",
+            SourceGroup::Homebrew => "Homebrew",
+        }
+    }
+}
+
+/// What is known about a source, e.g. `XGE` for Xanathar's Guide to Everything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceInfo {
+    /// The identifier used in the `source` field of records, e.g. `XGE`.
+    pub json: String,
+    pub abbreviation: String,
+    pub full: String,
+    pub group: SourceGroup,
+    /// ISO 8601 date, e.g. `2017-11-21`.
+    pub published: Option<String>,
+    /// Whether the source contains System Reference Document content.
+    pub srd: bool,
+    /// Whether the source is published by a partner of Wizards of the Coast.
+    pub partnered: bool,
+}
+
+struct Official {
+    json: &'static str,
+    abbreviation: &'static str,
+    full: &'static str,
+    group: SourceGroup,
+    published: &'static str,
+}
+
+const fn official(
+    json: &'static str,
+    abbreviation: &'static str,
+    full: &'static str,
+    group: SourceGroup,
+    published: &'static str,
+) -> Official {
+    Official {
+        json,
+        abbreviation,
+        full,
+        group,
+        published,
+    }
+}
+
+use SourceGroup::{Adventure, Core, Supplement};
+
+const OFFICIAL: [Official; 47] = [
+    official("PHB", "PHB", "Player's Handbook (2014)", Core, "2014-08-19"),
+    official("MM", "MM", "Monster Manual (2014)", Core, "2014-09-30"),
+    official(
+        "DMG",
+        "DMG",
+        "Dungeon Master's Guide (2014)",
+        Core,
+        "2014-12-09",
+    ),
+    official(
+        "XPHB",
+        "PHB'24",
+        "Player's Handbook (2024)",
+        Core,
+        "2024-09-17",
+    ),
+    official(
+        "XDMG",
+        "DMG'24",
+        "Dungeon Master's Guide (2024)",
+        Core,
+        "2024-11-12",
+    ),
+    official("XMM", "MM'25", "Monster Manual (2025)", Core, "2025-02-18"),
+    official(
+        "SCAG",
+        "SCAG",
+        "Sword Coast Adventurer's Guide",
+        Supplement,
+        "2015-11-03",
+    ),
+    official(
+        "VGM",
+        "VGM",
+        "Volo's Guide to Monsters",
+        Supplement,
+        "2016-11-15",
+    ),
+    official(
+        "XGE",
+        "XGE",
+        "Xanathar's Guide to Everything",
+        Supplement,
+        "2017-11-21",
+    ),
+    official(
+        "MTF",
+        "MTF",
+        "Mordenkainen's Tome of Foes",
+        Supplement,
+        "2018-05-29",
+    ),
+    official(
+        "GGR",
+        "GGR",
+        "Guildmasters' Guide to Ravnica",
+        Supplement,
+        "2018-11-20",
+    ),
+    official(
+        "AI",
+        "AI",
+        "Acquisitions Incorporated",
+        Supplement,
+        "2019-06-18",
+    ),
+    official(
+        "ERLW",
+        "E:RLW",
+        "Eberron: Rising from the Last War",
+        Supplement,
+        "2019-11-19",
+    ),
+    official(
+        "EGW",
+        "EGW",
+        "Explorer's Guide to Wildemount",
+        Supplement,
+        "2020-03-17",
+    ),
+    official(
+        "MOT",
+        "MOT",
+        "Mythic Odysseys of Theros",
+        Supplement,
+        "2020-06-02",
+    ),
+    official(
+        "TCE",
+        "TCE",
+        "Tasha's Cauldron of Everything",
+        Supplement,
+        "2020-11-17",
+    ),
+    official(
+        "VRGR",
+        "VRGR",
+        "Van Richten's Guide to Ravenloft",
+        Supplement,
+        "2021-05-18",
+    ),
+    official(
+        "FTD",
+        "FTD",
+        "Fizban's Treasury of Dragons",
+        Supplement,
+        "2021-10-26",
+    ),
+    official(
+        "SCC",
+        "SCC",
+        "Strixhaven: A Curriculum of Chaos",
+        Supplement,
+        "2021-12-07",
+    ),
+    official(
+        "MPMM",
+        "MPMM",
+        "Mordenkainen Presents: Monsters of the Multiverse",
+        Supplement,
+        "2022-05-17",
+    ),
+    official(
+        "BGG",
+        "BGG",
+        "Bigby Presents: Glory of the Giants",
+        Supplement,
+        "2023-08-15",
+    ),
+    official(
+        "BMT",
+        "BMT",
+        "The Book of Many Things",
+        Supplement,
+        "2023-11-14",
+    ),
+    official(
+        "TDCSR",
+        "TDCSR",
+        "Tal'Dorei Campaign Setting Reborn",
+        Supplement,
+        "2022-01-18",
+    ),
+    official(
+        "HWCS",
+        "HWCS",
+        "Humblewood Campaign Setting",
+        Supplement,
+        "2019-09-30",
+    ),
+    official(
+        "LMoP",
+        "LMoP",
+        "Lost Mine of Phandelver",
+        Adventure,
+        "2014-07-15",
+    ),
+    official(
+        "HotDQ",
+        "HotDQ",
+        "Hoard of the Dragon Queen",
+        Adventure,
+        "2014-08-19",
+    ),
+    official("RoT", "RoT", "The Rise of Tiamat", Adventure, "2014-11-04"),
+    official(
+        "PotA",
+        "PotA",
+        "Princes of the Apocalypse",
+        Adventure,
+        "2015-04-07",
+    ),
+    official("OotA", "OotA", "Out of the Abyss", Adventure, "2015-09-15"),
+    official("CoS", "CoS", "Curse of Strahd", Adventure, "2016-03-15"),
+    official(
+        "SKT",
+        "SKT",
+        "Storm King's Thunder",
+        Adventure,
+        "2016-09-06",
+    ),
+    official(
+        "TftYP",
+        "TftYP",
+        "Tales from the Yawning Portal",
+        Adventure,
+        "2017-04-04",
+    ),
+    official(
+        "ToA",
+        "ToA",
+        "Tomb of Annihilation",
+        Adventure,
+        "2017-09-19",
+    ),
+    official(
+        "WDH",
+        "WDH",
+        "Waterdeep: Dragon Heist",
+        Adventure,
+        "2018-09-18",
+    ),
+    official(
+        "WDMM",
+        "WDMM",
+        "Waterdeep: Dungeon of the Mad Mage",
+        Adventure,
+        "2018-11-20",
+    ),
+    official(
+        "BGDIA",
+        "BGDIA",
+        "Baldur's Gate: Descent Into Avernus",
+        Adventure,
+        "2019-09-17",
+    ),
+    official(
+        "IDRotF",
+        "IDRotF",
+        "Icewind Dale: Rime of the Frostmaiden",
+        Adventure,
+        "2020-09-15",
+    ),
+    official(
+        "WBtW",
+        "WBtW",
+        "The Wild Beyond the Witchlight",
+        Adventure,
+        "2021-09-21",
+    ),
+    official(
+        "CRCotN",
+        "CRCotN",
+        "Critical Role: Call of the Netherdeep",
+        Adventure,
+        "2022-03-15",
+    ),
+    official(
+        "DSotDQ",
+        "DSotDQ",
+        "Dragonlance: Shadow of the Dragon Queen",
+        Adventure,
+        "2022-11-22",
+    ),
+    official(
+        "KftGV",
+        "KftGV",
+        "Keys from the Golden Vault",
+        Adventure,
+        "2023-02-21",
+    ),
+    official(
+        "PaBTSO",
+        "PaBTSO",
+        "Phandelver and Below: The Shattered Obelisk",
+        Adventure,
+        "2023-09-19",
+    ),
+    official(
+        "ToFW",
+        "ToFW",
+        "Turn of Fortune's Wheel",
+        Adventure,
+        "2023-12-19",
+    ),
+    official(
+        "VEoR",
+        "VEoR",
+        "Vecna: Eve of Ruin",
+        Adventure,
+        "2024-05-21",
+    ),
+    official(
+        "HWAitW",
+        "HWAitW",
+        "Humblewood: Adventure in the Wood",
+        Adventure,
+        "2019-09-30",
+    ),
+    official(
+        "GHLoE",
+        "GHLoE",
+        "Grim Hollow: Lairs of Etharis",
+        Adventure,
+        "2023-07-21",
+    ),
+    official(
+        "DoDk",
+        "DoDk",
+        "Dungeons of Drakkenheim",
+        Adventure,
+        "2023-07-18",
+    ),
+];
+
+/// Sources containing System Reference Document content.
+const SRD: [&str; 6] = ["PHB", "MM", "DMG", "XPHB", "XDMG", "XMM"];
+
+/// Sources published by partners of Wizards of the Coast.
+const PARTNERED: [&str; 5] = ["TDCSR", "HWCS", "HWAitW", "GHLoE", "DoDk"];
+
+/// Every known source, looked up case-insensitively by its `json` identifier.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct SourceRegistry {
+    sources: BTreeMap<String, SourceInfo>,
+}
+
+impl SourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The official books and adventures known to the crate.
+    pub fn official() -> Self {
+        let mut registry = Self::new();
+        for source in &OFFICIAL {
+            registry.insert(SourceInfo {
+                json: source.json.to_owned(),
+                abbreviation: source.abbreviation.to_owned(),
+                full: source.full.to_owned(),
+                group: source.group,
+                published: Some(source.published.to_owned()),
+                srd: SRD.contains(&source.json),
+                partnered: PARTNERED.contains(&source.json),
+            });
+        }
+        registry
+    }
+
+    /// The registry of [SourceRegistry::official], built once, for filters and searches which
+    /// are not given a registry.
+    pub(crate) fn official_shared() -> &'static Self {
+        static OFFICIAL_REGISTRY: OnceLock<SourceRegistry> = OnceLock::new();
+        OFFICIAL_REGISTRY.get_or_init(Self::official)
+    }
+
+    /// Adds or replaces a source.
+    pub fn insert(&mut self, source: SourceInfo) {
+        self.sources.insert(source.json.to_lowercase(), source);
+    }
+
+    /// Adds the books and adventures of the official `books.json` and `adventures.json`,
+    /// which cover sources released after the crate.
+    pub fn add_store(&mut self, store: &DataStore) {
+        for category in ["book", "adventure"] {
+            for (_, record) in store.iter(category) {
+                if let Some(source) = source_from_record(category, record) {
+                    let known = self.get(&source.json).cloned();
+                    self.insert(SourceInfo {
+                        abbreviation: known
+                            .as_ref()
+                            .map_or(source.abbreviation, |known| known.abbreviation.clone()),
+                        srd: known.as_ref().is_some_and(|known| known.srd),
+                        partnered: known.as_ref().is_some_and(|known| known.partnered),
+                        ..source
+                    });
+                }
+            }
+        }
+    }
+
+    /// Adds the sources declared in a homebrew `_meta` block.
+    pub fn add_meta(&mut self, meta: &MetaBlock) {
+        for source in meta.sources.iter().flatten() {
+            self.insert(SourceInfo {
+                json: source.json.to_owned(),
+                abbreviation: source.abbreviation.unwrap_or(source.json).to_owned(),
+                full: source.full.unwrap_or(source.json).to_owned(),
+                group: SourceGroup::Homebrew,
+                published: source.date_released.map(str::to_owned),
+                srd: false,
+                partnered: false,
+            });
+        }
+    }
+
+    /// Adds the sources declared by every file of a homebrew collection.
+    pub fn add_homebrew(&mut self, collection: &HomebrewCollection) -> Result<()> {
+        for file in collection.files() {
+            self.add_meta(&file.meta()?);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, json: &str) -> Option<&SourceInfo> {
+        self.sources.get(&json.trim().to_lowercase())
+    }
+
+    /// The full name of a source, e.g. `Xanathar's Guide to Everything` for `XGE`.
+    pub fn full_name(&self, json: &str) -> Option<&str> {
+        self.get(json).map(|source| source.full.as_str())
+    }
+
+    /// The abbreviation shown for a source, or the identifier itself if the source is unknown.
+    pub fn abbreviation<'s>(&'s self, json: &'s str) -> &'s str {
+        self.get(json)
+            .map_or(json, |source| source.abbreviation.as_str())
+    }
+
+    /// The group of a source. Unknown sources whose identifier starts with `UA` are taken to be
+    /// Unearthed Arcana, as in `UA2020Feats`.
+    pub fn group(&self, json: &str) -> Option<SourceGroup> {
+        match self.get(json) {
+            Some(source) => Some(source.group),
+            None if json.trim().starts_with("UA") => Some(SourceGroup::UnearthedArcana),
+            None => None,
+        }
+    }
+
+    /// Whether a value given for a source, e.g. in a `{@filter}` tag, names the source with the
+    /// given identifier: as the identifier itself, its abbreviation, its full name or its group,
+    /// all ignoring case.
+    pub fn matches(&self, json: &str, value: &str) -> bool {
+        let value = value.trim();
+        let names = |source: &SourceInfo| {
+            source.abbreviation.eq_ignore_ascii_case(value)
+                || source.full.eq_ignore_ascii_case(value)
+        };
+
+        json.trim().eq_ignore_ascii_case(value)
+            || self.get(json).is_some_and(names)
+            || self
+                .group(json)
+                .is_some_and(|group| group.name().eq_ignore_ascii_case(value))
+    }
+
+    pub fn is_srd(&self, json: &str) -> bool {
+        self.get(json).is_some_and(|source| source.srd)
+    }
+
+    pub fn is_partnered(&self, json: &str) -> bool {
+        self.get(json).is_some_and(|source| source.partnered)
+    }
+
+    /// Every source, ordered by publication date, with undated sources last.
+    pub fn by_date(&self) -> Vec<&SourceInfo> {
+        let mut sources = self.sources.values().collect::<Vec<_>>();
+        sources.sort_by(|a, b| match (&a.published, &b.published) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.json.cmp(&b.json),
+        });
+        sources
+    }
+
+    /// Iterates over every source, ordered by lowercased identifier.
+    pub fn iter(&self) -> impl Iterator<Item = &SourceInfo> {
+        self.sources.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+fn source_from_record(category: &str, record: &Value) -> Option<SourceInfo> {
+    let json = record
+        .get("id")
+        .or_else(|| record.get("source"))?
+        .as_str()?;
+    let group = match (category, record.get("group").and_then(Value::as_str)) {
+        ("adventure", _) => SourceGroup::Adventure,
+        (_, Some("core")) => SourceGroup::Core,
+        (_, Some("homebrew")) => SourceGroup::Homebrew,
+        (_, Some("prerelease")) => SourceGroup::UnearthedArcana,
+        _ => SourceGroup::Supplement,
+    };
+
+    Some(SourceInfo {
+        json: json.to_owned(),
+        abbreviation: json.to_owned(),
+        full: record.get("name")?.as_str()?.to_owned(),
+        group,
+        published: record
+            .get("published")
+            .and_then(Value::as_str)
+            .map(str::to_owned),
+        srd: false,
+        partnered: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Dataset;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[test]
+    fn official_sources() {
+        let registry = SourceRegistry::official();
+
+        assert_eq!(
+            registry.full_name("xge"),
+            Some("Xanathar's Guide to Everything")
+        );
+        assert_eq!(registry.group("CoS"), Some(SourceGroup::Adventure));
+        assert_eq!(
+            registry.group("UA2020Feats"),
+            Some(SourceGroup::UnearthedArcana)
+        );
+        assert_eq!(registry.group("MyBrew"), None);
+        assert_eq!(registry.abbreviation("MyBrew"), "MyBrew");
+        assert!(registry.is_srd("PHB") && !registry.is_srd("XGE"));
+        assert!(registry.is_partnered("TDCSR"));
+        assert_eq!(registry.by_date()[0].json, "LMoP");
+        assert_eq!(registry.abbreviation("XPHB"), "PHB'24");
+
+        assert!(registry.matches("XGE", "xge"));
+        assert!(registry.matches("XGE", "Xanathar's Guide to Everything"));
+        assert!(registry.matches("XPHB", "phb'24"));
+        assert!(registry.matches("CoS", "adventure"));
+        assert!(registry.matches("UA2020Feats", "Unearthed Arcana"));
+        assert!(!registry.matches("XGE", "core"));
+        assert!(!registry.matches("MyBrew", "homebrew"));
+    }
+
+    #[test]
+    fn store_and_homebrew_sources() {
+        let store = DataStore::from_dataset(Dataset::from_value(json!({
+            "book": [{ "name": "Player's Handbook (2014)", "id": "PHB", "source": "PHB", "group": "core", "published": "2014-08-19" }],
+            "adventure": [{ "name": "A New Adventure", "id": "NEW", "source": "NEW", "group": "other", "published": "2030-01-01" }]
+        })))
+        .unwrap();
+        let mut registry = SourceRegistry::official();
+        registry.add_store(&store);
+
+        assert!(registry.is_srd("PHB"));
+        assert_eq!(registry.abbreviation("PHB"), "PHB");
+        assert_eq!(registry.group("new"), Some(SourceGroup::Adventure));
+
+        let meta = json!({
+            "sources": [{ "json": "MyBrew", "abbreviation": "MB", "full": "My Brew", "dateReleased": "2021-06-08" }]
+        });
+        registry.add_meta(&MetaBlock::deserialize(&meta).unwrap());
+        let brew = registry.get("mybrew").unwrap();
+        assert_eq!(brew.group, SourceGroup::Homebrew);
+        assert_eq!(registry.abbreviation("MyBrew"), "MB");
+        assert_eq!(brew.published.as_deref(), Some("2021-06-08"));
+        assert!(registry.matches("MyBrew", "homebrew"));
+    }
+}
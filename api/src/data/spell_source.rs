@@ -0,0 +1,165 @@
+use super::{Error, Result};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the official `data/` directory lists the classes each spell is available to.
+pub(super) const SPELL_SOURCES_FILE: &str = "generated/gendata-spell-source-lookup.json";
+
+/// The classes and subclasses each spell is available to, as listed by the
+/// `gendata-spell-source-lookup.json` file 5etools generates. Current data no longer lists them
+/// on the spells themselves.
+///
+/// The lookup is keyed by the lowercased source and name of each spell, e.g.
+/// `{"phb": {"fireball": {"class": {"PHB": {"Wizard": true}}}}}`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SpellSources {
+    lookup: Value,
+}
+
+impl SpellSources {
+    pub fn from_value(lookup: Value) -> Self {
+        Self { lookup }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let lookup = serde_json::from_str(&text).map_err(|e| Error::json(path, e))?;
+
+        Ok(Self::from_value(lookup))
+    }
+
+    /// The path of the lookup within a `data/` directory.
+    pub fn path_in<P: AsRef<Path>>(dir: P) -> PathBuf {
+        dir.as_ref().join(SPELL_SOURCES_FILE)
+    }
+
+    /// Reads the lookup of a `data/` directory, or `None` if it has none, as older data does not.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Option<Self>> {
+        let path = Self::path_in(dir);
+        match path.is_file() {
+            true => Self::from_file(path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Sets the `classes` of a spell listed by the lookup to its `fromClassList`,
+    /// `fromClassListVariant` and `fromSubclass`, as older data has them on the spell.
+    /// Spells which are not listed are left as they are.
+    pub fn apply(&self, spell: &mut Value) {
+        let (name, source) = match (spell["name"].as_str(), spell["source"].as_str()) {
+            (Some(name), Some(source)) => (name.to_lowercase(), source.to_lowercase()),
+            _ => return,
+        };
+        let sources = &self.lookup[source.as_str()][name.as_str()];
+        if !sources.is_object() {
+            return;
+        }
+
+        let mut classes = Map::new();
+        let class_list = class_entries(&sources["class"], |_| json!({}));
+        if !class_list.is_empty() {
+            classes.insert("fromClassList".to_owned(), class_list.into());
+        }
+        let variants = class_entries(&sources["classVariant"], |variant| {
+            match variant["definedInSources"]
+                .as_array()
+                .and_then(|s| s.first())
+            {
+                Some(source) => json!({ "definedInSource": source }),
+                None => json!({}),
+            }
+        });
+        if !variants.is_empty() {
+            classes.insert("fromClassListVariant".to_owned(), variants.into());
+        }
+        let subclasses = subclass_entries(&sources["subclass"]);
+        if !subclasses.is_empty() {
+            classes.insert("fromSubclass".to_owned(), subclasses.into());
+        }
+
+        if let (Some(spell), false) = (spell.as_object_mut(), classes.is_empty()) {
+            spell.insert("classes".to_owned(), classes.into());
+        }
+    }
+}
+
+/// `{"PHB": {"Wizard": ...}}` as `[{"name": "Wizard", "source": "PHB", ...}]`, with the fields
+/// `extra` gives for the value of each class.
+fn class_entries(classes: &Value, extra: impl Fn(&Value) -> Value) -> Vec<Value> {
+    let mut entries = Vec::new();
+    for (source, classes) in classes.as_object().into_iter().flatten() {
+        for (name, value) in classes.as_object().into_iter().flatten() {
+            let mut entry = json!({ "name": name, "source": source });
+            if let (Some(entry), Value::Object(extra)) = (entry.as_object_mut(), extra(value)) {
+                entry.extend(extra);
+            }
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// `{"PHB": {"Warlock": {"PHB": {"Fiend": {"name": "The Fiend"}}}}}`, keyed by the class source,
+/// class name, subclass source and subclass short name, as `fromSubclass` entries.
+fn subclass_entries(subclasses: &Value) -> Vec<Value> {
+    let mut entries = Vec::new();
+    for (class_source, classes) in subclasses.as_object().into_iter().flatten() {
+        for (class_name, sources) in classes.as_object().into_iter().flatten() {
+            for (source, subclasses) in sources.as_object().into_iter().flatten() {
+                for (short_name, subclass) in subclasses.as_object().into_iter().flatten() {
+                    let name = subclass["name"].as_str().unwrap_or(short_name);
+                    entries.push(json!({
+                        "class": { "name": class_name, "source": class_source },
+                        "subclass": { "name": name, "shortName": short_name, "source": source }
+                    }));
+                }
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_lookup() {
+        let sources = SpellSources::from_value(json!({
+            "phb": {
+                "fireball": {
+                    "class": { "PHB": { "Sorcerer": true, "Wizard": true } },
+                    "classVariant": { "PHB": { "Cleric": { "definedInSources": ["TCE"] } } },
+                    "subclass": { "PHB": { "Warlock": { "PHB": { "Fiend": { "name": "The Fiend" } } } } }
+                }
+            }
+        }));
+
+        let mut fireball = json!({ "name": "Fireball", "source": "PHB" });
+        sources.apply(&mut fireball);
+        assert_eq!(
+            fireball["classes"],
+            json!({
+                "fromClassList": [
+                    { "name": "Sorcerer", "source": "PHB" },
+                    { "name": "Wizard", "source": "PHB" }
+                ],
+                "fromClassListVariant": [
+                    { "name": "Cleric", "source": "PHB", "definedInSource": "TCE" }
+                ],
+                "fromSubclass": [{
+                    "class": { "name": "Warlock", "source": "PHB" },
+                    "subclass": { "name": "The Fiend", "shortName": "Fiend", "source": "PHB" }
+                }]
+            })
+        );
+
+        let mut shield =
+            json!({ "name": "Shield", "source": "PHB", "classes": { "fromClassList": [] } });
+        let unchanged = shield.clone();
+        sources.apply(&mut shield);
+        assert_eq!(shield, unchanged);
+    }
+}
@@ -0,0 +1,521 @@
+use super::{
+    ClassFeatureUid, Dataset, Error, Layer, LayeredStore, ParallelLoader, Result, SpellSources,
+    SubclassFeatureUid, Uid,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories of the official `data/` layout whose files are listed in an `index.json`,
+/// mapping each source to a file, e.g. `{"PHB": "spells-phb.json"}`.
+const INDEXED_DIRS: [&str; 3] = ["bestiary", "class", "spells"];
+
+/// Files in the indexed directories which are not listed in their index.
+const UNINDEXED_FILES: [(&str, &str); 2] = [
+    ("bestiary", "legendarygroups.json"),
+    ("bestiary", "template.json"),
+];
+
+/// What a tag such as `{@spell fireball}` refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TagTarget {
+    /// The categories searched, in order, e.g. `item` before `baseitem`.
+    pub categories: &'static [&'static str],
+    /// The source assumed when the tag gives none, lowercased.
+    pub default_source: &'static str,
+    pub format: UidFormat,
+}
+
+/// How the arguments of a tag make up the UID of the record it refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UidFormat {
+    /// `name|source`, with the source at the given argument,
+    /// e.g. 2 for `{@deity name|pantheon|source}`.
+    NameSource { source_arg: usize },
+    /// `name|className|classSource|level|source`, where the source defaults to the class source.
+    ClassFeature,
+    /// `name|className|classSource|subclassShortName|subclassSource|level|source`,
+    /// where the source defaults to the subclass source.
+    SubclassFeature,
+}
+
+const fn target(categories: &'static [&'static str], default_source: &'static str) -> TagTarget {
+    TagTarget {
+        categories,
+        default_source,
+        format: UidFormat::NameSource { source_arg: 1 },
+    }
+}
+
+impl TagTarget {
+    /// The target of a tag by its name, e.g. `spell` or `creature`.
+    /// Tags which do not refer to a record have no target.
+    pub fn of(tag: &str) -> Option<Self> {
+        Some(match tag {
+            "spell" => target(&["spell"], "phb"),
+            "item" => target(&["item", "baseitem", "itemGroup", "magicvariant"], "dmg"),
+            "class" => target(&["class"], "phb"),
+            "creature" => target(&["monster"], "mm"),
+            "condition" => target(&["condition"], "phb"),
+            "disease" => target(&["disease"], "dmg"),
+            "status" => target(&["status"], "phb"),
+            "background" => target(&["background"], "phb"),
+            "race" => target(&["race", "subrace"], "phb"),
+            "optfeature" => target(&["optionalfeature"], "phb"),
+            "reward" => target(&["reward"], "dmg"),
+            "feat" => target(&["feat"], "phb"),
+            "psionic" => target(&["psionic"], "uathemysticclass"),
+            "object" => target(&["object"], "dmg"),
+            "cult" => target(&["cult"], "mtf"),
+            "boon" => target(&["boon"], "mtf"),
+            "trap" => target(&["trap"], "dmg"),
+            "hazard" => target(&["hazard"], "dmg"),
+            "variantrule" => target(&["variantrule"], "dmg"),
+            "table" => target(&["table"], "dmg"),
+            "vehicle" => target(&["vehicle"], "gos"),
+            "vehupgrade" => target(&["vehicleUpgrade"], "gos"),
+            "action" => target(&["action"], "phb"),
+            "language" => target(&["language"], "phb"),
+            "charoption" => target(&["charoption"], "mot"),
+            "recipe" => target(&["recipe"], "hf"),
+            "sense" => target(&["sense"], "phb"),
+            "skill" => target(&["skill"], "phb"),
+            "legroup" => target(&["legendaryGroup"], "mm"),
+            // `{@deity name|pantheon|source}`
+            "deity" => TagTarget {
+                categories: &["deity"],
+                default_source: "phb",
+                format: UidFormat::NameSource { source_arg: 2 },
+            },
+            "classFeature" => TagTarget {
+                categories: &["classFeature"],
+                default_source: "phb",
+                format: UidFormat::ClassFeature,
+            },
+            "subclassFeature" => TagTarget {
+                categories: &["subclassFeature"],
+                default_source: "phb",
+                format: UidFormat::SubclassFeature,
+            },
+            _ => return None,
+        })
+    }
+
+    /// The UID a tag's arguments refer to, using the default source when none is given.
+    pub fn uid(&self, args: &[&str]) -> Option<Uid> {
+        let arg = |i: usize| {
+            args.get(i)
+                .map(|arg| arg.trim())
+                .filter(|arg| !arg.is_empty())
+        };
+        let name = arg(0)?;
+
+        Some(match self.format {
+            UidFormat::NameSource { source_arg } => {
+                Uid::new(name, arg(source_arg).unwrap_or(self.default_source))
+            }
+            UidFormat::ClassFeature => ClassFeatureUid::from_args(args).ok()?.uid(),
+            UidFormat::SubclassFeature => SubclassFeatureUid::from_args(args).ok()?.uid(),
+        })
+    }
+}
+
+/// A record found by [DataStore::lookup].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StoreRecord<'s> {
+    pub category: &'s str,
+    pub uid: &'s Uid,
+    pub value: &'s Value,
+}
+
+impl<'s> StoreRecord<'s> {
+    /// Deserializes the record as one of the crate's types, borrowing from the store.
+    pub fn parse<T: Deserialize<'s>>(&self) -> Result<T> {
+        T::deserialize(self.value).map_err(Error::from)
+    }
+}
+
+/// Every record of a set of data, indexed by category and UID, with `_copy` resolved.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct DataStore {
+    pub(super) records: BTreeMap<String, BTreeMap<Uid, Value>>,
+}
+
+impl DataStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indexes every record of a dataset. Records without a name and source are skipped.
+    pub fn from_dataset(dataset: Dataset) -> Result<Self> {
+        let mut layer = Layer::new("data", 0);
+        layer.add_dataset(dataset, None);
+
+        let mut store = LayeredStore::new();
+        store.add_layer(layer)?;
+
+        let mut records = BTreeMap::new();
+        for category in store.categories() {
+            let category_records = store
+                .iter(category)
+                .map(|record| (record.uid.clone(), record.value.clone()))
+                .collect::<BTreeMap<_, _>>();
+            records.insert(category.to_owned(), category_records);
+        }
+
+        Ok(Self { records })
+    }
+
+    /// Loads the official `data/` directory: the files listed in the `index.json` of `bestiary/`,
+    /// `class/` and `spells/`, and every JSON file directly inside the directory, such as
+    /// `items.json` and `races.json`. Fluff, books and adventures are not loaded.
+    ///
+    /// The classes of spells are added from `generated/gendata-spell-source-lookup.json`,
+    /// if the directory has it, see [SpellSources].
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut dataset = Dataset::new();
+        for path in Self::data_files(dir)? {
+            dataset.extend(Dataset::from_file(&path)?);
+        }
+
+        let mut store = Self::from_dataset(dataset)?;
+        if let Some(sources) = SpellSources::from_dir(dir)? {
+            store.add_spell_sources(&sources);
+        }
+        Ok(store)
+    }
+
+    /// Loads the same files as [DataStore::from_dir], parsing them in parallel.
+    pub fn from_dir_parallel<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let files = Self::data_files(dir)?;
+
+        let mut store = Self::from_dataset(ParallelLoader::new().dataset(&files)?)?;
+        if let Some(sources) = SpellSources::from_dir(dir)? {
+            store.add_spell_sources(&sources);
+        }
+        Ok(store)
+    }
+
+    /// The files [DataStore::from_dir] loads from the directory, in the order they are loaded.
+    pub fn data_files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+
+        for path in read_dir(dir)? {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            if path.is_file() && is_data_file(name) {
+                files.push(path);
+            }
+        }
+
+        for subdir in INDEXED_DIRS.iter().map(|subdir| dir.join(subdir)) {
+            let index_path = subdir.join("index.json");
+            if !index_path.is_file() {
+                continue;
+            }
+            let text = fs::read_to_string(&index_path).map_err(|e| Error::io(&index_path, e))?;
+            let index: BTreeMap<String, String> =
+                serde_json::from_str(&text).map_err(|e| Error::json(&index_path, e))?;
+
+            files.extend(index.values().map(|file| subdir.join(file)));
+        }
+
+        for (subdir, file) in UNINDEXED_FILES {
+            let path = dir.join(subdir).join(file);
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Adds or replaces a record, which should already have its `_copy` block resolved.
+    /// Returns the record it replaced.
+    pub fn insert(&mut self, category: &str, uid: Uid, value: Value) -> Option<Value> {
+        self.records
+            .entry(category.to_owned())
+            .or_default()
+            .insert(uid, value)
+    }
+
+    pub fn remove(&mut self, category: &str, uid: &Uid) -> Option<Value> {
+        let records = self.records.get_mut(category)?;
+        let removed = records.remove(uid);
+        if records.is_empty() {
+            self.records.remove(category);
+        }
+        removed
+    }
+
+    /// Sets the classes of every spell the lookup lists, see [SpellSources::apply].
+    pub fn add_spell_sources(&mut self, sources: &SpellSources) {
+        for spell in self
+            .records
+            .get_mut("spell")
+            .into_iter()
+            .flat_map(|r| r.values_mut())
+        {
+            sources.apply(spell);
+        }
+    }
+
+    pub fn get(&self, category: &str, uid: &Uid) -> Option<&Value> {
+        self.records.get(category)?.get(uid)
+    }
+
+    /// Looks a record up and deserializes it as one of the crate's types.
+    pub fn get_as<'a, T: Deserialize<'a>>(
+        &'a self,
+        category: &str,
+        uid: &Uid,
+    ) -> Result<Option<T>> {
+        self.get(category, uid)
+            .map(|value| T::deserialize(value).map_err(Error::from))
+            .transpose()
+    }
+
+    pub fn class_feature(&self, uid: &ClassFeatureUid) -> Option<&Value> {
+        self.get("classFeature", &uid.uid())
+    }
+
+    pub fn subclass_feature(&self, uid: &SubclassFeatureUid) -> Option<&Value> {
+        self.get("subclassFeature", &uid.uid())
+    }
+
+    /// Finds the record a tag refers to, e.g. `lookup("spell", &["fireball"])` for
+    /// `{@spell fireball}`, which is `fireball|phb`.
+    ///
+    /// Specific magic variants such as `{@item +1 longsword|dmg}` are generated from a generic
+    /// variant and a base item rather than stored, so they resolve to their generic variant.
+    pub fn lookup(&self, tag: &str, args: &[&str]) -> Option<StoreRecord<'_>> {
+        let target = TagTarget::of(tag)?;
+        let uid = target.uid(args)?;
+
+        let found = target.categories.iter().find_map(|category| {
+            let (category, records) = self.records.get_key_value(*category)?;
+            let (uid, value) = records.get_key_value(&uid)?;
+            Some(StoreRecord {
+                category,
+                uid,
+                value,
+            })
+        });
+
+        match found {
+            None if target.categories.contains(&"magicvariant") => self.specific_variant(&uid),
+            found => found,
+        }
+    }
+
+    /// The generic variant whose `inherits.namePrefix` and `inherits.nameSuffix` around the name
+    /// of a base item give the UID, with the variant's source.
+    /// Which base items a variant `requires` or `excludes` is not checked.
+    fn specific_variant(&self, uid: &Uid) -> Option<StoreRecord<'_>> {
+        let (category, variants) = self.records.get_key_value("magicvariant")?;
+        let base_items = self.records.get("baseitem")?;
+
+        variants
+            .iter()
+            .filter(|(variant, _)| variant.source() == uid.source())
+            .find(|(_, value)| {
+                let affix = |key: &str| {
+                    value["inherits"][key]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_lowercase()
+                };
+                let base_name = uid
+                    .name()
+                    .strip_prefix(affix("namePrefix").trim_start())
+                    .and_then(|name| name.strip_suffix(affix("nameSuffix").trim_end()))
+                    .map(str::trim);
+
+                base_name
+                    .is_some_and(|base_name| base_items.keys().any(|item| item.name() == base_name))
+            })
+            .map(|(uid, value)| StoreRecord {
+                category,
+                uid,
+                value,
+            })
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        self.records.keys().map(String::as_str)
+    }
+
+    /// Iterates over every record of a category, ordered by UID.
+    pub fn iter(&self, category: &str) -> impl Iterator<Item = (&Uid, &Value)> {
+        self.records
+            .get(category)
+            .into_iter()
+            .flat_map(|records| records.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn is_data_file(name: &str) -> bool {
+    name.ends_with(".json") && !name.starts_with("fluff-") && !name.starts_with("foundry")
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| Error::io(dir, e))? {
+        paths.push(entry.map_err(|e| Error::io(dir, e))?.path());
+    }
+    // Files are loaded in a fixed order so that the result does not depend on the file system
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, file: &str, value: Value) {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, value.to_string()).unwrap();
+    }
+
+    fn data_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let data = dir.path();
+
+        write(
+            data,
+            "spells/index.json",
+            json!({ "PHB": "spells-phb.json", "XGE": "spells-xge.json" }),
+        );
+        write(
+            data,
+            "spells/spells-phb.json",
+            json!({ "spell": [{ "name": "Fireball", "source": "PHB", "level": 3 }] }),
+        );
+        write(
+            data,
+            "spells/spells-xge.json",
+            json!({ "spell": [{ "name": "Fireball", "source": "XGE", "level": 9 }] }),
+        );
+        write(
+            data,
+            "spells/fluff-spells-phb.json",
+            json!({ "spellFluff": [{ "name": "Fireball", "source": "PHB" }] }),
+        );
+        write(
+            data,
+            "bestiary/index.json",
+            json!({ "MM": "bestiary-mm.json" }),
+        );
+        write(
+            data,
+            "bestiary/bestiary-mm.json",
+            json!({ "monster": [
+                { "name": "Goblin", "source": "MM", "cr": "1/4" },
+                { "name": "Goblin Boss", "source": "MM", "_copy": { "name": "Goblin", "source": "MM" }, "cr": "1" }
+            ]}),
+        );
+        write(
+            data,
+            "bestiary/legendarygroups.json",
+            json!({ "legendaryGroup": [{ "name": "Tiamat", "source": "RoT" }] }),
+        );
+        write(
+            data,
+            "items.json",
+            json!({ "item": [{ "name": "Bag of Holding", "source": "DMG" }] }),
+        );
+        write(
+            data,
+            "items-base.json",
+            json!({ "baseitem": [{ "name": "Longsword", "source": "PHB" }] }),
+        );
+        write(
+            data,
+            "magicvariants.json",
+            json!({ "magicvariant": [{ "name": "+1 Weapon", "inherits": { "namePrefix": "+1 ", "source": "DMG" } }] }),
+        );
+        write(
+            data,
+            "generated/gendata-spell-source-lookup.json",
+            json!({ "phb": { "fireball": { "class": { "PHB": { "Wizard": true } } } } }),
+        );
+        write(
+            data,
+            "conditionsdiseases.json",
+            json!({ "condition": [{ "name": "Blinded", "source": "PHB" }] }),
+        );
+        dir
+    }
+
+    #[test]
+    fn load_data_dir() {
+        let dir = data_dir();
+        let store = DataStore::from_dir(dir.path()).unwrap();
+
+        assert_eq!(store.len(), 9);
+        assert!(store
+            .get("spellFluff", &Uid::new("Fireball", "PHB"))
+            .is_none());
+        assert_eq!(
+            store
+                .get("monster", &Uid::new("goblin boss", "mm"))
+                .unwrap()["cr"],
+            "1"
+        );
+        assert!(store
+            .get("legendaryGroup", &Uid::new("Tiamat", "RoT"))
+            .is_some());
+        assert_eq!(
+            store.get("spell", &Uid::new("fireball", "phb")).unwrap()["classes"],
+            json!({ "fromClassList": [{ "name": "Wizard", "source": "PHB" }] })
+        );
+    }
+
+    #[test]
+    fn lookup_tags() {
+        let dir = data_dir();
+        let store = DataStore::from_dir(dir.path()).unwrap();
+
+        let fireball = store.lookup("spell", &["Fireball"]).unwrap();
+        assert_eq!(fireball.uid.as_str(), "fireball|phb");
+        assert_eq!(fireball.value["level"], 3);
+        assert_eq!(
+            store.lookup("spell", &["fireball", "xge"]).unwrap().value["level"],
+            9
+        );
+
+        let longsword = store.lookup("item", &["longsword", "phb"]).unwrap();
+        assert_eq!(longsword.category, "baseitem");
+        assert!(store.lookup("item", &["longsword"]).is_none());
+        let variant = store.lookup("item", &["+1 weapon", "dmg"]).unwrap();
+        assert_eq!(variant.category, "magicvariant");
+        assert_eq!(variant.uid.as_str(), "+1 weapon|dmg");
+        let specific = store.lookup("item", &["+1 Longsword"]).unwrap();
+        assert_eq!(specific.uid, variant.uid);
+        assert!(store.lookup("item", &["+1 Longsword", "phb"]).is_none());
+        assert!(store.lookup("item", &["+1 Greatsword"]).is_none());
+        assert!(store
+            .lookup("condition", &["blinded", "", "Blind"])
+            .is_some());
+        assert!(store.lookup("creature", &["goblin"]).is_some());
+        assert!(store.lookup("b", &["goblin"]).is_none());
+    }
+}
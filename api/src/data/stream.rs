@@ -0,0 +1,342 @@
+use super::{Dataset, Error, Result};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserializer;
+use serde_json::Value;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Reads a 5etools JSON document, calling `f` with each record of its top-level arrays as soon
+/// as the record has been read. Only one record is held in memory at a time.
+///
+/// As with [Dataset::from_value], keys beginning with an underscore (e.g. `_meta`) and values
+/// which are not arrays are skipped.
+pub fn stream_records<R, F>(reader: R, mut f: F) -> serde_json::Result<()>
+where
+    R: Read,
+    F: FnMut(&str, Value),
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer.deserialize_map(Categories { f: &mut f })?;
+    deserializer.end()
+}
+
+/// Streams the records of a file, see [stream_records].
+pub fn stream_file<P, F>(path: P, f: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str, Value),
+{
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    stream_records(BufReader::new(file), f).map_err(|e| Error::json(path, e))
+}
+
+/// A record read by a [ParallelLoader].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedRecord {
+    /// The index of the file the record was read from, in the paths given to the loader.
+    pub file: usize,
+    pub category: String,
+    pub value: Value,
+}
+
+/// Streams the records of several files at once, each file being read by one of a number of
+/// threads.
+///
+/// Records are handed to the caller's thread through a queue holding at most `capacity`
+/// records, so that memory stays bounded however large the files are. Records of the same file
+/// arrive in order, but records of different files are interleaved.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParallelLoader {
+    threads: usize,
+    capacity: usize,
+}
+
+impl Default for ParallelLoader {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            capacity: 256,
+        }
+    }
+}
+
+impl ParallelLoader {
+    /// A loader with a thread for each available core.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// How many records may be waiting for the callback before the threads reading files pause.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Calls `f` with every record of the files, as soon as each is read.
+    ///
+    /// After a file fails to load, no further files are started, and the first error is
+    /// returned once the files being read have been finished. Records read until then have
+    /// already been passed to `f`.
+    pub fn load<P, F>(&self, paths: &[P], mut f: F) -> Result<()>
+    where
+        P: AsRef<Path> + Sync,
+        F: FnMut(LoadedRecord),
+    {
+        let next = &AtomicUsize::new(0);
+        let failed = &AtomicBool::new(false);
+        let (sender, receiver) = mpsc::sync_channel(self.capacity);
+
+        thread::scope(|scope| {
+            let workers = (0..self.threads.min(paths.len()))
+                .map(|_| {
+                    let sender = sender.clone();
+                    scope.spawn(move || -> Result<()> {
+                        while !failed.load(Ordering::Relaxed) {
+                            let file = next.fetch_add(1, Ordering::Relaxed);
+                            let path = match paths.get(file) {
+                                Some(path) => path,
+                                None => break,
+                            };
+
+                            let result = stream_file(path, |category, value| {
+                                // The receiver outlives the workers, so sending cannot fail
+                                let _ = sender.send(LoadedRecord {
+                                    file,
+                                    category: category.to_owned(),
+                                    value,
+                                });
+                            });
+                            if result.is_err() {
+                                failed.store(true, Ordering::Relaxed);
+                                return result;
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            drop(sender);
+
+            for record in receiver {
+                f(record);
+            }
+            // Workers which are not joined here are joined at the end of the scope
+            workers
+                .into_iter()
+                .try_for_each(|worker| worker.join().expect("loader thread panicked"))
+        })
+    }
+
+    /// Loads the files into a dataset, in the order of the paths as [Dataset::from_file] and
+    /// [Dataset::extend] would.
+    pub fn dataset<P: AsRef<Path> + Sync>(&self, paths: &[P]) -> Result<Dataset> {
+        let mut files = vec![Dataset::new(); paths.len()];
+        self.load(paths, |record| {
+            files[record.file].insert(&record.category, record.value)
+        })?;
+
+        let mut dataset = Dataset::new();
+        for file in files {
+            dataset.extend(file);
+        }
+        Ok(dataset)
+    }
+}
+
+struct Categories<'f, F> {
+    f: &'f mut F,
+}
+
+impl<'de, 'f, F: FnMut(&str, Value)> Visitor<'de> for Categories<'f, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object of record arrays")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        while let Some(category) = map.next_key::<String>()? {
+            if category.starts_with('_') {
+                map.next_value::<IgnoredAny>()?;
+            } else {
+                map.next_value_seed(Records {
+                    category: &category,
+                    f: &mut *self.f,
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The value of a category, which is skipped if it is not an array.
+struct Records<'c, 'f, F> {
+    category: &'c str,
+    f: &'f mut F,
+}
+
+impl<'de, 'c, 'f, F: FnMut(&str, Value)> DeserializeSeed<'de> for Records<'c, 'f, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'c, 'f, F: FnMut(&str, Value)> Visitor<'de> for Records<'c, 'f, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of records")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(record) = seq.next_element::<Value>()? {
+            (self.f)(self.category, record);
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(())
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<(), E> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataStore;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn stream_document() {
+        let document = json!({
+            "_meta": { "sources": [{ "json": "MyBrew" }] },
+            "monster": [{ "name": "Goblin" }, { "name": "Hobgoblin" }],
+            "spell": [{ "name": "Fireball" }],
+            "note": "not records",
+            "settings": { "a": [1, 2] }
+        })
+        .to_string();
+
+        let mut records = Vec::new();
+        stream_records(document.as_bytes(), |category, record| {
+            records.push((category.to_owned(), record["name"].clone()))
+        })
+        .unwrap();
+        assert_eq!(
+            records,
+            [
+                ("monster".to_owned(), json!("Goblin")),
+                ("monster".to_owned(), json!("Hobgoblin")),
+                ("spell".to_owned(), json!("Fireball")),
+            ]
+        );
+
+        assert!(stream_records(&b"[1, 2]"[..], |_, _| {}).is_err());
+        assert!(stream_records(&br#"{"monster": [{}"#[..], |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn load_in_parallel() {
+        let dir = TempDir::new().unwrap();
+        let paths = (0..8)
+            .map(|i| {
+                let path = dir.path().join(format!("bestiary-{}.json", i));
+                let monsters = (0..50)
+                    .map(|j| json!({ "name": format!("Monster {}", j), "source": format!("S{}", i) }))
+                    .collect::<Vec<_>>();
+                fs::write(&path, json!({ "monster": monsters }).to_string()).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+
+        let mut sequential = Dataset::new();
+        for path in &paths {
+            sequential.extend(Dataset::from_file(path).unwrap());
+        }
+        let loader = ParallelLoader::new().threads(3).capacity(4);
+        assert_eq!(loader.dataset(&paths).unwrap(), sequential);
+
+        let mut count = 0;
+        loader.load(&paths, |_| count += 1).unwrap();
+        assert_eq!(count, 400);
+
+        let broken = dir.path().join("broken.json");
+        fs::write(&broken, "{ \"monster\": [").unwrap();
+        let mut with_broken = paths.clone();
+        with_broken.insert(4, broken.clone());
+        match loader.dataset(&with_broken) {
+            Err(Error::Json { path, .. }) => assert_eq!(path, broken),
+            result => panic!("expected a JSON error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn store_from_dir_in_parallel() {
+        let dir = TempDir::new().unwrap();
+        let write = |file: &str, value: Value| {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, value.to_string()).unwrap();
+        };
+        write("spells/index.json", json!({ "PHB": "spells-phb.json" }));
+        write(
+            "spells/spells-phb.json",
+            json!({ "spell": [{ "name": "Fireball", "source": "PHB", "level": 3 }] }),
+        );
+        write(
+            "items.json",
+            json!({ "item": [
+                { "name": "Longsword", "source": "PHB" },
+                { "name": "Flame Tongue Longsword", "source": "DMG", "_copy": { "name": "Longsword", "source": "PHB" } }
+            ] }),
+        );
+
+        assert_eq!(
+            DataStore::from_dir_parallel(dir.path()).unwrap(),
+            DataStore::from_dir(dir.path()).unwrap()
+        );
+    }
+}
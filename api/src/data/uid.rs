@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// The canonical identifier of a record: its name and source, lowercased and joined by a pipe,
+/// as used in tags such as `{@spell fireball|phb}`.
+///
+/// Class and subclass features share names across classes and levels, so their UIDs also hold
+/// the class, the subclass and the level, e.g. `extra attack|fighter|phb|5|phb`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Uid(String);
+
+impl Uid {
+    pub fn new(name: &str, source: &str) -> Self {
+        Self(format!(
+            "{}|{}",
+            name.trim().to_lowercase(),
+            source.trim().to_lowercase()
+        ))
+    }
+
+    /// Joins lowercased parts with pipes, starting with the name and ending with the source.
+    pub fn from_parts(parts: &[&str]) -> Self {
+        let parts = parts
+            .iter()
+            .map(|part| part.trim().to_lowercase())
+            .collect::<Vec<_>>();
+
+        Self(parts.join("|"))
+    }
+
+    /// Builds the UID of a raw record from its `name` and `source` fields,
+    /// and for class and subclass features also from the fields naming the class and level.
+    /// Magic variants keep their source in `inherits`, with the properties they give to items.
+    pub fn from_record(record: &Value) -> Option<Self> {
+        let name = record.get("name")?.as_str()?;
+        let source = match record.get("source") {
+            Some(source) => source.as_str()?,
+            None => record.get("inherits")?.get("source")?.as_str()?,
+        };
+
+        let field = |key: &str| match record.get(key) {
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        };
+        let mut parts = vec![name.to_owned()];
+        if let (Some(class), Some(class_source), Some(level)) =
+            (field("className"), field("classSource"), field("level"))
+        {
+            parts.extend([class, class_source]);
+            if let (Some(subclass), Some(subclass_source)) =
+                (field("subclassShortName"), field("subclassSource"))
+            {
+                parts.extend([subclass, subclass_source]);
+            }
+            parts.push(level);
+        }
+        parts.push(source.to_owned());
+
+        Some(Self::from_parts(
+            &parts.iter().map(String::as_str).collect::<Vec<_>>(),
+        ))
+    }
+
+    pub fn name(&self) -> &str {
+        self.0.split('|').next().unwrap_or_default()
+    }
+
+    pub fn source(&self) -> &str {
+        self.0.rsplit('|').next().unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -0,0 +1,571 @@
+use super::layer::{
+    copy_target, resolve_copy, template_target, Records, Shadowed, TEMPLATE_CATEGORY,
+};
+use super::{DataStore, Dataset, Error, Provenance, ResolvedRecord, Result, SpellSources, Uid};
+use crate::search::SearchIndex;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// A category and the UID of a record within it.
+type Key = (String, Uid);
+
+/// What changed in a [StoreWatcher] after a poll.
+#[derive(Debug, Default)]
+pub struct ChangeEvent {
+    /// The files which were changed, added or removed.
+    pub files: Vec<PathBuf>,
+    pub added: Vec<(String, Uid)>,
+    pub removed: Vec<(String, Uid)>,
+    /// Records whose resolved value changed, including records copying a changed record.
+    pub modified: Vec<(String, Uid)>,
+    /// Files which could not be read, whose records are kept as they were, and the first record
+    /// whose `_copy` could not be resolved, as [DataStore::from_dir] would report it. The store
+    /// is then left as it was, and the records are resolved again by every poll until they can be.
+    pub errors: Vec<Error>,
+}
+
+impl ChangeEvent {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.errors.is_empty()
+    }
+}
+
+/// What a watched file held when it was last read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Stamp {
+    Missing,
+    /// The file could not be read. The error is only reported when the file first fails.
+    Unreadable,
+    /// A BLAKE3 hash of the contents.
+    Contents([u8; 32]),
+}
+
+#[derive(Debug, Clone)]
+struct WatchedFile {
+    path: PathBuf,
+    stamp: Option<Stamp>,
+    records: BTreeMap<Key, Value>,
+}
+
+/// Keeps a [DataStore] and its [SearchIndex] up to date with the files they were loaded from,
+/// by polling the files for changes.
+///
+/// Files are compared by a hash of their contents. Only changed files are parsed again, and only
+/// the records they contain and the records copying those through `_copy` are resolved and
+/// indexed again.
+#[derive(Debug, Default)]
+pub struct StoreWatcher {
+    dir: Option<PathBuf>,
+    extra_files: Vec<PathBuf>,
+    files: Vec<WatchedFile>,
+    /// The record each key resolves to before `_copy` is applied: the one in the last file.
+    raw: BTreeMap<Key, Value>,
+    /// The records copying each record, through `_copy` or as a `_trait` template.
+    dependents: BTreeMap<Key, BTreeSet<Key>>,
+    /// Records which changed but could not be resolved, and are still stale in the store.
+    pending: BTreeSet<Key>,
+    /// The classes of spells, read once when watching a directory.
+    spell_sources: Option<SpellSources>,
+    store: DataStore,
+    search: SearchIndex,
+}
+
+impl StoreWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the files [DataStore::from_dir] would, and watches them and the `index.json` files
+    /// listing them. The classes of spells are read once, and not watched.
+    pub fn from_dir<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        let mut watcher = Self {
+            spell_sources: SpellSources::from_dir(&dir)?,
+            dir: Some(dir),
+            ..Self::default()
+        };
+        let mut event = watcher.poll();
+        match event.errors.is_empty() {
+            true => Ok(watcher),
+            false => Err(event.errors.remove(0)),
+        }
+    }
+
+    /// Watches another file, such as a homebrew file, whose records override those of the data
+    /// directory and of files added before it. It is loaded by the next poll.
+    pub fn add_file<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.extra_files.push(path.into());
+        self
+    }
+
+    pub fn store(&self) -> &DataStore {
+        &self.store
+    }
+
+    pub fn search(&self) -> &SearchIndex {
+        &self.search
+    }
+
+    /// Reloads the files which changed since the last poll.
+    pub fn poll(&mut self) -> ChangeEvent {
+        let mut event = ChangeEvent::default();
+        let mut touched = BTreeSet::new();
+
+        let paths = match self.paths() {
+            Ok(paths) => paths,
+            Err(e) => {
+                event.errors.push(e);
+                self.files.iter().map(|file| file.path.clone()).collect()
+            }
+        };
+
+        // A change in the order of the files may change which of them each record comes from
+        let old_order = self
+            .files
+            .iter()
+            .map(|file| &file.path)
+            .filter(|path| paths.contains(path));
+        let new_order = paths
+            .iter()
+            .filter(|path| self.files.iter().any(|file| file.path == **path));
+        let reordered = !old_order.eq(new_order);
+
+        // Files which are no longer listed are dropped along with their records
+        let mut previous = std::mem::take(&mut self.files)
+            .into_iter()
+            .map(|file| (file.path.clone(), file))
+            .collect::<HashMap<_, _>>();
+
+        let mut files = Vec::new();
+        for path in paths {
+            let mut file = previous.remove(&path).unwrap_or_else(|| WatchedFile {
+                path: path.clone(),
+                stamp: None,
+                records: BTreeMap::new(),
+            });
+            let (stamp, contents) = match fs::read(&path) {
+                Ok(contents) => {
+                    let hash = *blake3::hash(&contents).as_bytes();
+                    (Stamp::Contents(hash), Ok(Some(contents)))
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => (Stamp::Missing, Ok(None)),
+                Err(e) => (Stamp::Unreadable, Err(Error::io(&path, e))),
+            };
+            if file.stamp.as_ref() != Some(&stamp) {
+                file.stamp = Some(stamp);
+                match contents.and_then(|contents| read_records(&path, contents.as_deref())) {
+                    Ok(records) => {
+                        touched.extend(file.records.keys().cloned());
+                        touched.extend(records.keys().cloned());
+                        file.records = records;
+                        event.files.push(path);
+                    }
+                    Err(e) => event.errors.push(e),
+                }
+            }
+            files.push(file);
+        }
+        for (path, file) in previous {
+            touched.extend(file.records.into_keys());
+            event.files.push(path);
+        }
+        self.files = files;
+        if reordered {
+            for file in &self.files {
+                touched.extend(file.records.keys().cloned());
+            }
+        }
+
+        self.update(touched, &mut event);
+        event
+    }
+
+    /// Polls every `interval`, calling `f` after each poll which found changes, until `f` breaks.
+    pub fn run<F>(&mut self, interval: Duration, mut f: F)
+    where
+        F: FnMut(&Self, &ChangeEvent) -> ControlFlow<()>,
+    {
+        loop {
+            let event = self.poll();
+            if !event.is_empty() && f(self, &event).is_break() {
+                return;
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    /// The files to watch, in the order they are loaded.
+    fn paths(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = match &self.dir {
+            Some(dir) => DataStore::data_files(dir)?,
+            None => Vec::new(),
+        };
+        paths.extend(self.extra_files.iter().cloned());
+        Ok(paths)
+    }
+
+    /// Brings the raw records, the store and the search index up to date for the touched keys
+    /// and every record copying them.
+    fn update(&mut self, mut touched: BTreeSet<Key>, event: &mut ChangeEvent) {
+        for key in &touched {
+            let winner = self
+                .files
+                .iter()
+                .rev()
+                .find_map(|file| file.records.get(key))
+                .cloned();
+
+            if let Some(old) = self.raw.remove(key) {
+                for target in copy_targets(key, &old) {
+                    if let Some(dependents) = self.dependents.get_mut(&target) {
+                        dependents.remove(key);
+                    }
+                }
+            }
+            if let Some(new) = winner {
+                for target in copy_targets(key, &new) {
+                    self.dependents
+                        .entry(target)
+                        .or_default()
+                        .insert(key.clone());
+                }
+                self.raw.insert(key.clone(), new);
+            }
+        }
+
+        touched.append(&mut self.pending);
+        let mut affected = BTreeSet::new();
+        let mut queue = touched.into_iter().collect::<Vec<_>>();
+        while let Some(key) = queue.pop() {
+            if let Some(dependents) = self.dependents.get(&key) {
+                if !affected.contains(&key) {
+                    queue.extend(dependents.iter().cloned());
+                }
+            }
+            affected.insert(key);
+        }
+
+        let resolved = match self.resolve(&affected) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                event.errors.push(e);
+                self.pending = affected;
+                return;
+            }
+        };
+        self.pending.clear();
+
+        let mut reindexed: BTreeMap<String, BTreeSet<Uid>> = BTreeMap::new();
+        for key in &affected {
+            let (category, uid) = key;
+            let mut new = resolved
+                .get(category)
+                .and_then(|records| records.get(uid))
+                .map(|record| record.value.clone());
+            if let (Some(sources), Some(spell), "spell") =
+                (&self.spell_sources, &mut new, category.as_str())
+            {
+                sources.apply(spell);
+            }
+            let changed = match (self.store.get(category, uid), &new) {
+                (None, None) => continue,
+                (None, Some(_)) => &mut event.added,
+                (Some(_), None) => &mut event.removed,
+                (Some(old), Some(new)) if old == new => continue,
+                (Some(_), Some(_)) => &mut event.modified,
+            };
+            changed.push(key.clone());
+            reindexed
+                .entry(category.clone())
+                .or_default()
+                .insert(uid.clone());
+
+            match new {
+                Some(new) => self.store.insert(category, uid.clone(), new),
+                None => self.store.remove(category, uid),
+            };
+        }
+
+        self.search.retain_records(|category, uid| {
+            !reindexed
+                .get(category)
+                .is_some_and(|uids| uids.contains(uid))
+        });
+        for (category, uids) in &reindexed {
+            for uid in uids {
+                if let Some(record) = self.store.get(category, uid) {
+                    self.search.add_record(category, uid, record);
+                }
+            }
+        }
+    }
+
+    /// Resolves the affected records with the same resolver as [LayeredStore](super::LayeredStore).
+    /// The records they copy which are not affected are taken from the store, already resolved.
+    fn resolve(&self, affected: &BTreeSet<Key>) -> Result<Records> {
+        let mut raw = Records::new();
+        let mut queue = affected.iter().collect::<Vec<_>>();
+        while let Some(key) = queue.pop() {
+            let (category, uid) = key;
+            if raw
+                .get(category)
+                .is_some_and(|records| records.contains_key(uid))
+            {
+                continue;
+            }
+
+            let value = match affected.contains(key) {
+                true => self.raw.get(key),
+                false => self.store.get(category, uid),
+            };
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            if affected.contains(key) {
+                queue.extend(
+                    copy_targets(key, value)
+                        .iter()
+                        .filter_map(|target| self.raw.get_key_value(target))
+                        .map(|(target, _)| target),
+                );
+            }
+
+            let record = ResolvedRecord {
+                uid: uid.clone(),
+                value: value.clone(),
+                provenance: Provenance {
+                    layer: "data".to_owned(),
+                    file: None,
+                    copy_chain: Vec::new(),
+                    overridden: Vec::new(),
+                },
+            };
+            raw.entry(category.clone())
+                .or_default()
+                .insert(uid.clone(), record);
+        }
+
+        // Every file is part of one layer, so no record overrides another with its own `_copy`
+        let shadowed = Shadowed::new();
+        let mut resolved = raw.clone();
+        for (category, uid) in affected {
+            if raw
+                .get(category)
+                .is_some_and(|records| records.contains_key(uid))
+            {
+                resolve_copy(
+                    &raw,
+                    &shadowed,
+                    &mut resolved,
+                    category,
+                    uid,
+                    &mut Vec::new(),
+                )?;
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+/// The records a record copies, through `_copy` or as a `_trait` template.
+fn copy_targets((category, _): &Key, record: &Value) -> Vec<Key> {
+    let copy = match record.get("_copy") {
+        Some(copy) => copy,
+        None => return Vec::new(),
+    };
+
+    let mut targets = vec![(category.clone(), copy_target(copy))];
+    if let Some(template) = template_target(copy) {
+        targets.push((TEMPLATE_CATEGORY.to_owned(), template));
+    }
+    targets
+}
+
+/// The records of a file by key, the last one winning, or none if the file does not exist.
+fn read_records(path: &Path, contents: Option<&[u8]>) -> Result<BTreeMap<Key, Value>> {
+    let contents = match contents {
+        Some(contents) => contents,
+        None => return Ok(BTreeMap::new()),
+    };
+    let value = serde_json::from_slice(contents).map_err(|e| Error::json(path, e))?;
+
+    let mut records = BTreeMap::new();
+    for (category, record) in Dataset::from_value(value).iter() {
+        if let Some(uid) = Uid::from_record(record) {
+            records.insert((category.to_owned(), uid), record.clone());
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Query;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, value: Value) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, value.to_string()).unwrap();
+    }
+
+    fn key(category: &str, name: &str, source: &str) -> Key {
+        (category.to_owned(), Uid::new(name, source))
+    }
+
+    fn data_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir.path().join("spells/index.json"),
+            json!({ "PHB": "spells-phb.json" }),
+        );
+        write(
+            &dir.path().join("spells/spells-phb.json"),
+            json!({ "spell": [{ "name": "Fireball", "source": "PHB", "level": 3 }] }),
+        );
+        write(
+            &dir.path().join("items.json"),
+            json!({ "item": [
+                { "name": "Longsword", "source": "PHB", "entries": ["A sword."] },
+                { "name": "Flame Tongue Longsword", "source": "DMG", "_copy": { "name": "Longsword", "source": "PHB" } }
+            ] }),
+        );
+        dir
+    }
+
+    #[test]
+    fn reload_changed_files() {
+        let dir = data_dir();
+        let mut watcher = StoreWatcher::from_dir(dir.path()).unwrap();
+        assert_eq!(watcher.store(), &DataStore::from_dir(dir.path()).unwrap());
+        assert!(watcher.poll().is_empty());
+
+        write(
+            &dir.path().join("items.json"),
+            json!({ "item": [
+                { "name": "Longsword", "source": "PHB", "entries": ["A versatile sword."] },
+                { "name": "Flame Tongue Longsword", "source": "DMG", "_copy": { "name": "Longsword", "source": "PHB" } },
+                { "name": "Dagger", "source": "PHB" }
+            ] }),
+        );
+        let event = watcher.poll();
+        assert_eq!(event.files, [dir.path().join("items.json")]);
+        assert_eq!(event.added, [key("item", "Dagger", "PHB")]);
+        assert_eq!(
+            event.modified,
+            [
+                key("item", "Flame Tongue Longsword", "DMG"),
+                key("item", "Longsword", "PHB")
+            ]
+        );
+        assert!(event.removed.is_empty() && event.errors.is_empty());
+        assert_eq!(watcher.store(), &DataStore::from_dir(dir.path()).unwrap());
+        assert_eq!(watcher.search().search(&Query::parse("versatile")).total, 2);
+
+        // An edit which keeps the length of the file is still seen
+        write(
+            &dir.path().join("spells/spells-phb.json"),
+            json!({ "spell": [{ "name": "Fireball", "source": "PHB", "level": 4 }] }),
+        );
+        let event = watcher.poll();
+        assert_eq!(event.modified, [key("spell", "Fireball", "PHB")]);
+
+        let homebrew = dir.path().join("homebrew.json");
+        watcher.add_file(&homebrew);
+        write(
+            &homebrew,
+            json!({ "item": [{ "name": "Longsword", "source": "PHB", "entries": ["A homebrew sword."] }] }),
+        );
+        let event = watcher.poll();
+        assert_eq!(event.modified.len(), 2);
+        assert_eq!(watcher.search().search(&Query::parse("versatile")).total, 0);
+
+        fs::remove_file(&homebrew).unwrap();
+        let event = watcher.poll();
+        assert_eq!(event.modified.len(), 2);
+        assert_eq!(watcher.store(), &DataStore::from_dir(dir.path()).unwrap());
+
+        fs::remove_file(dir.path().join("spells/spells-phb.json")).unwrap();
+        write(&dir.path().join("spells/index.json"), json!({}));
+        let event = watcher.poll();
+        assert_eq!(event.removed, [key("spell", "Fireball", "PHB")]);
+        assert_eq!(watcher.search().search(&Query::parse("fireball")).total, 0);
+    }
+
+    #[test]
+    fn report_errors() {
+        let dir = data_dir();
+        let items = dir.path().join("items.json");
+        let mut watcher = StoreWatcher::from_dir(dir.path()).unwrap();
+
+        fs::write(&items, "{ \"item\": [").unwrap();
+        let event = watcher.poll();
+        assert!(matches!(event.errors[..], [Error::Json { .. }]));
+        assert!(event.added.is_empty() && event.removed.is_empty());
+        assert_eq!(watcher.store().len(), 3);
+
+        write(
+            &items,
+            json!({ "item": [
+                { "name": "Flame Tongue Longsword", "source": "DMG", "_copy": { "name": "Longsword", "source": "PHB" } },
+                { "name": "Flame Tongue Greatsword", "source": "DMG", "_copy": { "name": "Flame Tongue Longsword", "source": "DMG" } }
+            ] }),
+        );
+        let event = watcher.poll();
+        assert!(matches!(
+            event.errors[..],
+            [Error::MissingCopySource { .. }]
+        ));
+        assert!(matches!(
+            DataStore::from_dir(dir.path()),
+            Err(Error::MissingCopySource { .. })
+        ));
+        assert!(event.removed.is_empty());
+        assert_eq!(watcher.store().len(), 3);
+
+        // The records are resolved again until the missing one is added
+        assert!(matches!(
+            watcher.poll().errors[..],
+            [Error::MissingCopySource { .. }]
+        ));
+        let homebrew = dir.path().join("homebrew.json");
+        watcher.add_file(&homebrew);
+        write(
+            &homebrew,
+            json!({ "item": [{ "name": "Longsword", "source": "PHB", "entries": ["A sword."] }] }),
+        );
+        let event = watcher.poll();
+        assert!(event.errors.is_empty());
+        assert_eq!(event.added, [key("item", "Flame Tongue Greatsword", "DMG")]);
+        assert_eq!(watcher.store().len(), 4);
+        fs::remove_file(&homebrew).unwrap();
+
+        write(
+            &items,
+            json!({ "item": [
+                { "name": "A", "source": "DMG", "_copy": { "name": "B", "source": "DMG" } },
+                { "name": "B", "source": "DMG", "_copy": { "name": "A", "source": "DMG" } }
+            ] }),
+        );
+        let event = watcher.poll();
+        assert!(matches!(event.errors[..], [Error::CircularCopy(_)]));
+        assert!(event.added.is_empty());
+
+        assert!(matches!(
+            StoreWatcher::from_dir(dir.path()),
+            Err(Error::CircularCopy(_))
+        ));
+        assert!(matches!(
+            DataStore::from_dir(dir.path()),
+            Err(Error::CircularCopy(_))
+        ));
+    }
+}
@@ -0,0 +1,24 @@
+//! Parsing and rolling of 5etools dice expressions, as found in `{@dice}`, `{@damage}`, `{@d20}`,
+//! `{@hit}`, `{@scaledice}` and `{@scaledamage}` tags and in `dice` entries.
+
+mod average;
+mod distribution;
+mod entry;
+mod error;
+mod expression;
+mod roll;
+mod scale;
+
+pub(crate) use average::trailing_number;
+pub use average::{
+    check_printed_averages, correct_printed_averages, printed_averages, PrintedAverage,
+};
+pub use distribution::{
+    chance_to_crit, chance_to_hit, chance_to_succeed, Advantage, Distribution, MAX_COMBINATIONS,
+};
+pub use error::{Error, Result};
+pub use expression::{
+    BinaryOp, CompareOp, Comparison, Dice, DiceModifier, Expression, Formula, Prompt,
+};
+pub use roll::{Die, Roll, RolledDice, Roller, MAX_DICE};
+pub use scale::{scale_cantrip, ScaledDice};
@@ -0,0 +1,146 @@
+use super::{Formula, Result};
+use crate::string::{tokenize, Lexeme};
+
+/// An average printed before a dice tag in running text, as in `7 ({@damage 2d6})`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintedAverage {
+    pub printed: i64,
+    /// The formula of the tag following the average.
+    pub formula: String,
+    /// The average of the formula, rounded down as statblocks do.
+    pub expected: Result<i64>,
+}
+
+impl PrintedAverage {
+    pub fn is_correct(&self) -> bool {
+        self.expected.as_ref() == Ok(&self.printed)
+    }
+}
+
+/// Finds every average printed before a `{@damage}` or `{@dice}` tag.
+/// Text which cannot be tokenized has no averages.
+pub fn printed_averages(text: &str) -> Vec<PrintedAverage> {
+    let lexemes = match tokenize(text) {
+        Ok(lexemes) => lexemes.collect::<Vec<_>>(),
+        Err(_) => return Vec::new(),
+    };
+
+    lexemes
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [Lexeme::Text(before), Lexeme::Tag(tag)] if matches!(tag.name, "damage" | "dice") => {
+                let printed = trailing_number(before.trim_end().strip_suffix('(')?)?;
+                let formula = tag.args.first()?.trim();
+                let expected = formula
+                    .parse::<Formula>()
+                    .and_then(|formula| formula.parts[0].distribution())
+                    .map(|distribution| distribution.printed_average());
+
+                Some(PrintedAverage {
+                    printed,
+                    formula: formula.to_owned(),
+                    expected,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Finds the printed averages which do not match their formula.
+pub fn check_printed_averages(text: &str) -> Vec<PrintedAverage> {
+    printed_averages(text)
+        .into_iter()
+        .filter(|average| !average.is_correct())
+        .collect()
+}
+
+/// Rewrites every average printed before a `{@damage}` or `{@dice}` tag to match its formula.
+/// Text which cannot be tokenized is returned unchanged.
+pub fn correct_printed_averages(text: &str) -> String {
+    let lexemes = match tokenize(text) {
+        Ok(lexemes) => lexemes.collect::<Vec<_>>(),
+        Err(_) => return text.to_owned(),
+    };
+
+    let mut corrected = String::with_capacity(text.len());
+    for (i, lexeme) in lexemes.iter().enumerate() {
+        let before = match (lexeme, lexemes.get(i + 1)) {
+            (Lexeme::Text(before), Some(Lexeme::Tag(tag)))
+                if matches!(tag.name, "damage" | "dice") =>
+            {
+                before
+            }
+            _ => {
+                corrected.push_str(&lexeme.to_string());
+                continue;
+            }
+        };
+
+        let average = printed_averages(&format!("{}{}", before, lexemes[i + 1]))
+            .pop()
+            .and_then(|average| average.expected.ok());
+        // The printed number ends where the whitespace before the opening parenthesis starts
+        let end = before
+            .trim_end()
+            .strip_suffix('(')
+            .map_or(0, |open| open.trim_end().len());
+        let start = before[..end]
+            .trim_end_matches(|c: char| c.is_ascii_digit())
+            .len();
+        match average {
+            Some(average) if start < end => {
+                corrected.push_str(&before[..start]);
+                corrected.push_str(&average.to_string());
+                corrected.push_str(&before[end..]);
+            }
+            _ => corrected.push_str(before),
+        }
+    }
+    corrected
+}
+
+pub(crate) fn trailing_number(text: &str) -> Option<i64> {
+    let text = text.trim_end();
+    let digits = text.chars().rev().take_while(char::is_ascii_digit).count();
+    text[text.len() - digits..].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn correct_averages() {
+        assert_eq!(
+            correct_printed_averages(
+                "{@h}5 ({@damage 3d6 + 2}) slashing damage plus 7 ({@damage 2d6}) fire damage."
+            ),
+            "{@h}12 ({@damage 3d6 + 2}) slashing damage plus 7 ({@damage 2d6}) fire damage."
+        );
+        assert_eq!(
+            correct_printed_averages("({@damage 1d6})"),
+            "({@damage 1d6})"
+        );
+    }
+
+    #[test]
+    fn check_averages() {
+        let text =
+            "{@h}7 ({@damage 2d6}) slashing damage plus 10 ({@damage 3d6 + 1}) fire damage, \
+            or 4 ({@dice 1d8}) if it misses.";
+
+        let averages = printed_averages(text);
+        assert_eq!(
+            averages.iter().map(|a| a.printed).collect::<Vec<_>>(),
+            vec![7, 10, 4]
+        );
+
+        let wrong = check_printed_averages(text);
+        assert_eq!(wrong.len(), 1);
+        assert_eq!(wrong[0].formula, "3d6 + 1");
+        assert_eq!(wrong[0].expected, Ok(11));
+
+        assert!(printed_averages("roll {@damage 2d6}").is_empty());
+    }
+}
@@ -0,0 +1,559 @@
+use super::expression::{BinaryOp, Dice, DiceModifier, Expression, Prompt};
+use super::roll::{apply, MAX_DICE};
+use super::{Error, Result};
+use std::collections::BTreeMap;
+
+/// The most combinations of dice enumerated when computing the distribution of kept dice,
+/// e.g. for `4d6kh3`. Beyond this, [`Error::TooComplex`] is returned.
+pub const MAX_COMBINATIONS: f64 = 2_000_000.0;
+
+/// The exact probability distribution of the total of a dice expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    /// The smallest possible total, i.e. the value whose probability is `probabilities[0]`.
+    offset: i64,
+    probabilities: Vec<f64>,
+}
+
+/// Whether a d20 is rolled with advantage or disadvantage, e.g. `2d20kh1` rather than `1d20`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Advantage {
+    #[default]
+    Normal,
+    Advantage,
+    Disadvantage,
+}
+
+impl Distribution {
+    pub fn constant(n: i64) -> Self {
+        Self {
+            offset: n,
+            probabilities: vec![1.0],
+        }
+    }
+
+    /// A single die with the given number of faces.
+    pub fn die(faces: u32) -> Result<Self> {
+        if faces == 0 {
+            return Err(Error::ZeroFaces);
+        }
+        let faces = faces as usize;
+        Ok(Self {
+            offset: 1,
+            probabilities: vec![1.0 / faces as f64; faces],
+        })
+    }
+
+    fn from_map(map: BTreeMap<i64, f64>) -> Self {
+        let offset = map.keys().next().copied().unwrap_or_default();
+        let max = map.keys().next_back().copied().unwrap_or_default();
+
+        let mut probabilities = vec![0.0; (max - offset + 1) as usize];
+        for (value, p) in map {
+            probabilities[(value - offset) as usize] += p;
+        }
+
+        Self {
+            offset,
+            probabilities,
+        }
+        .trimmed()
+    }
+
+    /// Removes impossible totals from either end.
+    fn trimmed(mut self) -> Self {
+        while self.probabilities.len() > 1 && self.probabilities.last() == Some(&0.0) {
+            self.probabilities.pop();
+        }
+        let leading = self
+            .probabilities
+            .iter()
+            .take_while(|&&p| p == 0.0)
+            .count()
+            .min(self.probabilities.len().saturating_sub(1));
+        self.probabilities.drain(..leading);
+        self.offset += leading as i64;
+        self
+    }
+
+    pub fn min(&self) -> i64 {
+        self.offset
+    }
+
+    pub fn max(&self) -> i64 {
+        self.offset + self.probabilities.len() as i64 - 1
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(value, p)| value as f64 * p).sum()
+    }
+
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.iter()
+            .map(|(value, p)| (value as f64 - mean).powi(2) * p)
+            .sum()
+    }
+
+    /// The average as printed in statblocks, which always rounds down.
+    pub fn printed_average(&self) -> i64 {
+        // Guard against the mean of e.g. 2d6 coming out as 6.999999...
+        (self.mean() + 1e-9).floor() as i64
+    }
+
+    /// The probability of rolling exactly `value`.
+    pub fn probability(&self, value: i64) -> f64 {
+        if value < self.offset {
+            return 0.0;
+        }
+        self.probabilities
+            .get((value - self.offset) as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The probability of meeting or beating a target, e.g. a DC.
+    pub fn chance_at_least(&self, target: i64) -> f64 {
+        self.iter()
+            .filter(|&(value, _)| value >= target)
+            .map(|(_, p)| p)
+            .sum()
+    }
+
+    pub fn chance_at_most(&self, target: i64) -> f64 {
+        self.iter()
+            .filter(|&(value, _)| value <= target)
+            .map(|(_, p)| p)
+            .sum()
+    }
+
+    /// Every possible total with its probability, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, f64)> + '_ {
+        self.probabilities
+            .iter()
+            .enumerate()
+            .filter(|(_, &p)| p > 0.0)
+            .map(move |(i, &p)| (self.offset + i as i64, p))
+    }
+
+    /// The distribution of the sum of two independent totals.
+    pub fn add(&self, other: &Self) -> Self {
+        let mut probabilities = vec![0.0; self.probabilities.len() + other.probabilities.len() - 1];
+        for (i, p) in self.probabilities.iter().enumerate() {
+            for (j, q) in other.probabilities.iter().enumerate() {
+                probabilities[i + j] += p * q;
+            }
+        }
+
+        Self {
+            offset: self.offset + other.offset,
+            probabilities,
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        let mut probabilities = self.probabilities.clone();
+        probabilities.reverse();
+
+        Self {
+            offset: -self.max(),
+            probabilities,
+        }
+    }
+
+    /// The distribution of `n` independent copies added together.
+    pub fn repeat(&self, n: u32) -> Self {
+        let mut result = Self::constant(0);
+        let mut base = self.clone();
+        let mut n = n;
+        // Exponentiation by squaring
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.add(&base);
+            }
+            base = base.add(&base);
+            n >>= 1;
+        }
+        result
+    }
+
+    fn combine(&self, other: &Self, op: BinaryOp) -> Result<Self> {
+        match op {
+            BinaryOp::Add => Ok(self.add(other)),
+            BinaryOp::Sub => Ok(self.add(&other.neg())),
+            BinaryOp::Mul | BinaryOp::Div => {
+                let mut map = BTreeMap::new();
+                for (a, p) in self.iter() {
+                    for (b, q) in other.iter() {
+                        *map.entry(apply(op, a, b)?).or_insert(0.0) += p * q;
+                    }
+                }
+                Ok(Self::from_map(map))
+            }
+        }
+    }
+
+    /// The distribution of one of several distributions, chosen with the given probabilities.
+    fn mixture<I: IntoIterator<Item = (f64, Self)>>(parts: I) -> Self {
+        let mut map = BTreeMap::new();
+        for (weight, part) in parts {
+            for (value, p) in part.iter() {
+                *map.entry(value).or_insert(0.0) += weight * p;
+            }
+        }
+        Self::from_map(map)
+    }
+}
+
+impl Expression {
+    /// The exact distribution of the expression's total.
+    /// Prompts take their default value; see [`Expression::distribution_with`].
+    pub fn distribution(&self) -> Result<Distribution> {
+        self.distribution_with(&[])
+    }
+
+    /// Like [`Expression::distribution`], with answers to the expression's prompts in order.
+    pub fn distribution_with(&self, answers: &[i64]) -> Result<Distribution> {
+        let mut answers = answers.iter().copied();
+        distribution(self, &mut answers)
+    }
+
+    pub fn mean(&self) -> Result<f64> {
+        Ok(self.distribution()?.mean())
+    }
+}
+
+fn distribution<I: Iterator<Item = i64>>(
+    expression: &Expression,
+    answers: &mut I,
+) -> Result<Distribution> {
+    match expression {
+        Expression::Number(n) => Ok(Distribution::constant(*n)),
+        Expression::Prompt(prompt) => Ok(Distribution::constant(answer(prompt, answers)?)),
+        Expression::Neg(inner) => Ok(distribution(inner, answers)?.neg()),
+        Expression::Binary { op, lhs, rhs } => {
+            let lhs = distribution(lhs, answers)?;
+            let rhs = distribution(rhs, answers)?;
+            lhs.combine(&rhs, *op)
+        }
+        Expression::Dice(dice) => {
+            let counts = distribution(&dice.count, answers)?;
+            let parts = counts
+                .iter()
+                .map(|(count, p)| Ok((p, dice_distribution(dice, count)?)))
+                .collect::<Result<Vec<_>>>()?;
+
+            match parts.as_slice() {
+                [(_, single)] => Ok(single.clone()),
+                _ => Ok(Distribution::mixture(parts)),
+            }
+        }
+    }
+}
+
+fn answer<I: Iterator<Item = i64>>(prompt: &Prompt, answers: &mut I) -> Result<i64> {
+    let answer = answers
+        .next()
+        .or(prompt.default)
+        .ok_or_else(|| Error::UnansweredPrompt(prompt.to_string()))?;
+
+    if prompt.accepts(answer) {
+        Ok(answer)
+    } else {
+        Err(Error::PromptOutOfRange {
+            prompt: prompt.to_string(),
+            answer,
+        })
+    }
+}
+
+/// Rerolls are applied to each die before any dice are kept or dropped.
+fn dice_distribution(dice: &Dice, count: i64) -> Result<Distribution> {
+    if dice.faces == 0 {
+        return Err(Error::ZeroFaces);
+    }
+    if count < 0 {
+        return Err(Error::NegativeCount(count));
+    }
+    if count > MAX_DICE {
+        return Err(Error::TooManyDice {
+            count,
+            max: MAX_DICE,
+        });
+    }
+    let count = count as usize;
+    let faces = dice.faces as usize;
+
+    // The distribution of a single die's final value, indexed by face - 1
+    let mut die = vec![1.0 / faces as f64; faces];
+    // The window of dice which are kept, by rank from lowest to highest
+    let (mut low, mut high) = (0, count);
+
+    for modifier in &dice.modifiers {
+        match *modifier {
+            DiceModifier::Reroll { once, condition } => {
+                let matches = |i: usize| condition.matches(i as i64 + 1);
+                let rerolled = (0..faces)
+                    .filter(|&i| matches(i))
+                    .map(|i| die[i])
+                    .sum::<f64>();
+                let remaining = (0..faces).filter(|&i| !matches(i)).count();
+                if !once && remaining == 0 {
+                    return Err(Error::RerollsEveryFace(dice.faces));
+                }
+
+                for (i, p) in die.iter_mut().enumerate() {
+                    let kept = if matches(i) { 0.0 } else { *p };
+                    *p = if once {
+                        kept + rerolled / faces as f64
+                    } else if matches(i) {
+                        0.0
+                    } else {
+                        kept + rerolled / remaining as f64
+                    };
+                }
+            }
+            DiceModifier::Keep { highest, count } => {
+                let count = count as usize;
+                if highest {
+                    low = low.max(high.saturating_sub(count));
+                } else {
+                    high = high.min(low + count);
+                }
+            }
+            DiceModifier::Drop { highest, count } => {
+                let count = count as usize;
+                if highest {
+                    high = high.saturating_sub(count).max(low);
+                } else {
+                    low = (low + count).min(high);
+                }
+            }
+        }
+    }
+
+    let single = Distribution {
+        offset: 1,
+        probabilities: die,
+    }
+    .trimmed();
+
+    if low == 0 && high == count {
+        return Ok(single.repeat(count as u32));
+    }
+    kept_distribution(&single, count, low, high)
+}
+
+/// The distribution of the sum of the dice ranked `low..high` (from lowest) out of `count` dice,
+/// found by enumerating every multiset of values the dice could show.
+fn kept_distribution(
+    die: &Distribution,
+    count: usize,
+    low: usize,
+    high: usize,
+) -> Result<Distribution> {
+    let values = die.iter().collect::<Vec<_>>();
+
+    let combinations = binomial((count + values.len()).saturating_sub(1), count);
+    if combinations > MAX_COMBINATIONS {
+        return Err(Error::TooComplex(format!(
+            "{} dice with {} possible values each",
+            count,
+            values.len()
+        )));
+    }
+
+    let ln_factorials = (0..=count)
+        .scan(0.0, |acc: &mut f64, n| {
+            if n > 0 {
+                *acc += (n as f64).ln();
+            }
+            Some(*acc)
+        })
+        .collect::<Vec<_>>();
+
+    let mut map = BTreeMap::new();
+    let mut counts = vec![0; values.len()];
+    enumerate(&mut counts, 0, count, &mut |counts| {
+        // Multinomial probability of seeing exactly these counts
+        let mut ln_p = ln_factorials[count];
+        for (&(_, p), &c) in values.iter().zip(counts) {
+            ln_p += c as f64 * p.ln() - ln_factorials[c];
+        }
+
+        let mut total = 0;
+        let mut rank = 0;
+        for (&(value, _), &c) in values.iter().zip(counts) {
+            let kept = (rank + c).min(high).saturating_sub(rank.max(low));
+            total += value * kept as i64;
+            rank += c;
+        }
+
+        *map.entry(total).or_insert(0.0) += ln_p.exp();
+    });
+
+    Ok(Distribution::from_map(map))
+}
+
+/// Calls `f` with every way of distributing `remaining` dice among the values from `index` on.
+fn enumerate<F: FnMut(&[usize])>(
+    counts: &mut Vec<usize>,
+    index: usize,
+    remaining: usize,
+    f: &mut F,
+) {
+    if index == counts.len() - 1 {
+        counts[index] = remaining;
+        f(counts);
+        return;
+    }
+    for c in 0..=remaining {
+        counts[index] = c;
+        enumerate(counts, index + 1, remaining - c, f);
+    }
+    counts[index] = 0;
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k.min(n - k.min(n))).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+impl Advantage {
+    /// The d20 rolled for an attack roll, check or saving throw.
+    pub fn d20(&self) -> Expression {
+        let mut d20 = Dice {
+            count: Box::new(Expression::Number(1)),
+            faces: 20,
+            modifiers: Vec::new(),
+        };
+        if *self != Advantage::Normal {
+            d20.count = Box::new(Expression::Number(2));
+            d20.modifiers.push(DiceModifier::Keep {
+                highest: *self == Advantage::Advantage,
+                count: 1,
+            });
+        }
+        Expression::Dice(d20)
+    }
+
+    fn d20_distribution(&self) -> Distribution {
+        self.d20()
+            .distribution()
+            .expect("a d20 always has a distribution")
+    }
+}
+
+/// The chance of meeting or beating a DC with a d20 roll, e.g. `{@d20 5}` against `{@dc 15}`.
+pub fn chance_to_succeed(bonus: i64, dc: i64, advantage: Advantage) -> f64 {
+    advantage.d20_distribution().chance_at_least(dc - bonus)
+}
+
+/// The chance of an attack roll hitting, e.g. `{@hit 5}` against an AC.
+/// A natural 20 always hits and a natural 1 always misses.
+pub fn chance_to_hit(bonus: i64, ac: i64, advantage: Advantage) -> f64 {
+    advantage
+        .d20_distribution()
+        .iter()
+        .filter(|&(roll, _)| roll == 20 || (roll != 1 && roll + bonus >= ac))
+        .map(|(_, p)| p)
+        .sum()
+}
+
+/// The chance of an attack roll being a critical hit.
+pub fn chance_to_crit(advantage: Advantage) -> f64 {
+    advantage.d20_distribution().probability(20)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dist(input: &str) -> Distribution {
+        input.parse::<Expression>().unwrap().distribution().unwrap()
+    }
+
+    fn approx(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn distribution_sums() {
+        let d = dist("2d6 + 3");
+        assert_eq!((d.min(), d.max()), (5, 15));
+        approx(d.mean(), 10.0);
+        approx(d.probability(10), 6.0 / 36.0);
+        approx(d.chance_at_least(14), 3.0 / 36.0);
+        approx(d.iter().map(|(_, p)| p).sum(), 1.0);
+
+        let d = dist("1d4 - 1d4");
+        assert_eq!((d.min(), d.max()), (-3, 3));
+        approx(d.mean(), 0.0);
+
+        approx(dist("1d6 * 2").probability(7), 0.0);
+        approx(dist("1d6 / 2").probability(0), 1.0 / 6.0);
+    }
+
+    #[test]
+    fn distribution_zero_faces() {
+        assert_eq!(Expression::dice(1, 0).distribution(), Err(Error::ZeroFaces));
+        assert_eq!(Expression::dice(0, 0).distribution(), Err(Error::ZeroFaces));
+        assert_eq!(Distribution::die(0), Err(Error::ZeroFaces));
+        assert_eq!(Distribution::die(4).map(|d| d.max()), Ok(4));
+    }
+
+    #[test]
+    fn distribution_keep() {
+        let d = dist("4d6kh3");
+        assert_eq!((d.min(), d.max()), (3, 18));
+        approx(d.mean(), 15869.0 / 1296.0);
+        approx(d.probability(18), 21.0 / 1296.0);
+
+        approx(dist("2d20kh1").mean(), 13.825);
+        approx(dist("2d20kl1").mean(), 7.175);
+        approx(dist("4d6dl1").mean(), dist("4d6kh3").mean());
+    }
+
+    #[test]
+    fn distribution_rerolls() {
+        // Great Weapon Fighting: reroll 1s and 2s once
+        approx(dist("1d6r<3").mean(), 25.0 / 6.0);
+        approx(dist("1d6rr<3").mean(), 4.5);
+        approx(dist("2d6r<=2").mean(), 25.0 / 3.0);
+    }
+
+    #[test]
+    fn distribution_prompts() {
+        let expression = "#$prompt_number:min=1,max=9$#d8"
+            .parse::<Expression>()
+            .unwrap();
+        assert_eq!(
+            expression.distribution(),
+            Err(Error::UnansweredPrompt(
+                "#$prompt_number:min=1,max=9$#".to_owned()
+            ))
+        );
+        approx(expression.distribution_with(&[3]).unwrap().mean(), 13.5);
+
+        // A random number of dice
+        approx(dist("(1d2)d6").mean(), 1.5 * 3.5);
+    }
+
+    #[test]
+    fn d20_chances() {
+        approx(chance_to_succeed(5, 15, Advantage::Normal), 0.55);
+        approx(
+            chance_to_succeed(5, 15, Advantage::Advantage),
+            1.0 - 0.45 * 0.45,
+        );
+        approx(
+            chance_to_succeed(5, 15, Advantage::Disadvantage),
+            0.55 * 0.55,
+        );
+        approx(chance_to_succeed(0, 30, Advantage::Normal), 0.0);
+
+        approx(chance_to_hit(5, 15, Advantage::Normal), 0.55);
+        approx(chance_to_hit(20, 15, Advantage::Normal), 0.95);
+        approx(chance_to_hit(0, 30, Advantage::Normal), 0.05);
+        approx(chance_to_crit(Advantage::Advantage), 1.0 - 0.95 * 0.95);
+    }
+}
@@ -0,0 +1,76 @@
+use super::{Expression, Formula};
+use crate::entry::kinds::{EntryDice, EntryDiceToRoll};
+
+impl EntryDiceToRoll {
+    /// The dice to roll, including the modifier even if it is hidden.
+    pub fn expression(&self) -> Expression {
+        let dice = Expression::dice(self.number.into(), self.faces.into());
+        dice.plus(self.modifier.unwrap_or_default().into())
+    }
+
+    /// The dice as they should be displayed, without the modifier if it is hidden.
+    pub fn display(&self) -> String {
+        if self.hide_modifier.unwrap_or_default() {
+            format!("{}d{}", self.number, self.faces)
+        } else {
+            self.expression().to_string()
+        }
+    }
+}
+
+impl EntryDice<'_> {
+    /// The sum of all the dice to roll, or `None` if there are none.
+    pub fn expression(&self) -> Option<Expression> {
+        self.to_roll
+            .iter()
+            .flatten()
+            .map(EntryDiceToRoll::expression)
+            .reduce(|lhs, rhs| Expression::binary(super::BinaryOp::Add, lhs, rhs))
+    }
+
+    pub fn formula(&self) -> Option<Formula> {
+        self.expression().map(|expression| Formula {
+            parts: vec![expression],
+        })
+    }
+
+    pub fn display(&self) -> String {
+        self.to_roll
+            .iter()
+            .flatten()
+            .map(EntryDiceToRoll::display)
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dice::Roller;
+    use crate::entry::kinds::{EntryDice, EntryDiceToRoll};
+
+    #[test]
+    fn entry_dice_modifiers() {
+        let to_roll = |modifier, hide_modifier| EntryDiceToRoll {
+            number: 2,
+            faces: 6,
+            modifier,
+            hide_modifier,
+        };
+        let dice = EntryDice {
+            base: Default::default(),
+            to_roll: Some(vec![to_roll(Some(3), None), to_roll(Some(-1), Some(true))]),
+            rollable: Some(true),
+        };
+
+        assert_eq!(
+            dice.expression().unwrap().to_string(),
+            "2d6 + 3 + (2d6 - 1)"
+        );
+        assert_eq!(dice.display(), "2d6 + 3 + 2d6");
+
+        let roll = Roller::seeded(0).roll(&dice.expression().unwrap()).unwrap();
+        let dice_total = roll.groups.iter().map(|g| g.total).sum::<i64>();
+        assert_eq!(roll.total, dice_total + 2);
+    }
+}
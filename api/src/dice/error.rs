@@ -0,0 +1,43 @@
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("unexpected character `{found}` at position {position} in dice expression")]
+    UnexpectedChar { position: usize, found: char },
+    #[error("unexpected end of dice expression")]
+    UnexpectedEnd,
+    #[error("empty dice expression")]
+    Empty,
+    #[error("`{0}` is not a number")]
+    InvalidNumber(String),
+    #[error("the number `{0}` is too large")]
+    NumberTooLarge(String),
+    #[error("dice must have at least one face")]
+    ZeroFaces,
+    #[error("cannot roll a negative number of dice ({0})")]
+    NegativeCount(i64),
+    #[error("cannot roll more than {max} dice at once (tried to roll {count})")]
+    TooManyDice { count: i64, max: i64 },
+    #[error("invalid prompt `{0}`")]
+    InvalidPrompt(String),
+    #[error("no answer was given for the prompt `{0}`")]
+    UnansweredPrompt(String),
+    #[error("the answer {answer} is not within the range of the prompt `{prompt}`")]
+    PromptOutOfRange { prompt: String, answer: i64 },
+    #[error("every face of a d{0} would be rerolled")]
+    RerollsEveryFace(u32),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("the distribution of {0} is too costly to compute exactly")]
+    TooComplex(String),
+    #[error("invalid scaling `{0}`, expected a formula, levels and an increment")]
+    InvalidScaling(String),
+    #[error("invalid levels `{0}`")]
+    InvalidLevels(String),
+    #[error("level {level} is not one of the levels {levels}")]
+    LevelOutOfRange { level: u32, levels: String },
+    #[error("the tag `{0}` does not contain a dice expression")]
+    NotADiceTag(String),
+}
@@ -0,0 +1,701 @@
+use super::{Error, Result};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// One or more dice expressions separated by semicolons, e.g. `1d20;1d4`, which are rolled separately.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formula {
+    pub parts: Vec<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    Number(i64),
+    Dice(Dice),
+    /// A value chosen by the user when rolling, e.g. the level a spell is cast at.
+    Prompt(Prompt),
+    Neg(Box<Expression>),
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    /// Rounds down, as everywhere in 5e.
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dice {
+    /// Usually a number, but may be a prompt or any other expression, e.g. `(1+2)d6`.
+    pub count: Box<Expression>,
+    pub faces: u32,
+    pub modifiers: Vec<DiceModifier>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DiceModifier {
+    /// `kh3` keeps the three highest dice, `kl1` the lowest.
+    Keep { highest: bool, count: u32 },
+    /// `dl1` drops the lowest die, `dh1` the highest.
+    Drop { highest: bool, count: u32 },
+    /// `r<3` rerolls any die below 3 once; `rr<3` keeps rerolling until it is at least 3.
+    Reroll { once: bool, condition: Comparison },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Comparison {
+    pub op: CompareOp,
+    pub value: i64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CompareOp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// `#$prompt_number:min=1,max=9,default=1,title=Enter a level$#`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub default: Option<i64>,
+    pub title: Option<String>,
+}
+
+impl Formula {
+    /// The dice expression of a `{@dice}`, `{@damage}`, `{@d20}` or `{@hit}` tag, given its arguments.
+    /// `{@d20 5}` and `{@hit 5}` are shorthand for `1d20+5`.
+    pub fn from_tag(name: &str, args: &[&str]) -> Result<Self> {
+        let first = args.first().map(|s| s.trim()).unwrap_or_default();
+
+        match name {
+            "dice" | "damage" => first.parse(),
+            "d20" | "hit" => {
+                let bonus = first
+                    .parse::<i64>()
+                    .map_err(|_| Error::InvalidNumber(first.to_owned()))?;
+                Ok(Self {
+                    parts: vec![Expression::d20(bonus)],
+                })
+            }
+            _ => Err(Error::NotADiceTag(name.to_owned())),
+        }
+    }
+}
+
+impl FromStr for Formula {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s
+            .split(';')
+            .map(str::parse)
+            .collect::<Result<Vec<Expression>>>()?;
+
+        Ok(Self { parts })
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{}", part)?;
+        }
+        Ok(())
+    }
+}
+
+impl Expression {
+    pub fn dice(count: i64, faces: u32) -> Self {
+        Self::Dice(Dice {
+            count: Box::new(Self::Number(count)),
+            faces,
+            modifiers: Vec::new(),
+        })
+    }
+
+    /// `1d20` plus a (possibly negative) bonus.
+    pub fn d20(bonus: i64) -> Self {
+        Self::dice(1, 20).plus(bonus)
+    }
+
+    /// Adds a constant, omitting it if it is zero and subtracting if it is negative.
+    pub fn plus(self, n: i64) -> Self {
+        match n {
+            0 => self,
+            n if n < 0 => Self::binary(BinaryOp::Sub, self, Self::Number(-n)),
+            n => Self::binary(BinaryOp::Add, self, Self::Number(n)),
+        }
+    }
+
+    pub fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Self {
+        Self::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    /// Every group of dice in the expression, in order.
+    pub fn dice_groups(&self) -> Vec<&Dice> {
+        let mut groups = Vec::new();
+        self.visit(&mut |e| {
+            if let Expression::Dice(dice) = e {
+                groups.push(dice);
+            }
+        });
+        groups
+    }
+
+    /// Every prompt in the expression, in the order they are answered when rolling.
+    pub fn prompts(&self) -> Vec<&Prompt> {
+        let mut prompts = Vec::new();
+        self.visit(&mut |e| {
+            if let Expression::Prompt(prompt) = e {
+                prompts.push(prompt);
+            }
+        });
+        prompts
+    }
+
+    fn visit<'a, F: FnMut(&'a Expression)>(&'a self, f: &mut F) {
+        f(self);
+        match self {
+            Expression::Number(_) | Expression::Prompt(_) => {}
+            Expression::Dice(dice) => dice.count.visit(f),
+            Expression::Neg(inner) => inner.visit(f),
+            Expression::Binary { lhs, rhs, .. } => {
+                lhs.visit(f);
+                rhs.visit(f);
+            }
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary {
+                op: BinaryOp::Add | BinaryOp::Sub,
+                ..
+            } => 1,
+            Expression::Binary { .. } => 2,
+            Expression::Neg(_) => 3,
+            _ => 4,
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser::new(s);
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            return Err(Error::Empty);
+        }
+
+        let expression = parser.expression()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(expression),
+            Some((position, found)) => Err(Error::UnexpectedChar { position, found }),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Dice(dice) => write!(f, "{}", dice),
+            Expression::Prompt(prompt) => write!(f, "{}", prompt),
+            Expression::Neg(inner) if inner.precedence() < self.precedence() => {
+                write!(f, "-({})", inner)
+            }
+            Expression::Neg(inner) => write!(f, "-{}", inner),
+            Expression::Binary { op, lhs, rhs } => {
+                let precedence = self.precedence();
+                if lhs.precedence() < precedence {
+                    write!(f, "({})", lhs)?;
+                } else {
+                    write!(f, "{}", lhs)?;
+                }
+                write!(f, " {} ", op)?;
+                // Operators are left-associative, so the right operand needs parentheses on ties
+                if rhs.precedence() <= precedence {
+                    write!(f, "({})", rhs)
+                } else {
+                    write!(f, "{}", rhs)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+        })
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.count {
+            Expression::Number(_) | Expression::Prompt(_) => write!(f, "{}", self.count)?,
+            count => write!(f, "({})", count)?,
+        }
+        write!(f, "d{}", self.faces)?;
+        for modifier in &self.modifiers {
+            write!(f, "{}", modifier)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DiceModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |highest: bool| if highest { "h" } else { "l" };
+        match self {
+            DiceModifier::Keep { highest, count } => write!(f, "k{}{}", side(*highest), count),
+            DiceModifier::Drop { highest, count } => write!(f, "d{}{}", side(*highest), count),
+            DiceModifier::Reroll { once, condition } => {
+                write!(f, "{}{}", if *once { "r" } else { "rr" }, condition)
+            }
+        }
+    }
+}
+
+impl Comparison {
+    pub fn matches(&self, n: i64) -> bool {
+        match self.op {
+            CompareOp::Eq => n == self.value,
+            CompareOp::Lt => n < self.value,
+            CompareOp::Le => n <= self.value,
+            CompareOp::Gt => n > self.value,
+            CompareOp::Ge => n >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            CompareOp::Eq => "",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        };
+        write!(f, "{}{}", op, self.value)
+    }
+}
+
+impl Prompt {
+    /// Whether an answer is within the bounds of the prompt.
+    pub fn accepts(&self, answer: i64) -> bool {
+        self.min.is_none_or(|min| answer >= min) && self.max.is_none_or(|max| answer <= max)
+    }
+}
+
+impl FromStr for Prompt {
+    type Err = Error;
+
+    /// Parses the contents of a prompt, without the surrounding `#$` and `$#`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidPrompt(s.to_owned());
+
+        let options = s.strip_prefix("prompt_number").ok_or_else(invalid)?;
+        let options = match options.strip_prefix(':') {
+            Some(options) => options,
+            None if options.is_empty() => return Ok(Self::default()),
+            None => return Err(invalid()),
+        };
+
+        let mut prompt = Self::default();
+        for option in options.split(',').filter(|o| !o.trim().is_empty()) {
+            let (key, value) = option.split_once('=').ok_or_else(invalid)?;
+            let number = || value.trim().parse::<i64>().map_err(|_| invalid());
+            match key.trim() {
+                "min" => prompt.min = Some(number()?),
+                "max" => prompt.max = Some(number()?),
+                "default" => prompt.default = Some(number()?),
+                "title" => prompt.title = Some(value.to_owned()),
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(prompt)
+    }
+}
+
+impl fmt::Display for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options = Vec::new();
+        if let Some(min) = self.min {
+            options.push(format!("min={}", min));
+        }
+        if let Some(max) = self.max {
+            options.push(format!("max={}", max));
+        }
+        if let Some(default) = self.default {
+            options.push(format!("default={}", default));
+        }
+        if let Some(title) = &self.title {
+            options.push(format!("title={}", title));
+        }
+
+        if options.is_empty() {
+            write!(f, "#$prompt_number$#")
+        } else {
+            write!(f, "#$prompt_number:{}$#", options.join(","))
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().peekable(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<(usize, char)> {
+        self.chars.peek().copied()
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.peek().map(|(_, c)| c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek_char().is_some_and(char::is_whitespace) {
+            self.chars.next();
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek_char() == Some(c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&mut self) -> Error {
+        match self.peek() {
+            Some((position, found)) => Error::UnexpectedChar { position, found },
+            None => Error::UnexpectedEnd,
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression> {
+        let mut lhs = self.term()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.peek_char() {
+                Some('+') => BinaryOp::Add,
+                // Some sources use a unicode minus sign
+                Some('-') | Some('−') => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.chars.next();
+            let rhs = self.term()?;
+            lhs = Expression::binary(op, lhs, rhs);
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression> {
+        let mut lhs = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.peek_char() {
+                Some('*') | Some('×') => BinaryOp::Mul,
+                Some('/') | Some('÷') => BinaryOp::Div,
+                _ => return Ok(lhs),
+            };
+            self.chars.next();
+            let rhs = self.unary()?;
+            lhs = Expression::binary(op, lhs, rhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression> {
+        self.skip_whitespace();
+        match self.peek_char() {
+            Some('-') | Some('−') => {
+                self.chars.next();
+                Ok(Expression::Neg(Box::new(self.unary()?)))
+            }
+            Some('+') => {
+                self.chars.next();
+                self.unary()
+            }
+            _ => self.dice(),
+        }
+    }
+
+    /// An atom, optionally followed by `d<faces>` and modifiers. A leading `d` means one die.
+    fn dice(&mut self) -> Result<Expression> {
+        self.skip_whitespace();
+        let count = match self.peek_char() {
+            Some('d') | Some('D') => Expression::Number(1),
+            _ => self.atom()?,
+        };
+
+        if !matches!(self.peek_char(), Some('d') | Some('D')) {
+            return Ok(count);
+        }
+        self.chars.next();
+
+        let faces = match self.peek_char() {
+            Some('%') => {
+                self.chars.next();
+                100
+            }
+            _ => self.number()?,
+        };
+        let faces = u32::try_from(faces).map_err(|_| Error::NumberTooLarge(faces.to_string()))?;
+        if faces == 0 {
+            return Err(Error::ZeroFaces);
+        }
+
+        let mut modifiers = Vec::new();
+        while let Some(modifier) = self.modifier()? {
+            modifiers.push(modifier);
+        }
+
+        Ok(Expression::Dice(Dice {
+            count: Box::new(count),
+            faces,
+            modifiers,
+        }))
+    }
+
+    fn modifier(&mut self) -> Result<Option<DiceModifier>> {
+        let modifier = match self.peek_char() {
+            Some('k') => {
+                self.chars.next();
+                let highest = !self.eat('l');
+                if highest {
+                    self.eat('h');
+                }
+                DiceModifier::Keep {
+                    highest,
+                    count: self.optional_count()?,
+                }
+            }
+            Some('d') => {
+                self.chars.next();
+                let highest = match self.peek_char() {
+                    Some('h') => true,
+                    Some('l') => false,
+                    _ => return Err(self.unexpected()),
+                };
+                self.chars.next();
+                DiceModifier::Drop {
+                    highest,
+                    count: self.optional_count()?,
+                }
+            }
+            Some('r') => {
+                self.chars.next();
+                let once = !self.eat('r');
+                DiceModifier::Reroll {
+                    once,
+                    condition: self.comparison()?,
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(modifier))
+    }
+
+    fn optional_count(&mut self) -> Result<u32> {
+        if !self.peek_char().is_some_and(|c| c.is_ascii_digit()) {
+            return Ok(1);
+        }
+        let count = self.number()?;
+        u32::try_from(count).map_err(|_| Error::NumberTooLarge(count.to_string()))
+    }
+
+    fn comparison(&mut self) -> Result<Comparison> {
+        let op = if self.eat('<') {
+            if self.eat('=') {
+                CompareOp::Le
+            } else {
+                CompareOp::Lt
+            }
+        } else if self.eat('>') {
+            if self.eat('=') {
+                CompareOp::Ge
+            } else {
+                CompareOp::Gt
+            }
+        } else {
+            self.eat('=');
+            CompareOp::Eq
+        };
+
+        Ok(Comparison {
+            op,
+            value: self.number()?,
+        })
+    }
+
+    fn atom(&mut self) -> Result<Expression> {
+        self.skip_whitespace();
+        match self.peek_char() {
+            Some('(') => {
+                self.chars.next();
+                let inner = self.expression()?;
+                self.skip_whitespace();
+                if !self.eat(')') {
+                    return Err(self.unexpected());
+                }
+                Ok(inner)
+            }
+            Some('#') => self.prompt(),
+            Some(c) if c.is_ascii_digit() => Ok(Expression::Number(self.number()?)),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn prompt(&mut self) -> Result<Expression> {
+        let (start, _) = self.peek().ok_or(Error::UnexpectedEnd)?;
+        let rest = &self.input[start..];
+
+        let inner = rest.strip_prefix("#$").ok_or_else(|| self.unexpected())?;
+        let end = inner
+            .find("$#")
+            .ok_or_else(|| Error::InvalidPrompt(rest.to_owned()))?;
+        let prompt = inner[..end].parse()?;
+
+        let len = "#$".len() + end + "$#".len();
+        while self.peek().is_some_and(|(i, _)| i < start + len) {
+            self.chars.next();
+        }
+
+        Ok(Expression::Prompt(prompt))
+    }
+
+    fn number(&mut self) -> Result<i64> {
+        let start = match self.peek() {
+            Some((i, c)) if c.is_ascii_digit() => i,
+            _ => return Err(self.unexpected()),
+        };
+        let mut end = start;
+        while let Some((i, c)) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            end = i + c.len_utf8();
+            self.chars.next();
+        }
+
+        let digits = &self.input[start..end];
+        digits
+            .parse()
+            .map_err(|_| Error::NumberTooLarge(digits.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str, expected: &str) {
+        let formula = input.parse::<Formula>().unwrap();
+        assert_eq!(formula.to_string(), expected);
+    }
+
+    #[test]
+    fn parse_dice() {
+        check("2d6 + 3", "2d6 + 3");
+        check("1d20;1d4", "1d20;1d4");
+        check("4d6kh3", "4d6kh3");
+        check("d20", "1d20");
+        check("2d20kl", "2d20kl1");
+        check("4d6dl1", "4d6dl1");
+        check("2d6r<3", "2d6r<3");
+        check("1d10rr1", "1d10rr1");
+        check("(1+2)d6 * 2", "(1 + 2)d6 * 2");
+        check("1d8 − 1", "1d8 - 1");
+        check("1d6 - (2 - 1)", "1d6 - (2 - 1)");
+        check(
+            "1d8 + #$prompt_number:min=1,max=9$#d8",
+            "1d8 + #$prompt_number:min=1,max=9$#d8",
+        );
+    }
+
+    #[test]
+    fn parse_prompt() {
+        let expression = "#$prompt_number:min=1,max=9,default=3,title=Spell level$#d8"
+            .parse::<Expression>()
+            .unwrap();
+        assert_eq!(
+            expression.prompts(),
+            vec![&Prompt {
+                min: Some(1),
+                max: Some(9),
+                default: Some(3),
+                title: Some("Spell level".to_owned()),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("".parse::<Expression>(), Err(Error::Empty));
+        assert_eq!("2d".parse::<Expression>(), Err(Error::UnexpectedEnd));
+        assert_eq!("1d0".parse::<Expression>(), Err(Error::ZeroFaces));
+        assert_eq!(
+            "2d6 +* 3".parse::<Expression>(),
+            Err(Error::UnexpectedChar {
+                position: 5,
+                found: '*'
+            })
+        );
+    }
+
+    #[test]
+    fn formula_from_tag() {
+        assert_eq!(
+            Formula::from_tag("hit", &["-1"]).unwrap().to_string(),
+            "1d20 - 1"
+        );
+        assert_eq!(
+            Formula::from_tag("damage", &["2d6 + 3", "display"])
+                .unwrap()
+                .to_string(),
+            "2d6 + 3"
+        );
+    }
+}
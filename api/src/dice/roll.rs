@@ -0,0 +1,321 @@
+use super::expression::{BinaryOp, Dice, DiceModifier, Expression, Formula, Prompt};
+use super::{Error, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+
+/// The most dice a single group may roll, to guard against expressions like `99999999d6`.
+pub const MAX_DICE: i64 = 10_000;
+
+/// Rolls dice expressions. Seeding the roller makes every roll reproducible.
+#[derive(Debug, Clone)]
+pub struct Roller<R = ChaCha8Rng> {
+    rng: R,
+    answers: VecDeque<i64>,
+}
+
+/// The outcome of rolling a single expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roll {
+    pub total: i64,
+    /// Every group of dice rolled, in the order they appear in the expression.
+    pub groups: Vec<RolledDice>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolledDice {
+    pub faces: u32,
+    pub dice: Vec<Die>,
+    /// The sum of the kept dice.
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Die {
+    pub value: u32,
+    /// False if the die was dropped by a keep or drop modifier.
+    pub kept: bool,
+    /// The values the die showed before being rerolled, oldest first.
+    pub rerolled: Vec<u32>,
+}
+
+impl Roller<ChaCha8Rng> {
+    pub fn seeded(seed: u64) -> Self {
+        Self::new(ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(ChaCha8Rng::from_entropy())
+    }
+}
+
+impl<R: Rng> Roller<R> {
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng,
+            answers: VecDeque::new(),
+        }
+    }
+
+    /// Queues answers for the prompts of the expressions rolled next, in order.
+    /// Prompts without an answer use their default value, if they have one.
+    pub fn answer_prompts<I: IntoIterator<Item = i64>>(&mut self, answers: I) -> &mut Self {
+        self.answers.extend(answers);
+        self
+    }
+
+    pub fn rng(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    pub fn roll_die(&mut self, faces: u32) -> Result<u32> {
+        match faces {
+            0 => Err(Error::ZeroFaces),
+            _ => Ok(self.rng.gen_range(1..=faces)),
+        }
+    }
+
+    pub fn roll(&mut self, expression: &Expression) -> Result<Roll> {
+        let mut groups = Vec::new();
+        let total = self.evaluate(expression, &mut groups)?;
+
+        Ok(Roll { total, groups })
+    }
+
+    /// Rolls each part of a formula separately.
+    pub fn roll_formula(&mut self, formula: &Formula) -> Result<Vec<Roll>> {
+        formula.parts.iter().map(|part| self.roll(part)).collect()
+    }
+
+    fn evaluate(&mut self, expression: &Expression, groups: &mut Vec<RolledDice>) -> Result<i64> {
+        match expression {
+            Expression::Number(n) => Ok(*n),
+            Expression::Prompt(prompt) => self.answer(prompt),
+            Expression::Neg(inner) => Ok(self.evaluate(inner, groups)?.saturating_neg()),
+            Expression::Binary { op, lhs, rhs } => {
+                let lhs = self.evaluate(lhs, groups)?;
+                let rhs = self.evaluate(rhs, groups)?;
+                apply(*op, lhs, rhs)
+            }
+            Expression::Dice(dice) => {
+                let count = self.evaluate(&dice.count, groups)?;
+                let rolled = self.roll_dice(dice, count)?;
+                let total = rolled.total;
+                groups.push(rolled);
+                Ok(total)
+            }
+        }
+    }
+
+    fn answer(&mut self, prompt: &Prompt) -> Result<i64> {
+        let answer = self
+            .answers
+            .pop_front()
+            .or(prompt.default)
+            .ok_or_else(|| Error::UnansweredPrompt(prompt.to_string()))?;
+
+        if prompt.accepts(answer) {
+            Ok(answer)
+        } else {
+            Err(Error::PromptOutOfRange {
+                prompt: prompt.to_string(),
+                answer,
+            })
+        }
+    }
+
+    fn roll_dice(&mut self, dice: &Dice, count: i64) -> Result<RolledDice> {
+        if dice.faces == 0 {
+            return Err(Error::ZeroFaces);
+        }
+        if count < 0 {
+            return Err(Error::NegativeCount(count));
+        }
+        if count > MAX_DICE {
+            return Err(Error::TooManyDice {
+                count,
+                max: MAX_DICE,
+            });
+        }
+
+        let mut rolled = (0..count)
+            .map(|_| {
+                Ok(Die {
+                    value: self.roll_die(dice.faces)?,
+                    kept: true,
+                    rerolled: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for modifier in &dice.modifiers {
+            match *modifier {
+                DiceModifier::Reroll { once, condition } => {
+                    if !once && (1..=dice.faces).all(|f| condition.matches(f.into())) {
+                        return Err(Error::RerollsEveryFace(dice.faces));
+                    }
+                    for die in &mut rolled {
+                        while condition.matches(die.value.into()) {
+                            die.rerolled.push(die.value);
+                            die.value = self.roll_die(dice.faces)?;
+                            if once {
+                                break;
+                            }
+                        }
+                    }
+                }
+                DiceModifier::Keep { highest, count } => {
+                    let kept = rolled.iter().filter(|d| d.kept).count();
+                    let dropped = kept.saturating_sub(count as usize);
+                    drop_dice(&mut rolled, !highest, dropped);
+                }
+                DiceModifier::Drop { highest, count } => {
+                    drop_dice(&mut rolled, highest, count as usize);
+                }
+            }
+        }
+
+        let total = rolled
+            .iter()
+            .filter(|d| d.kept)
+            .map(|d| i64::from(d.value))
+            .sum();
+
+        Ok(RolledDice {
+            faces: dice.faces,
+            dice: rolled,
+            total,
+        })
+    }
+}
+
+/// Drops the `n` highest (or lowest) dice which are still kept.
+fn drop_dice(dice: &mut [Die], highest: bool, n: usize) {
+    let mut order = (0..dice.len())
+        .filter(|&i| dice[i].kept)
+        .collect::<Vec<_>>();
+    order.sort_by_key(|&i| dice[i].value);
+    if highest {
+        order.reverse();
+    }
+
+    for i in order.into_iter().take(n) {
+        dice[i].kept = false;
+    }
+}
+
+pub(crate) fn apply(op: BinaryOp, lhs: i64, rhs: i64) -> Result<i64> {
+    match op {
+        BinaryOp::Add => Ok(lhs.saturating_add(rhs)),
+        BinaryOp::Sub => Ok(lhs.saturating_sub(rhs)),
+        BinaryOp::Mul => Ok(lhs.saturating_mul(rhs)),
+        BinaryOp::Div if rhs == 0 => Err(Error::DivisionByZero),
+        BinaryOp::Div if lhs == i64::MIN && rhs == -1 => Ok(i64::MAX),
+        BinaryOp::Div => {
+            let quotient = lhs / rhs;
+            if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+                Ok(quotient - 1)
+            } else {
+                Ok(quotient)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll(roller: &mut Roller, input: &str) -> Roll {
+        roller.roll(&input.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn roll_seeded() {
+        let first = roll(&mut Roller::seeded(7), "10d20 + 3");
+        let second = roll(&mut Roller::seeded(7), "10d20 + 3");
+        assert_eq!(first, second);
+
+        let dice = &first.groups[0];
+        assert_eq!(dice.dice.len(), 10);
+        assert!(dice.dice.iter().all(|d| (1..=20).contains(&d.value)));
+        assert_eq!(first.total, dice.total + 3);
+    }
+
+    #[test]
+    fn roll_keep_highest() {
+        let mut roller = Roller::seeded(1);
+        for _ in 0..50 {
+            let result = roll(&mut roller, "4d6kh3");
+            let dice = &result.groups[0].dice;
+
+            let mut values = dice.iter().map(|d| d.value).collect::<Vec<_>>();
+            values.sort_unstable();
+            assert_eq!(dice.iter().filter(|d| d.kept).count(), 3);
+            assert_eq!(
+                result.total,
+                values[1..].iter().map(|&v| i64::from(v)).sum::<i64>()
+            );
+        }
+    }
+
+    #[test]
+    fn roll_rerolls() {
+        let mut roller = Roller::seeded(3);
+        for _ in 0..50 {
+            let result = roll(&mut roller, "8d6rr<3");
+            for die in &result.groups[0].dice {
+                assert!(die.value >= 3);
+                assert!(die.rerolled.iter().all(|&v| v < 3));
+            }
+        }
+        assert_eq!(
+            roller.roll(&"1d4rr<5".parse().unwrap()),
+            Err(Error::RerollsEveryFace(4))
+        );
+    }
+
+    #[test]
+    fn roll_prompts() {
+        let expression = "#$prompt_number:min=1,max=9$#d8".parse().unwrap();
+
+        let mut roller = Roller::seeded(0);
+        roller.answer_prompts(vec![3]);
+        assert_eq!(roller.roll(&expression).unwrap().groups[0].dice.len(), 3);
+        assert!(matches!(
+            roller.roll(&expression),
+            Err(Error::UnansweredPrompt(_))
+        ));
+        roller.answer_prompts(vec![10]);
+        assert!(matches!(
+            roller.roll(&expression),
+            Err(Error::PromptOutOfRange { answer: 10, .. })
+        ));
+    }
+
+    #[test]
+    fn roll_arithmetic() {
+        let mut roller = Roller::seeded(0);
+        assert_eq!(roll(&mut roller, "7 / 2").total, 3);
+        assert_eq!(roll(&mut roller, "-7 / 2").total, -4);
+        assert_eq!(roll(&mut roller, "2 * (3 + 4) - 1").total, 13);
+
+        let min = || Box::new(Expression::Number(i64::MIN));
+        let neg = Expression::Neg(min());
+        assert_eq!(roller.roll(&neg).unwrap().total, i64::MAX);
+        let div = Expression::Binary {
+            op: BinaryOp::Div,
+            lhs: min(),
+            rhs: Box::new(Expression::Number(-1)),
+        };
+        assert_eq!(roller.roll(&div).unwrap().total, i64::MAX);
+    }
+
+    #[test]
+    fn roll_zero_faces() {
+        let mut roller = Roller::seeded(0);
+        assert_eq!(roller.roll_die(0), Err(Error::ZeroFaces));
+        assert_eq!(roller.roll(&Expression::dice(1, 0)), Err(Error::ZeroFaces));
+        assert_eq!(roller.roll(&Expression::dice(0, 0)), Err(Error::ZeroFaces));
+    }
+}
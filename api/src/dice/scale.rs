@@ -0,0 +1,292 @@
+use super::expression::{BinaryOp, Expression, Formula};
+use super::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// The arguments of a `{@scaledice}` or `{@scaledamage}` tag, e.g. `{@scaledamage 8d6|3-9|1d6}`:
+/// a spell dealing `8d6` at 3rd level and `1d6` more for each slot level above.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaledDice {
+    pub base: Formula,
+    /// The levels the spell can be cast at, in increasing order. The first is the base level.
+    pub levels: Vec<u32>,
+    /// Added once for each of the levels above the base level, so that for levels `2,4,6,8` the
+    /// increment is added once at 4th level.
+    pub increment: Expression,
+    /// The text displayed in place of the tag, if any.
+    pub display: Option<String>,
+}
+
+impl ScaledDice {
+    /// Parses the arguments of a `{@scaledice}` or `{@scaledamage}` tag.
+    pub fn from_tag(args: &[&str]) -> Result<Self> {
+        match args {
+            [base, levels, increment, rest @ ..] => Ok(Self {
+                base: base.trim().parse()?,
+                levels: parse_levels(levels)?,
+                increment: increment.trim().parse()?,
+                display: rest
+                    .first()
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_owned),
+            }),
+            _ => Err(Error::InvalidScaling(args.join("|"))),
+        }
+    }
+
+    /// The lowest level, or `None` if there are no levels.
+    pub fn base_level(&self) -> Option<u32> {
+        self.levels.first().copied()
+    }
+
+    /// The formula rolled at a given slot or character level.
+    pub fn at_level(&self, level: u32) -> Result<Formula> {
+        // Each level is one step above the one before it, which for evenly spaced levels such
+        // as `3-9` or `2,4,6,8` is the offset from the base level divided by the spacing
+        let steps = self
+            .levels
+            .iter()
+            .position(|&l| l == level)
+            .ok_or_else(|| Error::LevelOutOfRange {
+                level,
+                levels: format_levels(&self.levels),
+            })? as i64;
+
+        Ok(Formula {
+            parts: self
+                .base
+                .parts
+                .iter()
+                .map(|part| scale(part.clone(), &self.increment, steps))
+                .collect(),
+        })
+    }
+
+    /// The formula at every level, in increasing order.
+    pub fn table(&self) -> Vec<(u32, Formula)> {
+        self.levels
+            .iter()
+            .map(|&level| {
+                let formula = self.at_level(level).expect("level is in range");
+                (level, formula)
+            })
+            .collect()
+    }
+}
+
+impl FromStr for ScaledDice {
+    type Err = Error;
+
+    /// Parses the tag's arguments, e.g. `8d6|3-9|1d6`.
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_tag(&s.split('|').collect::<Vec<_>>())
+    }
+}
+
+impl fmt::Display for ScaledDice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}",
+            self.base,
+            format_levels(&self.levels),
+            self.increment
+        )?;
+        if let Some(display) = &self.display {
+            write!(f, "|{}", display)?;
+        }
+        Ok(())
+    }
+}
+
+/// Multiplies every group of dice by the cantrip damage tier of a character level,
+/// e.g. `1d10` becomes `2d10` at 5th level, `3d10` at 11th and `4d10` at 17th.
+pub fn scale_cantrip(base: &Expression, character_level: u32) -> Expression {
+    let tier = 1 + [5, 11, 17]
+        .iter()
+        .filter(|&&level| character_level >= level)
+        .count() as i64;
+
+    base.multiply_dice(tier)
+}
+
+impl Expression {
+    /// Multiplies the number of dice in every group, e.g. doubling them for a critical hit.
+    pub fn multiply_dice(&self, factor: i64) -> Expression {
+        let mut multiplied = self.clone();
+        multiply_dice(&mut multiplied, factor);
+        multiplied
+    }
+}
+
+fn multiply_dice(expression: &mut Expression, factor: i64) {
+    match expression {
+        Expression::Number(_) | Expression::Prompt(_) => {}
+        Expression::Dice(dice) => {
+            let count = std::mem::replace(&mut *dice.count, Expression::Number(0));
+            *dice.count = match count {
+                Expression::Number(n) => Expression::Number(n * factor),
+                count => Expression::binary(BinaryOp::Mul, count, Expression::Number(factor)),
+            };
+        }
+        Expression::Neg(inner) => multiply_dice(inner, factor),
+        Expression::Binary { lhs, rhs, .. } => {
+            multiply_dice(lhs, factor);
+            multiply_dice(rhs, factor);
+        }
+    }
+}
+
+/// Adds the increment `steps` times, merging it into a matching group of dice where possible,
+/// so that `8d6` plus two `1d6` becomes `10d6` rather than `8d6 + 2d6`.
+fn scale(mut base: Expression, increment: &Expression, steps: i64) -> Expression {
+    if steps == 0 {
+        return base;
+    }
+
+    match increment {
+        Expression::Dice(dice) if dice.modifiers.is_empty() => {
+            if let Expression::Number(count) = *dice.count {
+                if add_dice(&mut base, dice.faces, count * steps) {
+                    return base;
+                }
+                return Expression::binary(
+                    BinaryOp::Add,
+                    base,
+                    Expression::dice(count * steps, dice.faces),
+                );
+            }
+        }
+        Expression::Number(n) => return base.plus(n * steps),
+        _ => {}
+    }
+
+    let increment = Expression::binary(BinaryOp::Mul, Expression::Number(steps), increment.clone());
+    Expression::binary(BinaryOp::Add, base, increment)
+}
+
+/// Adds dice to the first plain group of dice with the same faces which is added to the total.
+fn add_dice(expression: &mut Expression, faces: u32, count: i64) -> bool {
+    match expression {
+        Expression::Dice(dice) if dice.faces == faces && dice.modifiers.is_empty() => {
+            match &mut *dice.count {
+                Expression::Number(n) => {
+                    *n += count;
+                    true
+                }
+                _ => false,
+            }
+        }
+        Expression::Binary {
+            op: BinaryOp::Add,
+            lhs,
+            rhs,
+        } => add_dice(lhs, faces, count) || add_dice(rhs, faces, count),
+        Expression::Binary {
+            op: BinaryOp::Sub,
+            lhs,
+            ..
+        } => add_dice(lhs, faces, count),
+        _ => false,
+    }
+}
+
+/// Parses levels such as `3-9` or `1,3,5-9`.
+fn parse_levels(s: &str) -> Result<Vec<u32>> {
+    let invalid = || Error::InvalidLevels(s.to_owned());
+    let mut levels = Vec::new();
+
+    for part in s.split(',').map(str::trim) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (part, part),
+        };
+        let start = start.parse::<u32>().map_err(|_| invalid())?;
+        let end = end.parse::<u32>().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+        levels.extend(start..=end);
+    }
+
+    levels.sort_unstable();
+    levels.dedup();
+    Ok(levels)
+}
+
+fn format_levels(levels: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &level in levels {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == level => *end = level,
+            _ => ranges.push((level, level)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_dice() {
+        let fireball = "8d6|3-9|1d6".parse::<ScaledDice>().unwrap();
+        assert_eq!(fireball.base_level(), Some(3));
+        assert_eq!(fireball.at_level(3).unwrap().to_string(), "8d6");
+        assert_eq!(fireball.at_level(5).unwrap().to_string(), "10d6");
+        assert_eq!(
+            fireball.at_level(2),
+            Err(Error::LevelOutOfRange {
+                level: 2,
+                levels: "3-9".to_owned()
+            })
+        );
+        assert_eq!(fireball.table().len(), 7);
+        assert_eq!(fireball.to_string(), "8d6|3-9|1d6");
+
+        let spiritual_weapon = "1d8|2,4,6,8|1d8".parse::<ScaledDice>().unwrap();
+        assert_eq!(spiritual_weapon.levels, vec![2, 4, 6, 8]);
+        assert_eq!(spiritual_weapon.at_level(4).unwrap().to_string(), "2d8");
+        assert_eq!(spiritual_weapon.at_level(8).unwrap().to_string(), "4d8");
+        assert!(spiritual_weapon.at_level(5).is_err());
+
+        let scaled = ScaledDice::from_tag(&["1d8 + 4", "1,3,5-6", "2d4", "extra"]).unwrap();
+        assert_eq!(scaled.levels, vec![1, 3, 5, 6]);
+        assert_eq!(scaled.at_level(3).unwrap().to_string(), "1d8 + 4 + 2d4");
+        assert_eq!(scaled.display.as_deref(), Some("extra"));
+
+        let empty = ScaledDice {
+            levels: Vec::new(),
+            ..scaled
+        };
+        assert_eq!(empty.base_level(), None);
+        assert!(empty.at_level(1).is_err());
+        assert!(empty.table().is_empty());
+
+        assert_eq!(
+            "1d6|9-3|1d6".parse::<ScaledDice>(),
+            Err(Error::InvalidLevels("9-3".to_owned()))
+        );
+    }
+
+    #[test]
+    fn scale_cantrips() {
+        let fire_bolt = "1d10".parse::<Expression>().unwrap();
+        assert_eq!(scale_cantrip(&fire_bolt, 4).to_string(), "1d10");
+        assert_eq!(scale_cantrip(&fire_bolt, 5).to_string(), "2d10");
+        assert_eq!(scale_cantrip(&fire_bolt, 20).to_string(), "4d10");
+    }
+}
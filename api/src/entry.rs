@@ -10,9 +10,16 @@ pub use util::*;
 
 use kinds::*;
 
+use crate::serde_utils::unknown_entry;
+use crate::validate::{self, Diagnostic, Lenient, Mode, Severity};
+use serde::de::{self, value::MapDeserializer};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::skip_serializing_none;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -25,6 +32,9 @@ pub struct EntryBaseData<'a> {
     /// Technically the API allows for negative page numbers. This should be handled during rendering.
     pub page: Option<i64>,
     pub id: Option<&'a str>,
+    /// Properties not known to this crate, kept so that they survive a round trip.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
 }
 
 pub trait EntryBase<'a> {
@@ -45,9 +55,72 @@ pub enum Entry<'a> {
 }
 
 impl<'a> Entry<'a> {
+    /// Entries of a type unknown to this crate are rejected; see [`Entry::from_json_lenient`].
+    /// [`validate_entry`](crate::validate::validate_entry) reports why an entry is invalid.
     pub fn from_json(s: &'a str) -> Result<Self> {
-        serde_json::from_str(s)
-            .map_err(Error::from)
+        serde_json::from_str(s).map_err(Error::from)
+    }
+
+    /// Like [`Entry::from_json`], but entries which are not understood are kept as
+    /// [`EntryKind::Unknown`] and reported as warnings instead. If the entry is still invalid,
+    /// the error lists every problem found by the validator.
+    pub fn from_json_lenient(s: &'a str) -> Result<Lenient<Self>> {
+        let value = serde_json::from_str(s)?;
+        let diagnostics = validate::validate_entry_with(&value, Mode::Lenient);
+
+        match unknown_entry::lenient(|| serde_json::from_str(s)) {
+            Ok(entry) => {
+                let warnings = diagnostics
+                    .into_iter()
+                    .map(|d| Diagnostic {
+                        severity: Severity::Warning,
+                        ..d
+                    })
+                    .collect();
+
+                Ok(Lenient {
+                    value: entry,
+                    warnings,
+                })
+            }
+            Err(e) => {
+                let errors = diagnostics
+                    .into_iter()
+                    .filter(Diagnostic::is_error)
+                    .collect::<Vec<_>>();
+
+                if errors.is_empty() {
+                    Err(Error::from(e))
+                } else {
+                    Err(Error::Invalid(errors))
+                }
+            }
+        }
+    }
+
+    /// Every string in the entry and its children, in order, including property values such as
+    /// names. Useful for finding tags without walking every kind of entry.
+    pub fn strings(&self) -> Vec<String> {
+        fn collect(value: &Value, out: &mut Vec<String>) {
+            match value {
+                Value::String(s) => out.push(s.clone()),
+                Value::Array(items) => items.iter().for_each(|v| collect(v, out)),
+                Value::Object(map) => map.values().for_each(|v| collect(v, out)),
+                _ => {}
+            }
+        }
+
+        match self {
+            Entry::String(s) => vec![(*s).to_owned()],
+            Entry::Integer(_) => Vec::new(),
+            entry => {
+                let mut strings = Vec::new();
+                if let Ok(value) = serde_json::to_value(entry) {
+                    collect(&value, &mut strings);
+                }
+                strings
+            }
+        }
     }
 }
 
@@ -109,6 +182,51 @@ pub enum EntryKind<'a> {
     RefOptionalFeature(refs::EntryRefOptionalFeature<'a>),
     Hr(hr::EntryHr),
     Spellcasting(spellcasting::EntrySpellcasting<'a>),
+    /// An entry whose `type` is not known to this crate. These are only accepted by
+    /// [`Entry::from_json_lenient`]; entries of a known type must always match it.
+    #[serde(untagged, deserialize_with = "unknown_entry::deserialize")]
+    Unknown(Value),
+}
+
+impl EntryKind<'_> {
+    /// The `type` of every kind of entry known to this crate, as serde reads them.
+    pub fn types() -> &'static [&'static str] {
+        static TYPES: OnceLock<&'static [&'static str]> = OnceLock::new();
+
+        TYPES.get_or_init(|| {
+            // Reading an unknown tag reports every known one, though serde then falls back to
+            // the untagged variant and returns its error instead
+            let probe = MapDeserializer::<_, TypesProbe>::new(std::iter::once(("type", "")));
+            let _ = unknown_entry::strict(|| EntryKind::deserialize(probe));
+            PROBED_TYPES.with(Cell::take).unwrap_or_default()
+        })
+    }
+}
+
+thread_local! {
+    static PROBED_TYPES: Cell<Option<&'static [&'static str]>> = const { Cell::new(None) };
+}
+
+#[derive(Debug)]
+struct TypesProbe;
+
+impl fmt::Display for TypesProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("entry type probe")
+    }
+}
+
+impl std::error::Error for TypesProbe {}
+
+impl de::Error for TypesProbe {
+    fn custom<T: fmt::Display>(_: T) -> Self {
+        Self
+    }
+
+    fn unknown_variant(_: &str, expected: &'static [&'static str]) -> Self {
+        PROBED_TYPES.with(|types| types.set(Some(expected)));
+        Self
+    }
 }
 
 impl<'a, T> From<T> for Entry<'a>
@@ -132,3 +250,92 @@ impl<'a> From<&'a str> for Entry<'a> {
         Self::String(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUTURE_ENTRY: &str = r#"{"type":"entries","entries":["Known",{"type":"hologram","frames":3},{"type":"list","items":["a"],"glow":true}],"sparkle":"blue"}"#;
+
+    #[test]
+    fn entry_strict_rejects_unknown_type() {
+        assert!(matches!(
+            Entry::from_json(FUTURE_ENTRY),
+            Err(Error::SerdeError(_))
+        ));
+
+        let value = serde_json::from_str(FUTURE_ENTRY).unwrap();
+        let errors = validate::validate_entry(&value)
+            .into_iter()
+            .filter(Diagnostic::is_error)
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "entries[1]: unknown entry type \"hologram\""
+        );
+    }
+
+    #[test]
+    fn entry_unknown_only_for_unknown_types() {
+        let bad_list = r#"{"type":"list","items":true}"#;
+        assert!(serde_json::from_str::<Entry>(bad_list).is_err());
+        assert!(Entry::from_json_lenient(bad_list).is_err());
+
+        let hologram = r#"{"type":"hologram","frames":3}"#;
+        assert!(serde_json::from_str::<Entry>(hologram).is_err());
+        assert!(matches!(
+            Entry::from_json_lenient(hologram).unwrap().value,
+            Entry::Entry(EntryKind::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn entry_extra_escaped_keys() {
+        let json = r#"{"type":"entries","entries":[],"spark\"le":1}"#;
+        let entry = serde_json::from_str::<Entry>(json).unwrap();
+        match entry {
+            Entry::Entry(EntryKind::Entries(entries)) => {
+                assert_eq!(entries.base.extra["spark\"le"], Value::from(1))
+            }
+            other => panic!("expected entries, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn entry_lenient_round_trip() {
+        let lenient = Entry::from_json_lenient(FUTURE_ENTRY).unwrap();
+
+        let warnings = lenient
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![
+                "entries[1]: unknown entry type \"hologram\"",
+                "entries[2].glow: unknown property `glow` of list",
+                "sparkle: unknown property `sparkle` of entries",
+            ]
+        );
+        assert!(lenient.warnings.iter().all(|w| !w.is_error()));
+
+        match &lenient.value {
+            Entry::Entry(EntryKind::Entries(entries)) => {
+                assert_eq!(entries.base.extra["sparkle"], Value::from("blue"));
+                assert!(matches!(
+                    entries.entries[1],
+                    Entry::Entry(EntryKind::Unknown(_))
+                ));
+            }
+            other => panic!("expected entries, found {:?}", other),
+        }
+
+        let json = serde_json::to_string(&lenient.value).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            serde_json::from_str::<Value>(FUTURE_ENTRY).unwrap()
+        );
+    }
+}
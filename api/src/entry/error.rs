@@ -1,4 +1,5 @@
 use super::render::RenderError;
+use crate::validate::Diagnostic;
 use serde_json::Error as SerdeError;
 use thiserror::Error as ErrorDerive;
 
@@ -10,4 +11,6 @@ pub enum Error {
     RenderError(#[from] RenderError),
     #[error("{0}")]
     SerdeError(#[from] SerdeError),
+    #[error("invalid entry: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Invalid(Vec<Diagnostic>),
 }
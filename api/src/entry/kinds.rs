@@ -64,6 +64,7 @@ pub(crate) mod test_utils {
             data: None,
             page: None,
             id: None,
+            extra: Default::default(),
         }
     }
 }
@@ -22,9 +22,9 @@ impl<'a> From<EntryDice<'a>> for EntryKind<'a> {
 pub struct EntryDiceToRoll {
     pub number: u16,
     pub faces: u16,
-    /// (Warning: unused)
+    /// Added to the total of the dice.
     pub modifier: Option<i16>,
-    /// (Warning: unused)
+    /// Whether the modifier is left out when the dice are displayed. It is still added when rolling.
     pub hide_modifier: Option<bool>,
 }
 
@@ -3,12 +3,65 @@ use serde_json::Number;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    from = "IngredientRepr<'a>",
+    into = "IngredientRepr<'a>",
+    bound(deserialize = "'de: 'a")
+)]
 pub struct EntryIngredient<'a> {
-    #[serde(borrow, flatten)]
     pub base: EntryBaseData<'a>,
     pub entry: Box<Entry<'a>>,
+    /// Numeric properties such as `amount1`, referenced by `{=amount1}` in the entry.
+    pub amounts: HashMap<String, Number>,
+}
+
+/// The amounts of an ingredient are arbitrary properties, so they are split out of the unknown
+/// properties of the base data rather than being flattened alongside them.
+#[derive(Serialize, Deserialize)]
+struct IngredientRepr<'a> {
+    #[serde(borrow)]
+    entry: Box<Entry<'a>>,
     #[serde(flatten)]
-    pub amounts: HashMap<&'a str, Number>,
+    base: EntryBaseData<'a>,
+}
+
+impl<'a> From<IngredientRepr<'a>> for EntryIngredient<'a> {
+    fn from(repr: IngredientRepr<'a>) -> Self {
+        let IngredientRepr { mut base, entry } = repr;
+
+        let mut amounts = HashMap::new();
+        base.extra.retain(|key, value| match value {
+            Value::Number(n) => {
+                amounts.insert(key.clone(), n.clone());
+                false
+            }
+            _ => true,
+        });
+
+        Self {
+            base,
+            entry,
+            amounts,
+        }
+    }
+}
+
+impl<'a> From<EntryIngredient<'a>> for IngredientRepr<'a> {
+    fn from(ingredient: EntryIngredient<'a>) -> Self {
+        let EntryIngredient {
+            mut base,
+            entry,
+            amounts,
+        } = ingredient;
+
+        base.extra.extend(
+            amounts
+                .into_iter()
+                .map(|(key, amount)| (key, Value::Number(amount))),
+        );
+
+        Self { base, entry }
+    }
 }
 
 impl<'a> From<EntryIngredient<'a>> for EntryKind<'a> {
@@ -35,7 +88,7 @@ mod tests {
             entry: Box::new("{=amount1} pound thick-cut bacon".into()),
             amounts: {
                 let mut map = HashMap::new();
-                map.insert("amount1", Number::from(1u8));
+                map.insert("amount1".to_owned(), Number::from(1u8));
                 map
             },
         }
@@ -3,12 +3,13 @@ use super::*;
 #[skip_serializing_none]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EntryItem<'a> {
-    #[serde(flatten)]
-    pub base: EntryBaseData<'a>,
     pub style: Option<&'a str>,
     pub name: &'a str,
-    #[serde(flatten)]
+    #[serde(flatten, borrow)]
     pub kind: EntryItemKind<'a>,
+    // Must come after `kind`, or `entry`/`entries` would also be kept as unknown properties
+    #[serde(flatten)]
+    pub base: EntryBaseData<'a>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use super::*;
+use crate::data::{self, ClassFeatureUid, SubclassFeatureUid};
 
 /// For use in classes page content only.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -15,6 +16,10 @@ impl<'a> EntryRefClassFeature<'a> {
             class_feature: class_feature_ref,
         }
     }
+
+    pub fn uid(&self) -> data::Result<ClassFeatureUid> {
+        self.class_feature.parse()
+    }
 }
 
 /// For use in classes page content only.
@@ -30,6 +35,10 @@ impl<'a> EntryRefSubclassFeature<'a> {
             subclass_feature: subclass_feature_ref,
         }
     }
+
+    pub fn uid(&self) -> data::Result<SubclassFeatureUid> {
+        self.subclass_feature.parse()
+    }
 }
 
 /// For use in classes page content only.
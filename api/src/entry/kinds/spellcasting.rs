@@ -45,7 +45,7 @@ pub enum Spell<'a> {
 pub struct EntrySpellcastingFrequency<'a>(#[serde(borrow)] HashMap<&'a str, ArrayOfSpell<'a>>);
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
-pub struct SpellsByLevel<'a>(#[serde(borrow)] HashMap<u8, EntrySpellcastingLevels<'a>>);
+pub struct SpellsByLevel<'a>(#[serde(borrow)] pub HashMap<u8, EntrySpellcastingLevels<'a>>);
 
 impl<'a> Serialize for SpellsByLevel<'a> {
     fn serialize<S>(&self, s: S) -> std::result::Result<S::Ok, S::Error>
@@ -61,6 +61,7 @@ pub trait RenderEntry {
             RefOptionalFeature(value) => self.render_ref_optional_feature(value),
             Hr(value) => self.render_hr(value),
             Spellcasting(value) => self.render_spellcasting(value),
+            Unknown(value) => self.render_unknown(value),
         }
     }
 
@@ -248,6 +249,10 @@ pub trait RenderEntry {
         let _ = entry;
         Err(RenderError::not_implemented("render_spellcasting").into())
     }
+    fn render_unknown(&self, entry: Value) -> Result<String> {
+        let _ = entry;
+        Err(RenderError::not_implemented("render_unknown").into())
+    }
 }
 
 #[derive(ErrorDerive, Debug, PartialEq)]
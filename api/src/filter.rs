@@ -0,0 +1,14 @@
+//! Queries encoded by `{@filter}` tags, such as
+//! `{@filter 1st- and 2nd-level wizard evocations|spells|level=1;2|class=wizard|school=V}`.
+//!
+//! Each argument after the page names a filter of the page and the values it is set to.
+//! Values are separated by `;`, and those starting with `!` are excluded rather than included.
+//! Numeric filters also take ranges such as `[1;5]`, where either bound may be left out.
+
+mod error;
+mod page;
+mod query;
+
+pub use error::{Error, Result};
+pub use page::{FilterPage, Property, PropertyKind};
+pub use query::{Condition, FilterQuery};
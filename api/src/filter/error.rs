@@ -0,0 +1,17 @@
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("the filter does not name a page")]
+    MissingPage,
+    #[error("`{0}` is not a filterable page")]
+    UnknownPage(String),
+    #[error("the `{page}` page has no `{property}` filter")]
+    UnknownProperty { page: String, property: String },
+    #[error("`{0}` is not a filter of the form `name=values`")]
+    InvalidArgument(String),
+    #[error("`{property}`: `{value}` is not a valid range")]
+    InvalidRange { property: String, value: String },
+}
@@ -0,0 +1,358 @@
+use serde_json::Value;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PropertyKind {
+    /// Matched against values case-insensitively.
+    Values,
+    /// Matched against numbers, which may be fractions such as `1/4`, and ranges.
+    Number,
+    /// Matched against source identifiers, and the abbreviations, full names and groups of the
+    /// sources as known to a [SourceRegistry](crate::data::SourceRegistry).
+    Source,
+}
+
+/// A filter of a page, with the values a record has for it.
+#[derive(Debug, Copy, Clone)]
+pub struct Property {
+    pub name: &'static str,
+    pub kind: PropertyKind,
+    values: fn(&Value) -> Vec<String>,
+}
+
+/// A list page of the 5etools site, such as `spells`, and the filters `{@filter}` tags may set on it.
+#[derive(Debug, Copy, Clone)]
+pub struct FilterPage {
+    pub name: &'static str,
+    /// The categories of records listed on the page.
+    pub categories: &'static [&'static str],
+    pub properties: &'static [Property],
+}
+
+const fn property(name: &'static str, values: fn(&Value) -> Vec<String>) -> Property {
+    Property {
+        name,
+        kind: PropertyKind::Values,
+        values,
+    }
+}
+
+const fn number(name: &'static str, values: fn(&Value) -> Vec<String>) -> Property {
+    Property {
+        name,
+        kind: PropertyKind::Number,
+        values,
+    }
+}
+
+const SOURCE: Property = Property {
+    name: "source",
+    kind: PropertyKind::Source,
+    values: |r| strings_at(r, &["source"]),
+};
+
+const PAGES: [FilterPage; 8] = [
+    FilterPage {
+        name: "spells",
+        categories: &["spell"],
+        properties: &[
+            SOURCE,
+            number("level", |r| strings_at(r, &["level"])),
+            property("school", spell_school),
+            // Current data lists these in a generated lookup, added to spells by `DataStore::from_dir`
+            property("class", |r| {
+                let mut classes = strings_at(r, &["classes", "fromClassList", "name"]);
+                classes.extend(strings_at(r, &["classes", "fromClassListVariant", "name"]));
+                classes
+            }),
+            property("subclass", |r| {
+                strings_at(r, &["classes", "fromSubclass", "subclass", "name"])
+            }),
+            property("components", spell_components),
+            property("damage type", |r| strings_at(r, &["damageInflict"])),
+            property("saving throw", |r| strings_at(r, &["savingThrow"])),
+            property("conditions", |r| strings_at(r, &["conditionInflict"])),
+            property("miscellaneous", spell_miscellaneous),
+        ],
+    },
+    FilterPage {
+        name: "bestiary",
+        categories: &["monster"],
+        properties: &[
+            SOURCE,
+            number("challenge rating", |r| {
+                let mut cr = strings_at(r, &["cr"]);
+                cr.extend(strings_at(r, &["cr", "cr"]));
+                cr
+            }),
+            property("type", |r| {
+                let mut types = strings_at(r, &["type"]);
+                types.extend(strings_at(r, &["type", "type"]));
+                types.extend(strings_at(r, &["type", "type", "choose"]));
+                types
+            }),
+            property("size", |r| sizes(strings_at(r, &["size"]))),
+            property("environment", |r| strings_at(r, &["environment"])),
+            property("miscellaneous", monster_miscellaneous),
+        ],
+    },
+    FilterPage {
+        name: "items",
+        categories: &["item", "baseitem"],
+        properties: &[
+            SOURCE,
+            property("type", |r| without_source(strings_at(r, &["type"]))),
+            property("rarity", |r| strings_at(r, &["rarity"])),
+            property("property", |r| without_source(strings_at(r, &["property"]))),
+            property("damage type", |r| strings_at(r, &["dmgType"])),
+            property("miscellaneous", item_miscellaneous),
+        ],
+    },
+    FilterPage {
+        name: "feats",
+        categories: &["feat"],
+        properties: &[SOURCE, property("miscellaneous", srd)],
+    },
+    FilterPage {
+        name: "races",
+        categories: &["race"],
+        properties: &[
+            SOURCE,
+            property("size", |r| sizes(strings_at(r, &["size"]))),
+            property("miscellaneous", srd),
+        ],
+    },
+    FilterPage {
+        name: "backgrounds",
+        categories: &["background"],
+        properties: &[SOURCE, property("miscellaneous", srd)],
+    },
+    FilterPage {
+        name: "optionalfeatures",
+        categories: &["optionalfeature"],
+        properties: &[
+            SOURCE,
+            property("feature type", |r| strings_at(r, &["featureType"])),
+        ],
+    },
+    FilterPage {
+        name: "conditionsdiseases",
+        categories: &["condition", "disease", "status"],
+        properties: &[SOURCE],
+    },
+];
+
+const SCHOOLS: [(&str, &str); 9] = [
+    ("A", "abjuration"),
+    ("C", "conjuration"),
+    ("D", "divination"),
+    ("E", "enchantment"),
+    ("V", "evocation"),
+    ("I", "illusion"),
+    ("N", "necromancy"),
+    ("T", "transmutation"),
+    ("P", "psionic"),
+];
+
+const SIZES: [(&str, &str); 6] = [
+    ("T", "tiny"),
+    ("S", "small"),
+    ("M", "medium"),
+    ("L", "large"),
+    ("H", "huge"),
+    ("G", "gargantuan"),
+];
+
+impl FilterPage {
+    /// The page with the given name, e.g. `spells` or `bestiary`, ignoring case and a `.html` suffix.
+    pub fn of(name: &str) -> Option<&'static Self> {
+        let name = name.trim().to_lowercase();
+        let name = name.strip_suffix(".html").unwrap_or(&name);
+        PAGES.iter().find(|page| page.name == name)
+    }
+
+    /// The filter with the given name, ignoring case.
+    pub fn property(&self, name: &str) -> Option<&'static Property> {
+        let name = name.trim();
+        self.properties
+            .iter()
+            .find(|property| property.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Property {
+    /// The values a record has for this filter. Codes such as spell schools and sizes are given
+    /// both as written and as the name they stand for, so that either can be filtered on.
+    pub fn values(&self, record: &Value) -> Vec<String> {
+        (self.values)(record)
+    }
+}
+
+/// The strings found by following the keys through the record, looking into every element of
+/// the arrays on the way. Numbers are included as written.
+fn strings_at(record: &Value, keys: &[&str]) -> Vec<String> {
+    let mut values = vec![record];
+    for key in keys {
+        values = values
+            .into_iter()
+            .flat_map(|value| match value {
+                Value::Array(array) => array.iter().filter_map(|v| v.get(key)).collect(),
+                _ => value.get(key).into_iter().collect::<Vec<_>>(),
+            })
+            .collect();
+    }
+
+    values
+        .into_iter()
+        .flat_map(|value| match value {
+            Value::Array(array) => array.iter().collect(),
+            _ => vec![value],
+        })
+        .filter_map(|value| match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Adds the names of codes to the codes themselves.
+fn with_names(codes: Vec<String>, names: &[(&str, &str)]) -> Vec<String> {
+    let mut values = codes.clone();
+    for code in codes {
+        if let Some((_, name)) = names.iter().find(|(c, _)| c.eq_ignore_ascii_case(&code)) {
+            values.push((*name).to_owned());
+        }
+    }
+    values
+}
+
+fn sizes(codes: Vec<String>) -> Vec<String> {
+    with_names(codes, &SIZES)
+}
+
+/// Drops the source from values such as `LA|XPHB`.
+fn without_source(values: Vec<String>) -> Vec<String> {
+    values
+        .into_iter()
+        .map(|value| match value.split_once('|') {
+            Some((value, _)) => value.to_owned(),
+            None => value,
+        })
+        .collect()
+}
+
+fn flag(record: &Value, key: &str) -> bool {
+    record.get(key).is_some_and(|value| match value {
+        Value::Bool(b) => *b,
+        Value::Null => false,
+        _ => true,
+    })
+}
+
+fn srd(record: &Value) -> Vec<String> {
+    let mut values = Vec::new();
+    if flag(record, "srd") {
+        values.push("SRD".to_owned());
+    }
+    if flag(record, "basicRules") {
+        values.push("Basic Rules".to_owned());
+    }
+    values
+}
+
+fn spell_school(record: &Value) -> Vec<String> {
+    with_names(strings_at(record, &["school"]), &SCHOOLS)
+}
+
+fn spell_components(record: &Value) -> Vec<String> {
+    ["v", "s", "m", "r"]
+        .iter()
+        .filter(|key| record.get("components").is_some_and(|c| flag(c, key)))
+        .map(|key| key.to_uppercase())
+        .collect()
+}
+
+fn spell_miscellaneous(record: &Value) -> Vec<String> {
+    let mut values = srd(record);
+    if record.get("meta").is_some_and(|meta| flag(meta, "ritual")) {
+        values.push("Ritual".to_owned());
+    }
+    let durations = record.get("duration").and_then(Value::as_array);
+    if durations.is_some_and(|d| d.iter().any(|d| flag(d, "concentration"))) {
+        values.push("Concentration".to_owned());
+    }
+    if flag(record, "scalingLevelDice") || flag(record, "entriesHigherLevel") {
+        values.push("Scaling".to_owned());
+    }
+    values
+}
+
+fn monster_miscellaneous(record: &Value) -> Vec<String> {
+    let mut values = srd(record);
+    if flag(record, "legendary") {
+        values.push("Legendary".to_owned());
+    }
+    if flag(record, "mythic") {
+        values.push("Mythic".to_owned());
+    }
+    if flag(record, "spellcasting") {
+        values.push("Spellcaster".to_owned());
+    }
+    values
+}
+
+fn item_miscellaneous(record: &Value) -> Vec<String> {
+    let mut values = srd(record);
+    if flag(record, "reqAttune") {
+        values.push("Requires Attunement".to_owned());
+    }
+    if flag(record, "wondrous") {
+        values.push("Wondrous".to_owned());
+    }
+    if flag(record, "curse") {
+        values.push("Cursed".to_owned());
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn property_values() {
+        let spells = FilterPage::of("Spells.html").unwrap();
+        let fireball = json!({
+            "name": "Fireball",
+            "level": 3,
+            "school": "V",
+            "components": { "v": true, "s": true, "m": "a tiny ball of bat guano and sulfur" },
+            "classes": { "fromClassList": [{ "name": "Sorcerer" }, { "name": "Wizard" }] },
+            "duration": [{ "type": "instant" }]
+        });
+
+        let values = |name| spells.property(name).unwrap().values(&fireball);
+        assert_eq!(values("level"), ["3"]);
+        assert_eq!(values("School"), ["V", "evocation"]);
+        assert_eq!(values("class"), ["Sorcerer", "Wizard"]);
+        assert_eq!(values("components"), ["V", "S", "M"]);
+        assert!(values("miscellaneous").is_empty());
+        assert!(spells.property("rarity").is_none());
+
+        let bestiary = FilterPage::of("bestiary").unwrap();
+        let dragon = json!({
+            "cr": { "cr": "17", "lair": "18" },
+            "type": { "type": "dragon", "tags": [] },
+            "size": ["H"],
+            "legendary": []
+        });
+        let values = |name| bestiary.property(name).unwrap().values(&dragon);
+        assert_eq!(values("challenge rating"), ["17"]);
+        assert_eq!(values("type"), ["dragon"]);
+        assert_eq!(values("size"), ["H", "huge"]);
+        assert_eq!(values("miscellaneous"), ["Legendary"]);
+
+        assert!(FilterPage::of("nowhere").is_none());
+    }
+}
@@ -0,0 +1,331 @@
+use super::{Error, FilterPage, Property, PropertyKind, Result};
+use crate::creature::parse_cr;
+use crate::data::{DataStore, SourceRegistry, StoreRecord};
+use serde_json::Value;
+
+/// What a single filter of a [FilterQuery] is set to.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub property: &'static Property,
+    /// A record matches if it has any of these values. Empty lists allow every value.
+    pub include: Vec<String>,
+    /// A record does not match if it has any of these values.
+    pub exclude: Vec<String>,
+    /// A record matches if any of its values is within the bounds, which are inclusive.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// The query encoded by the arguments of a `{@filter}` tag.
+#[derive(Debug, Clone)]
+pub struct FilterQuery {
+    /// The text the tag displays.
+    pub display: String,
+    pub page: &'static FilterPage,
+    /// A record must match every condition.
+    pub conditions: Vec<Condition>,
+    /// The sources the `source` filter is matched against, the official ones if `None`.
+    pub registry: Option<SourceRegistry>,
+}
+
+impl FilterQuery {
+    /// Parses the arguments of a `{@filter}` tag: the displayed text, the page and its filters.
+    pub fn from_args(args: &[&str]) -> Result<Self> {
+        let page = args
+            .get(1)
+            .map(|page| page.trim())
+            .filter(|page| !page.is_empty())
+            .ok_or(Error::MissingPage)?;
+        let page = FilterPage::of(page).ok_or_else(|| Error::UnknownPage(page.to_owned()))?;
+
+        let conditions = args[2..]
+            .iter()
+            .filter(|arg| !arg.trim().is_empty())
+            .map(|arg| Condition::parse(page, arg))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            display: args[0].to_owned(),
+            page,
+            conditions,
+            registry: None,
+        })
+    }
+
+    /// Matches the `source` filter against the sources of the registry, e.g. one including
+    /// the sources of loaded homebrew, rather than the official sources.
+    pub fn registry(mut self, registry: SourceRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn matches(&self, record: &Value) -> bool {
+        let registry = self
+            .registry
+            .as_ref()
+            .unwrap_or_else(|| SourceRegistry::official_shared());
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(record, registry))
+    }
+
+    /// Every record of the store listed on the page and matching the query.
+    pub fn run<'s>(&self, store: &'s DataStore) -> Vec<StoreRecord<'s>> {
+        store
+            .categories()
+            .filter(|category| self.page.categories.contains(category))
+            .flat_map(|category| {
+                store.iter(category).map(move |(uid, value)| StoreRecord {
+                    category,
+                    uid,
+                    value,
+                })
+            })
+            .filter(|record| self.matches(record.value))
+            .collect()
+    }
+}
+
+impl Condition {
+    /// Parses a filter such as `level=1;2`, `school=!V` or `challenge rating=[1;5]`.
+    fn parse(page: &FilterPage, arg: &str) -> Result<Self> {
+        let (name, values) = arg
+            .split_once('=')
+            .ok_or_else(|| Error::InvalidArgument(arg.to_owned()))?;
+        let property = page.property(name).ok_or_else(|| Error::UnknownProperty {
+            page: page.name.to_owned(),
+            property: name.trim().to_owned(),
+        })?;
+
+        let mut condition = Self {
+            property,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            min: None,
+            max: None,
+        };
+
+        let values = values.trim();
+        if let Some(range) = values.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let invalid = || Error::InvalidRange {
+                property: property.name.to_owned(),
+                value: values.to_owned(),
+            };
+            let (min, max) = range.split_once(';').ok_or_else(invalid)?;
+            let bound = |bound: &str| {
+                let bound = bound.trim().trim_start_matches('&');
+                match bound {
+                    "" => Ok(None),
+                    _ => parse_cr(bound).map(Some).ok_or_else(invalid),
+                }
+            };
+            if property.kind != PropertyKind::Number {
+                return Err(invalid());
+            }
+            condition.min = bound(min)?;
+            condition.max = bound(max)?;
+            return Ok(condition);
+        }
+
+        for value in values.split(';').map(str::trim).filter(|v| !v.is_empty()) {
+            match value.strip_prefix('!') {
+                Some(value) => condition.exclude.push(value.trim().to_owned()),
+                None => condition.include.push(value.to_owned()),
+            }
+        }
+        Ok(condition)
+    }
+
+    /// Whether the record matches, looking the sources of `source` filters up in the registry.
+    pub fn matches(&self, record: &Value, registry: &SourceRegistry) -> bool {
+        let values = self.property.values(record);
+        let has = |expected: &String| {
+            values
+                .iter()
+                .any(|value| self.equals(value, expected, registry))
+        };
+
+        let in_range = || {
+            values
+                .iter()
+                .filter_map(|value| parse_cr(value))
+                .any(|n| self.min.is_none_or(|min| n >= min) && self.max.is_none_or(|max| n <= max))
+        };
+
+        (self.include.is_empty() || self.include.iter().any(has))
+            && !self.exclude.iter().any(has)
+            && ((self.min.is_none() && self.max.is_none()) || in_range())
+    }
+
+    fn equals(&self, value: &str, expected: &str, registry: &SourceRegistry) -> bool {
+        match self.property.kind {
+            PropertyKind::Number => match (parse_cr(value), parse_cr(expected)) {
+                (Some(value), Some(expected)) => value == expected,
+                _ => value.eq_ignore_ascii_case(expected.trim()),
+            },
+            PropertyKind::Values => value.eq_ignore_ascii_case(expected.trim()),
+            PropertyKind::Source => registry.matches(value, expected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Dataset, SourceGroup, SourceInfo, SpellSources};
+    use serde_json::json;
+
+    fn store() -> DataStore {
+        DataStore::from_dataset(Dataset::from_value(json!({
+            "spell": [
+                { "name": "Magic Missile", "source": "PHB", "level": 1, "school": "V",
+                  "classes": { "fromClassList": [{ "name": "Wizard", "source": "PHB" }, { "name": "Sorcerer", "source": "PHB" }] } },
+                { "name": "Shield", "source": "PHB", "level": 1, "school": "A",
+                  "classes": { "fromClassList": [{ "name": "Wizard", "source": "PHB" }] } },
+                { "name": "Scorching Ray", "source": "PHB", "level": 2, "school": "V",
+                  "classes": { "fromClassList": [{ "name": "Wizard", "source": "PHB" }] } },
+                { "name": "Fireball", "source": "PHB", "level": 3, "school": "V",
+                  "classes": { "fromClassList": [{ "name": "Wizard", "source": "PHB" }] } },
+                { "name": "Cure Wounds", "source": "PHB", "level": 1, "school": "V",
+                  "classes": { "fromClassList": [{ "name": "Cleric", "source": "PHB" }] } }
+            ],
+            "monster": [
+                { "name": "Goblin", "source": "MM", "cr": "1/4" },
+                { "name": "Ogre", "source": "MM", "cr": "2" },
+                { "name": "Adult Red Dragon", "source": "MM", "cr": { "cr": "17", "lair": "18" } }
+            ]
+        })))
+        .unwrap()
+    }
+
+    fn names(store: &DataStore, args: &[&str]) -> Vec<String> {
+        let mut names = FilterQuery::from_args(args)
+            .unwrap()
+            .run(store)
+            .into_iter()
+            .map(|record| record.value["name"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn run_filters() {
+        let store = store();
+
+        assert_eq!(
+            names(
+                &store,
+                &[
+                    "low evocations",
+                    "spells",
+                    "level=1;2",
+                    "class=wizard",
+                    "school=V"
+                ]
+            ),
+            ["Magic Missile", "Scorching Ray"]
+        );
+        assert_eq!(
+            names(&store, &["", "spells", "class=Wizard", "school=!evocation"]),
+            ["Shield"]
+        );
+        assert_eq!(
+            names(&store, &["", "spells", "level=[2;]"]),
+            ["Fireball", "Scorching Ray"]
+        );
+        assert_eq!(
+            names(&store, &["", "bestiary", "challenge rating=[&0;&2]"]),
+            ["Goblin", "Ogre"]
+        );
+        assert_eq!(
+            names(&store, &["", "bestiary", "challenge rating=0.25"]),
+            ["Goblin"]
+        );
+        assert_eq!(
+            names(
+                &store,
+                &["dragons", "bestiary", "source=mm", "challenge rating=[10;]"]
+            ),
+            ["Adult Red Dragon"]
+        );
+        assert_eq!(names(&store, &["all", "bestiary"]).len(), 3);
+    }
+
+    #[test]
+    fn class_from_spell_sources() {
+        let mut store = DataStore::from_dataset(Dataset::from_value(json!({
+            "spell": [
+                { "name": "Fireball", "source": "XPHB", "level": 3 },
+                { "name": "Cure Wounds", "source": "XPHB", "level": 1 }
+            ]
+        })))
+        .unwrap();
+        store.add_spell_sources(&SpellSources::from_value(json!({
+            "xphb": {
+                "fireball": { "class": { "XPHB": { "Sorcerer": true, "Wizard": true } } },
+                "cure wounds": { "class": { "XPHB": { "Cleric": true } } }
+            }
+        })));
+
+        assert_eq!(names(&store, &["", "spells", "class=wizard"]), ["Fireball"]);
+    }
+
+    #[test]
+    fn source_filters() {
+        let store = store();
+
+        assert_eq!(names(&store, &["", "bestiary", "source=core"]).len(), 3);
+        assert_eq!(
+            names(&store, &["", "bestiary", "source=Monster Manual (2014)"]).len(),
+            3
+        );
+        assert!(names(&store, &["", "bestiary", "source=!core"]).is_empty());
+
+        let brew = json!({ "name": "Gnoll Pup", "source": "MyBrew" });
+        let query = FilterQuery::from_args(&["", "bestiary", "source=homebrew"]).unwrap();
+        assert!(!query.matches(&brew));
+
+        let mut registry = SourceRegistry::official();
+        registry.insert(SourceInfo {
+            json: "MyBrew".into(),
+            abbreviation: "MB".into(),
+            full: "My Brew".into(),
+            group: SourceGroup::Homebrew,
+            published: None,
+            srd: false,
+            partnered: false,
+        });
+        assert!(query.registry(registry).matches(&brew));
+    }
+
+    #[test]
+    fn invalid_filters() {
+        let error = |args: &[&str]| FilterQuery::from_args(args).unwrap_err();
+
+        assert_eq!(error(&["text"]), Error::MissingPage);
+        assert_eq!(
+            error(&["text", "nowhere"]),
+            Error::UnknownPage("nowhere".into())
+        );
+        assert_eq!(
+            error(&["text", "spells", "rarity=rare"]),
+            Error::UnknownProperty {
+                page: "spells".into(),
+                property: "rarity".into()
+            }
+        );
+        assert_eq!(
+            error(&["text", "spells", "level"]),
+            Error::InvalidArgument("level".into())
+        );
+        assert!(matches!(
+            error(&["text", "spells", "school=[1;2]"]),
+            Error::InvalidRange { .. }
+        ));
+        assert!(matches!(
+            error(&["text", "spells", "level=[one;2]"]),
+            Error::InvalidRange { .. }
+        ));
+    }
+}
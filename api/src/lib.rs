@@ -1,6 +1,23 @@
+/// A regex which is compiled once, the first time it is used.
+macro_rules! regex {
+    ($pattern:expr) => {{
+        static REGEX: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+        REGEX.get_or_init(|| regex::Regex::new($pattern).expect("valid regex"))
+    }};
+}
+
+pub mod attack;
+pub mod creature;
+pub mod data;
+pub mod dice;
 pub mod entry;
+pub mod filter;
+pub mod mechanics;
+pub mod search;
 pub mod string;
+pub mod table;
 pub mod util;
+pub mod validate;
 
 mod serde_utils;
 
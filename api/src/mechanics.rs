@@ -0,0 +1,9 @@
+//! Game mechanics given by `{@recharge}` and `{@chance}` tags, which can be rolled and simulated.
+
+mod chance;
+mod error;
+mod recharge;
+
+pub use chance::{Chance, ChanceOutcome};
+pub use error::{Error, Result};
+pub use recharge::{Recharge, RechargeSimulation};
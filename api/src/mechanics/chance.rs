@@ -0,0 +1,124 @@
+use super::{Error, Result};
+use crate::dice::Roller;
+use rand::Rng;
+
+/// A percentage chance of something happening, as with `{@chance 25}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chance {
+    pub percent: u32,
+    /// Displayed in place of the percentage.
+    pub display: Option<String>,
+    /// The name of the roll.
+    pub name: Option<String>,
+    pub success: Option<String>,
+    pub failure: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChanceOutcome<'c> {
+    /// The d100 roll, which succeeds if no higher than the percentage.
+    pub roll: u32,
+    pub success: bool,
+    /// The success or failure text, if any.
+    pub text: Option<&'c str>,
+}
+
+impl Chance {
+    pub fn new(percent: u32) -> Result<Self> {
+        if percent > 100 {
+            return Err(Error::InvalidChance(percent.to_string()));
+        }
+
+        Ok(Self {
+            percent,
+            display: None,
+            name: None,
+            success: None,
+            failure: None,
+        })
+    }
+
+    /// The chance given by the arguments of a `{@chance}` tag: the percentage, then optionally
+    /// display text, the name of the roll, success text and failure text.
+    pub fn from_tag(args: &[&str]) -> Result<Self> {
+        let first = args.first().map(|s| s.trim()).unwrap_or_default();
+        let percent = first
+            .parse()
+            .map_err(|_| Error::InvalidChance(first.to_owned()))?;
+
+        let arg = |i: usize| {
+            args.get(i)
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(str::to_owned)
+        };
+
+        Ok(Self {
+            display: arg(1),
+            name: arg(2),
+            success: arg(3),
+            failure: arg(4),
+            ..Self::new(percent)?
+        })
+    }
+
+    pub fn probability(&self) -> f64 {
+        f64::from(self.percent) / 100.0
+    }
+
+    pub fn roll<R: Rng>(&self, roller: &mut Roller<R>) -> ChanceOutcome<'_> {
+        let roll = roller.rng().gen_range(1..=100);
+        let success = roll <= self.percent;
+        let text = if success {
+            self.success.as_deref()
+        } else {
+            self.failure.as_deref()
+        };
+
+        ChanceOutcome {
+            roll,
+            success,
+            text,
+        }
+    }
+
+    /// The expected number of successes over the given number of attempts.
+    pub fn expected_successes(&self, attempts: usize) -> f64 {
+        self.probability() * attempts as f64
+    }
+
+    /// Rolls the given number of attempts and counts the successes.
+    pub fn simulate<R: Rng>(&self, roller: &mut Roller<R>, attempts: usize) -> usize {
+        (0..attempts).filter(|_| self.roll(roller).success).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chance_rolls() {
+        let chance = Chance::from_tag(&["25", "", "Loot", "Found it", "Nothing"]).unwrap();
+        assert_eq!(chance.display, None);
+        assert_eq!(chance.name.as_deref(), Some("Loot"));
+        assert!((chance.expected_successes(8) - 2.0).abs() < 1e-9);
+
+        let mut roller = Roller::seeded(9);
+        for _ in 0..50 {
+            let outcome = chance.roll(&mut roller);
+            assert_eq!(outcome.success, outcome.roll <= 25);
+            let expected = if outcome.success {
+                "Found it"
+            } else {
+                "Nothing"
+            };
+            assert_eq!(outcome.text, Some(expected));
+        }
+
+        assert_eq!(
+            Chance::from_tag(&["150"]),
+            Err(Error::InvalidChance("150".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,11 @@
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("`{0}` is not a recharge roll between 1 and 6")]
+    InvalidRecharge(String),
+    #[error("`{0}` is not a percentage between 0 and 100")]
+    InvalidChance(String),
+}
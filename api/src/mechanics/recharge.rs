@@ -0,0 +1,131 @@
+use super::{Error, Result};
+use crate::dice::Roller;
+use rand::Rng;
+use std::fmt;
+
+/// An ability which recharges on a d6 roll of `threshold` or higher, rolled at the start of each
+/// of the creature's turns while the ability is expended, as with `{@recharge 5}`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Recharge {
+    pub threshold: u8,
+}
+
+/// Whether an ability was available on each round, when used whenever available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RechargeSimulation {
+    pub available: Vec<bool>,
+    pub uses: usize,
+}
+
+impl Default for Recharge {
+    fn default() -> Self {
+        Self { threshold: 6 }
+    }
+}
+
+impl Recharge {
+    pub fn new(threshold: u8) -> Result<Self> {
+        if (1..=6).contains(&threshold) {
+            Ok(Self { threshold })
+        } else {
+            Err(Error::InvalidRecharge(threshold.to_string()))
+        }
+    }
+
+    /// The recharge given by the arguments of a `{@recharge}` tag, which defaults to 6.
+    pub fn from_tag(args: &[&str]) -> Result<Self> {
+        match args.first().map(|s| s.trim()) {
+            None | Some("") => Ok(Self::default()),
+            Some(arg) => arg
+                .parse()
+                .map_err(|_| Error::InvalidRecharge(arg.to_owned()))
+                .and_then(Self::new),
+        }
+    }
+
+    /// The chance of recharging on a single roll.
+    pub fn chance(&self) -> f64 {
+        f64::from(7 - self.threshold) / 6.0
+    }
+
+    pub fn roll<R: Rng>(&self, roller: &mut Roller<R>) -> bool {
+        roller.rng().gen_range(1..=6) >= self.threshold
+    }
+
+    /// The chance of the ability being available on each round, when used whenever available.
+    /// The ability starts out available.
+    pub fn availability(&self, rounds: usize) -> Vec<f64> {
+        let mut available = 1.0;
+        (0..rounds)
+            .map(|round| {
+                if round > 0 {
+                    // Used last round if it was available, and otherwise still expended
+                    available = self.chance();
+                }
+                available
+            })
+            .collect()
+    }
+
+    /// The expected number of uses over the given number of rounds.
+    pub fn expected_uses(&self, rounds: usize) -> f64 {
+        self.availability(rounds).iter().sum()
+    }
+
+    /// Simulates the ability being used whenever available over the given number of rounds.
+    pub fn simulate<R: Rng>(&self, roller: &mut Roller<R>, rounds: usize) -> RechargeSimulation {
+        let mut ready = true;
+        let available = (0..rounds)
+            .map(|_| {
+                if !ready {
+                    ready = self.roll(roller);
+                }
+                let available = ready;
+                ready = false;
+                available
+            })
+            .collect::<Vec<_>>();
+        let uses = available.iter().filter(|&&a| a).count();
+
+        RechargeSimulation { available, uses }
+    }
+}
+
+impl fmt::Display for Recharge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.threshold == 6 {
+            write!(f, "(Recharge 6)")
+        } else {
+            write!(f, "(Recharge {}-6)", self.threshold)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recharge_uses() {
+        let breath = Recharge::from_tag(&["5"]).unwrap();
+        assert_eq!(breath.to_string(), "(Recharge 5-6)");
+        assert_eq!(Recharge::from_tag(&[]), Ok(Recharge::default()));
+        assert_eq!(
+            Recharge::from_tag(&["7"]),
+            Err(Error::InvalidRecharge("7".to_owned()))
+        );
+
+        assert!((breath.expected_uses(4) - 2.0).abs() < 1e-9);
+
+        let mut roller = Roller::seeded(2);
+        let trials = 2000;
+        let total = (0..trials)
+            .map(|_| breath.simulate(&mut roller, 4).uses)
+            .sum::<usize>();
+        assert!((total as f64 / trials as f64 - 2.0).abs() < 0.1);
+
+        let simulation = breath.simulate(&mut roller, 10);
+        assert!(simulation.available[0]);
+        assert_eq!(simulation.available.len(), 10);
+    }
+}
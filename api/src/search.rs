@@ -0,0 +1,42 @@
+//! Full-text search over the names and entries of the records in a
+//! [DataStore](crate::data::DataStore).
+//!
+//! Entries are indexed as the plain text rendered by
+//! [DefaultStringRenderer](crate::string::DefaultStringRenderer), one document per string, so
+//! that every hit points to the exact entry it was found in.
+
+mod error;
+mod index;
+mod query;
+
+pub use error::{Error, Result};
+pub use index::{Document, SearchHit, SearchIndex, SearchResults};
+pub use query::{Clause, Query};
+
+pub(crate) use index::VERSION;
+
+/// Splits text into lowercased words. Apostrophes are dropped rather than splitting words,
+/// so that `Xanathar's` is found by `xanathars`.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '’')
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_words() {
+        assert_eq!(
+            tokenize("Xanathar's Guide: {@b difficult-terrain}!").collect::<Vec<_>>(),
+            ["xanathars", "guide", "b", "difficult", "terrain"]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use serde_json::Error as SerdeError;
+use std::io::Error as IoError;
+use std::path::PathBuf;
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug)]
+pub enum Error {
+    #[error("{}: {source}", .path.display())]
+    Io { path: PathBuf, source: IoError },
+    #[error("{}: {source}", .path.display())]
+    Json { path: PathBuf, source: SerdeError },
+    #[error("{}: the index has version {found}, expected {expected}", .path.display())]
+    Version {
+        path: PathBuf,
+        found: u32,
+        expected: u32,
+    },
+}
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: IoError) -> Self {
+        Self::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn json(path: impl Into<PathBuf>, source: SerdeError) -> Self {
+        Self::Json {
+            path: path.into(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,591 @@
+use super::{tokenize, Clause, Error, Query, Result};
+use crate::data::{DataStore, Uid};
+use crate::string::{DefaultStringRenderer, RenderString};
+use crate::validate::{Path, PathSegment};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// Bumped whenever the persisted format changes, so that stale index files are rejected.
+pub(crate) const VERSION: u32 = 1;
+
+/// Keys holding entries. Every string below them is indexed.
+const ENTRY_KEYS: [&str; 16] = [
+    "entries",
+    "entriesHigherLevel",
+    "headerEntries",
+    "footerEntries",
+    "entry",
+    "items",
+    "rows",
+    "trait",
+    "action",
+    "bonus",
+    "reaction",
+    "legendary",
+    "legendaryHeader",
+    "mythic",
+    "mythicHeader",
+    "spellcasting",
+];
+
+/// Keys below entries which do not hold text.
+const SKIP_KEYS: [&str; 10] = [
+    "type",
+    "source",
+    "page",
+    "style",
+    "id",
+    "href",
+    "ability",
+    "displayAs",
+    "colStyles",
+    "hidden",
+];
+
+/// BM25 parameters.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Names count for more than the entries mentioning them.
+const NAME_BOOST: f64 = 2.0;
+
+/// A single indexed string: the name of a record, or one string of its entries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub category: String,
+    pub uid: Uid,
+    pub name: String,
+    pub source: String,
+    /// Where the string is within the record. The name of the record has the root path.
+    pub path: Path,
+    /// The text as rendered by [DefaultStringRenderer].
+    pub text: String,
+    len: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Posting {
+    doc: u32,
+    positions: Vec<u32>,
+}
+
+/// An inverted index of the words in every document, which can be saved to and loaded from a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    documents: Vec<Document>,
+    /// Postings are ordered by document.
+    terms: BTreeMap<String, Vec<Posting>>,
+    total_len: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit<'i> {
+    pub document: &'i Document,
+    pub score: f64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SearchResults<'i> {
+    /// The best hits first, at most as many as the limit of the query.
+    pub hits: Vec<SearchHit<'i>>,
+    /// The number of hits before the limit was applied.
+    pub total: usize,
+    /// The number of hits in each category, before the facets of the query were applied.
+    pub categories: BTreeMap<String, usize>,
+    /// The number of hits in each source, before the facets of the query were applied.
+    pub sources: BTreeMap<String, usize>,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            documents: Vec::new(),
+            terms: BTreeMap::new(),
+            total_len: 0,
+        }
+    }
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indexes every record of the store.
+    pub fn build(store: &DataStore) -> Self {
+        let mut index = Self::new();
+        for category in store.categories() {
+            for (uid, record) in store.iter(category) {
+                index.add_record(category, uid, record);
+            }
+        }
+        index
+    }
+
+    /// Indexes the name and entries of a record. Records without a name are skipped.
+    pub fn add_record(&mut self, category: &str, uid: &Uid, record: &Value) {
+        let name = match record.get("name").and_then(Value::as_str) {
+            Some(name) => name,
+            None => return,
+        };
+        let source = record
+            .get("source")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        let mut texts = vec![(Path::default(), name.to_owned())];
+        collect_texts(record, &mut Vec::new(), false, &mut texts);
+
+        for (path, text) in texts {
+            let text = DefaultStringRenderer.render(&text).unwrap_or(text);
+            self.add_document(Document {
+                category: category.to_owned(),
+                uid: uid.clone(),
+                name: name.to_owned(),
+                source: source.to_owned(),
+                path,
+                text,
+                len: 0,
+            });
+        }
+    }
+
+    fn add_document(&mut self, mut document: Document) {
+        let doc = self.documents.len() as u32;
+        let mut positions = HashMap::<String, Vec<u32>>::new();
+        for (position, word) in tokenize(&document.text).enumerate() {
+            positions.entry(word).or_default().push(position as u32);
+        }
+        if positions.is_empty() {
+            return;
+        }
+
+        document.len = positions.values().map(|p| p.len() as u32).sum();
+        self.total_len += u64::from(document.len);
+        self.documents.push(document);
+        for (word, positions) in positions {
+            self.terms
+                .entry(word)
+                .or_default()
+                .push(Posting { doc, positions });
+        }
+    }
+
+    /// Removes the documents of every record for which `f` returns false.
+    pub fn retain_records<F: FnMut(&str, &Uid) -> bool>(&mut self, mut f: F) {
+        let removed = (0..self.documents.len() as u32)
+            .filter(|&doc| {
+                let document = &self.documents[doc as usize];
+                !f(&document.category, &document.uid)
+            })
+            .collect::<Vec<_>>();
+        if removed.is_empty() {
+            return;
+        }
+
+        // Documents after a removed one move down by the number of removed documents before them
+        self.terms.retain(|_, postings| {
+            postings.retain(|posting| removed.binary_search(&posting.doc).is_err());
+            for posting in postings.iter_mut() {
+                posting.doc -= removed.partition_point(|&doc| doc < posting.doc) as u32;
+            }
+            !postings.is_empty()
+        });
+        let mut doc = 0;
+        let mut removed_len = 0;
+        self.documents.retain(|document| {
+            let keep = removed.binary_search(&doc).is_err();
+            if !keep {
+                removed_len += u64::from(document.len);
+            }
+            doc += 1;
+            keep
+        });
+        self.total_len -= removed_len;
+    }
+
+    pub fn documents(&self) -> &[Document] {
+        &self.documents
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = serde_json::to_vec(self).map_err(|e| Error::json(path, e))?;
+        fs::write(path, bytes).map_err(|e| Error::io(path, e))
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| Error::io(path, e))?;
+        let index: Self = serde_json::from_slice(&bytes).map_err(|e| Error::json(path, e))?;
+
+        if index.version != VERSION {
+            return Err(Error::Version {
+                path: path.to_owned(),
+                found: index.version,
+                expected: VERSION,
+            });
+        }
+        Ok(index)
+    }
+
+    /// Finds the documents matching every clause of the query, ranked by BM25 relevance.
+    pub fn search(&self, query: &Query) -> SearchResults<'_> {
+        let mut scores: Option<HashMap<u32, f64>> = None;
+        for clause in &query.clauses {
+            let clause_scores = self.clause_scores(clause);
+            scores = Some(match scores {
+                None => clause_scores,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(doc, score)| Some((doc, score + clause_scores.get(&doc)?)))
+                    .collect(),
+            });
+        }
+
+        let mut results = SearchResults::default();
+        let mut hits = Vec::new();
+        for (doc, score) in scores.unwrap_or_default() {
+            let document = &self.documents[doc as usize];
+            *results
+                .categories
+                .entry(document.category.clone())
+                .or_default() += 1;
+            *results.sources.entry(document.source.clone()).or_default() += 1;
+
+            if query.allows(&document.category, &document.source) {
+                let boost = if document.path.is_root() {
+                    NAME_BOOST
+                } else {
+                    1.0
+                };
+                hits.push((doc, score * boost));
+            }
+        }
+
+        hits.sort_by(|(a_doc, a), (b_doc, b)| b.total_cmp(a).then(a_doc.cmp(b_doc)));
+        results.total = hits.len();
+        hits.truncate(query.limit.unwrap_or(usize::MAX));
+        results.hits = hits
+            .into_iter()
+            .map(|(doc, score)| SearchHit {
+                document: &self.documents[doc as usize],
+                score,
+            })
+            .collect();
+        results
+    }
+
+    /// The score of every document matching the clause. A document matching several words of a
+    /// prefix or fuzzy clause is scored by its best match.
+    fn clause_scores(&self, clause: &Clause) -> HashMap<u32, f64> {
+        let mut scores = HashMap::new();
+        let mut add_term = |postings: &[Posting], weight: f64| {
+            let idf = self.idf(postings.len());
+            for posting in postings {
+                let score = weight * idf * self.bm25(posting.positions.len(), posting.doc);
+                let best = scores.entry(posting.doc).or_insert(0.0);
+                *best = f64::max(*best, score);
+            }
+        };
+
+        match clause {
+            Clause::Term(term) => {
+                if let Some(postings) = self.terms.get(term) {
+                    add_term(postings, 1.0);
+                }
+            }
+            Clause::Prefix(prefix) => {
+                let matches = self
+                    .terms
+                    .range(prefix.clone()..)
+                    .take_while(|(term, _)| term.starts_with(prefix.as_str()));
+                for (term, postings) in matches {
+                    add_term(postings, if term == prefix { 1.0 } else { 0.8 });
+                }
+            }
+            Clause::Fuzzy { term, distance } => {
+                for (candidate, postings) in &self.terms {
+                    if let Some(edits) = edit_distance(term, candidate, *distance) {
+                        add_term(postings, 1.0 / (1.0 + f64::from(edits)));
+                    }
+                }
+            }
+            Clause::Phrase(words) => return self.phrase_scores(words),
+        }
+        scores
+    }
+
+    fn phrase_scores(&self, words: &[String]) -> HashMap<u32, f64> {
+        let postings = match words
+            .iter()
+            .map(|word| self.terms.get(word))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(postings) if !postings.is_empty() => postings,
+            _ => return HashMap::new(),
+        };
+        let idf = postings.iter().map(|p| self.idf(p.len())).sum::<f64>();
+
+        let mut scores = HashMap::new();
+        for first in postings[0] {
+            let rest = postings[1..]
+                .iter()
+                .map(|postings| {
+                    let i = postings.binary_search_by_key(&first.doc, |p| p.doc).ok()?;
+                    Some(&postings[i].positions)
+                })
+                .collect::<Option<Vec<_>>>();
+            let rest = match rest {
+                Some(rest) => rest,
+                None => continue,
+            };
+
+            let occurrences = first
+                .positions
+                .iter()
+                .filter(|&&start| {
+                    rest.iter().enumerate().all(|(i, positions)| {
+                        positions.binary_search(&(start + i as u32 + 1)).is_ok()
+                    })
+                })
+                .count();
+            if occurrences > 0 {
+                scores.insert(first.doc, idf * self.bm25(occurrences, first.doc));
+            }
+        }
+        scores
+    }
+
+    fn idf(&self, document_frequency: usize) -> f64 {
+        let n = self.documents.len() as f64;
+        let df = document_frequency as f64;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    fn bm25(&self, term_frequency: usize, doc: u32) -> f64 {
+        let tf = term_frequency as f64;
+        let len = f64::from(self.documents[doc as usize].len);
+        let average_len = self.total_len as f64 / self.documents.len() as f64;
+        tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / average_len))
+    }
+}
+
+fn collect_texts(
+    value: &Value,
+    path: &mut Vec<PathSegment>,
+    in_entries: bool,
+    texts: &mut Vec<(Path, String)>,
+) {
+    match value {
+        Value::String(text) if in_entries => texts.push((Path::new(path.clone()), text.clone())),
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                collect_texts(value, path, in_entries, texts);
+                path.pop();
+            }
+        }
+        Value::Object(object) => {
+            for (key, value) in object {
+                if in_entries && SKIP_KEYS.contains(&key.as_str()) {
+                    continue;
+                }
+                path.push(PathSegment::Key(key.clone()));
+                let in_entries = in_entries || ENTRY_KEYS.contains(&key.as_str());
+                collect_texts(value, path, in_entries, texts);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The Levenshtein distance between two words, if it is at most `max`.
+fn edit_distance(a: &str, b: &str, max: u8) -> Option<u8> {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let max = usize::from(max);
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().all(|&distance| distance > max) {
+            return None;
+        }
+        previous = current;
+    }
+
+    let distance = previous[b.len()];
+    (distance <= max).then_some(distance as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Dataset;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn index() -> SearchIndex {
+        let store = DataStore::from_dataset(Dataset::from_value(json!({
+            "spell": [
+                {
+                    "name": "Spike Growth",
+                    "source": "PHB",
+                    "entries": [
+                        "The ground in a 20-foot radius sprouts hard spikes. The area becomes {@b difficult terrain} for the duration.",
+                        "The transformation is camouflaged."
+                    ]
+                },
+                {
+                    "name": "Grease",
+                    "source": "PHB",
+                    "entries": ["Slick grease covers the ground, turning it into difficult terrain."]
+                },
+                {
+                    "name": "Fireball",
+                    "source": "PHB",
+                    "entries": ["A bright streak flashes to a point you choose, then blossoms into a {@damage 8d6} explosion of flame."],
+                    "entriesHigherLevel": [{ "type": "entries", "name": "At Higher Levels", "entries": ["The damage increases by {@scaledamage 8d6|3-9|1d6}."] }]
+                }
+            ],
+            "item": [
+                {
+                    "name": "Boots of the Winterlands",
+                    "source": "XGE",
+                    "entries": ["You ignore difficult terrain created by ice or snow."]
+                }
+            ]
+        })))
+        .unwrap();
+        SearchIndex::build(&store)
+    }
+
+    fn names<'i>(results: &SearchResults<'i>) -> Vec<&'i str> {
+        results
+            .hits
+            .iter()
+            .map(|hit| hit.document.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn search_phrase_and_facets() {
+        let index = index();
+        let results = index.search(&Query::parse("\"difficult terrain\""));
+        assert_eq!(results.total, 3);
+        assert_eq!(results.categories["spell"], 2);
+        assert_eq!(results.sources["XGE"], 1);
+
+        let spike = results
+            .hits
+            .iter()
+            .find(|hit| hit.document.name == "Spike Growth")
+            .unwrap();
+        assert_eq!(spike.document.path.to_pointer(), "/entries/0");
+        assert!(spike.document.text.contains("becomes difficult terrain"));
+
+        let results = index.search(&Query::parse(
+            "\"difficult terrain\" category:spell source:phb",
+        ));
+        assert_eq!(names(&results).len(), 2);
+        assert!(!names(&results).contains(&"Boots of the Winterlands"));
+        assert_eq!(results.sources["XGE"], 1);
+
+        assert_eq!(
+            index.search(&Query::parse("\"terrain difficult\"")).total,
+            0
+        );
+        assert_eq!(index.search(&Query::parse("")).total, 0);
+    }
+
+    #[test]
+    fn search_prefix_fuzzy_and_ranking() {
+        let index = index();
+
+        let results = index.search(&Query::parse("fireball"));
+        assert_eq!(results.hits[0].document.path, Path::default());
+
+        let results = index.search(&Query::parse("winter*"));
+        assert_eq!(names(&results), ["Boots of the Winterlands"]);
+
+        let results = index.search(&Query::parse("transfomation~ camoflaged~"));
+        assert_eq!(names(&results), ["Spike Growth"]);
+        assert_eq!(results.hits[0].document.path.to_pointer(), "/entries/1");
+
+        let results = index.search(&Query::parse("damage increases").limit(1));
+        assert_eq!(results.total, 1);
+        assert_eq!(
+            results.hits[0].document.path.to_pointer(),
+            "/entriesHigherLevel/0/entries/0"
+        );
+    }
+
+    #[test]
+    fn remove_records() {
+        let mut index = index();
+        let total_len = index.total_len;
+        index.retain_records(|_, uid| *uid != Uid::new("Spike Growth", "PHB"));
+
+        let mut results = names(&index.search(&Query::parse("\"difficult terrain\"")));
+        results.sort_unstable();
+        assert_eq!(results, ["Boots of the Winterlands", "Grease"]);
+        assert_eq!(index.search(&Query::parse("transformation")).total, 0);
+        assert_eq!(
+            index.search(&Query::parse("fireball")).hits[0]
+                .document
+                .name,
+            "Fireball"
+        );
+        assert!(index.total_len < total_len);
+    }
+
+    #[test]
+    fn persist_index() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("search.json");
+        let index = index();
+        index.save(&path).unwrap();
+
+        let loaded = SearchIndex::load(&path).unwrap();
+        assert_eq!(loaded, index);
+
+        fs::write(
+            &path,
+            r#"{"version":0,"documents":[],"terms":{},"total_len":0}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            SearchIndex::load(&path),
+            Err(Error::Version { found: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("terrain", "terrain", 2), Some(0));
+        assert_eq!(edit_distance("terain", "terrain", 2), Some(1));
+        assert_eq!(edit_distance("flame", "blame", 0), None);
+        assert_eq!(edit_distance("ab", "abcd", 1), None);
+    }
+}
@@ -0,0 +1,180 @@
+use super::tokenize;
+use crate::data::SourceRegistry;
+
+/// One part of a [Query]. A document must match every clause of a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clause {
+    /// A word, e.g. `terrain`.
+    Term(String),
+    /// Any word starting with the given text, e.g. `terr*`.
+    Prefix(String),
+    /// Any word within the given number of edits, e.g. `terain~` or `terain~1`.
+    Fuzzy { term: String, distance: u8 },
+    /// Consecutive words, e.g. `"difficult terrain"`.
+    Phrase(Vec<String>),
+}
+
+/// A search, parsed from text such as `"difficult terrain" categ* category:spell source:xge`.
+///
+/// Words ending in `*` are prefixes and words ending in `~` are fuzzy. `category:` and `source:`
+/// restrict hits to the given categories or sources; repeating one allows any of the values.
+/// Sources may also be given by abbreviation, full name or group, e.g. `source:core`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+    pub categories: Vec<String>,
+    pub sources: Vec<String>,
+    pub limit: Option<usize>,
+    /// The sources `sources` are matched against, the official ones if `None`.
+    pub registry: Option<SourceRegistry>,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Self {
+        let mut query = Self::default();
+        let mut rest = input;
+
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            rest = &rest[start..];
+            if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                query.push_words(&quoted[..end]);
+                rest = quoted.get(end + 1..).unwrap_or_default();
+                continue;
+            }
+
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+
+            if let Some(category) = word.strip_prefix("category:") {
+                query = query.category(category);
+            } else if let Some(source) = word.strip_prefix("source:") {
+                query = query.source(source);
+            } else if let Some(prefix) = word.strip_suffix('*') {
+                let mut words = tokenize(prefix).collect::<Vec<_>>();
+                match words.pop() {
+                    Some(last) if words.is_empty() => query.clauses.push(Clause::Prefix(last)),
+                    // A prefix cannot be a phrase, so only its last word is a prefix
+                    Some(last) => {
+                        query.clauses.push(Clause::Phrase(words));
+                        query.clauses.push(Clause::Prefix(last));
+                    }
+                    None => {}
+                }
+            } else if let Some((term, distance)) = word.rsplit_once('~') {
+                let term = tokenize(term).collect::<String>();
+                let distance = distance.parse().unwrap_or_else(|_| default_distance(&term));
+                if !term.is_empty() {
+                    query.clauses.push(Clause::Fuzzy { term, distance });
+                }
+            } else {
+                query.push_words(word);
+            }
+        }
+
+        query
+    }
+
+    /// Only returns hits from this category, or any of the categories if called repeatedly.
+    pub fn category(mut self, category: &str) -> Self {
+        self.categories.push(category.to_lowercase());
+        self
+    }
+
+    /// Only returns hits from this source, or any of the sources if called repeatedly.
+    pub fn source(mut self, source: &str) -> Self {
+        self.sources.push(source.to_lowercase());
+        self
+    }
+
+    /// Matches the source facets against the sources of the registry, e.g. one including the
+    /// sources of loaded homebrew, rather than the official sources.
+    pub fn registry(mut self, registry: SourceRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    /// Whether hits in the category and source are kept by the facets of the query.
+    pub(super) fn allows(&self, category: &str, source: &str) -> bool {
+        let registry = self
+            .registry
+            .as_ref()
+            .unwrap_or_else(|| SourceRegistry::official_shared());
+        let allows = |values: &[String], matches: &dyn Fn(&str) -> bool| {
+            values.is_empty() || values.iter().any(|v| matches(v))
+        };
+
+        allows(&self.categories, &|v| v.eq_ignore_ascii_case(category))
+            && allows(&self.sources, &|v| registry.matches(source, v))
+    }
+
+    fn push_words(&mut self, text: &str) {
+        let mut words = tokenize(text).collect::<Vec<_>>();
+        match words.len() {
+            0 => {}
+            1 => self.clauses.push(Clause::Term(words.remove(0))),
+            _ => self.clauses.push(Clause::Phrase(words)),
+        }
+    }
+}
+
+/// One typo for short words, two for longer ones.
+fn default_distance(term: &str) -> u8 {
+    if term.chars().count() <= 5 {
+        1
+    } else {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query() {
+        let query = Query::parse(
+            r#""Difficult  Terrain" fire* concentraton~ ray~1 category:spell source:PHB cold-iron"#,
+        );
+        assert_eq!(
+            query.clauses,
+            [
+                Clause::Phrase(vec!["difficult".into(), "terrain".into()]),
+                Clause::Prefix("fire".into()),
+                Clause::Fuzzy {
+                    term: "concentraton".into(),
+                    distance: 2
+                },
+                Clause::Fuzzy {
+                    term: "ray".into(),
+                    distance: 1
+                },
+                Clause::Phrase(vec!["cold".into(), "iron".into()]),
+            ]
+        );
+        assert!(query.allows("spell", "phb"));
+        assert!(!query.allows("item", "phb"));
+        assert!(Query::parse("source:core").allows("spell", "XPHB"));
+        assert!(Query::parse("source:PHB'24").allows("spell", "XPHB"));
+        assert!(!Query::parse("source:adventure").allows("spell", "XPHB"));
+        assert!(Query::default()
+            .source("Xanathar's Guide to Everything")
+            .allows("spell", "XGE"));
+
+        assert!(Query::parse(" \"\" * ").is_empty());
+        assert_eq!(
+            Query::parse("\"unterminated phrase").clauses,
+            [Clause::Phrase(vec!["unterminated".into(), "phrase".into()])]
+        );
+    }
+}
@@ -128,3 +128,58 @@ pub mod bitflags_as_map {
         d.deserialize_map(ProficiencyMapVisitor(PhantomData::<P>))
     }
 }
+
+/// Deserializes [`EntryKind::Unknown`](crate::entry::EntryKind::Unknown), which only accepts
+/// objects whose `type` is not known to the crate, and only while deserializing leniently.
+/// Entries of a known type which do not match it are errors, as are unknown types otherwise.
+pub mod unknown_entry {
+    use crate::entry::EntryKind;
+    use serde::{de::Error, Deserialize, Deserializer};
+    use serde_json::Value;
+    use std::cell::Cell;
+
+    thread_local! {
+        static LENIENT: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Runs `f` with unknown entry types accepted, e.g. around a call to `serde_json::from_str`.
+    pub(crate) fn lenient<T, F: FnOnce() -> T>(f: F) -> T {
+        with_mode(true, f)
+    }
+
+    /// Runs `f` with unknown entry types rejected, as they are outside of [lenient].
+    pub(crate) fn strict<T, F: FnOnce() -> T>(f: F) -> T {
+        with_mode(false, f)
+    }
+
+    fn with_mode<T, F: FnOnce() -> T>(lenient: bool, f: F) -> T {
+        struct Reset(bool);
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                LENIENT.with(|lenient| lenient.set(self.0));
+            }
+        }
+
+        let _reset = Reset(LENIENT.with(|cell| cell.replace(lenient)));
+        f()
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(d)?;
+        let ty = match value.get("type") {
+            Some(Value::String(ty)) => ty,
+            _ => return Err(D::Error::custom("expected an entry object with a `type`")),
+        };
+
+        if !LENIENT.with(Cell::get) {
+            Err(D::Error::custom(format!("unknown entry type `{}`", ty)))
+        } else if EntryKind::types().contains(&ty.as_str()) {
+            Err(D::Error::custom(format!("invalid `{}` entry", ty)))
+        } else {
+            Ok(value)
+        }
+    }
+}
@@ -8,7 +8,7 @@ pub mod render_utils {
 }
 
 pub use error::*;
-pub use lexer::Lexeme;
+pub use lexer::{Lexeme, LexemeTag};
 pub use render::{DefaultStringRenderer, RenderString};
 pub use tags::{Tag, TagError, TagName};
 
@@ -23,6 +23,20 @@ pub fn tokenize(input: &str) -> Result<impl Iterator<Item = Lexeme>> {
         .into_iter())
 }
 
+/// Rebuilds the input, replacing every tag for which `f` returns new text.
+/// Other tags and text are kept as they are.
+pub fn rewrite_tags<F>(input: &str, mut f: F) -> Result<String>
+where
+    F: FnMut(&LexemeTag) -> Option<String>,
+{
+    Ok(tokenize(input)?
+        .map(|lexeme| match &lexeme {
+            Lexeme::Tag(tag) => f(tag).unwrap_or_else(|| lexeme.to_string()),
+            Lexeme::Text(text) => (*text).to_owned(),
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,6 +67,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rewrite_tag() {
+        let input = "{@atk mw} {@hit 4} to hit. {@h}5 ({@damage 1d6 + 2}) {@b {@i nested}|x}";
+        assert_eq!(rewrite_tags(input, |_| None).as_deref(), Ok(input));
+        assert_eq!(
+            rewrite_tags(input, |tag| match tag.name {
+                "hit" => Some("{@hit 6}".to_owned()),
+                _ => None,
+            })
+            .as_deref(),
+            Ok("{@atk mw} {@hit 6} to hit. {@h}5 ({@damage 1d6 + 2}) {@b {@i nested}|x}")
+        );
+    }
+
     #[test]
     fn render_long_entry() {
         check_def(
@@ -1,5 +1,6 @@
 use super::Result;
 use logos::{Logos, SpannedIter};
+use std::fmt;
 use std::iter::Peekable;
 use std::ops::Range;
 use thiserror::Error;
@@ -173,6 +174,26 @@ pub struct LexemeTag<'a> {
     pub args: Vec<&'a str>,
 }
 
+/// Writes the lexeme back out as it appeared in the source.
+impl fmt::Display for Lexeme<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lexeme::Tag(tag) => tag.fmt(f),
+            Lexeme::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+impl fmt::Display for LexemeTag<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{@{}", self.name)?;
+        if !self.args.is_empty() {
+            write!(f, " {}", self.args.join("|"))?;
+        }
+        write!(f, "}}")
+    }
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum LexError {
     #[error("tag beginning at index {0} does not have a name")]
@@ -580,6 +580,11 @@ mod tests {
             DefaultStringRenderer::render_attack_tag(vec!["ms,rs"]),
             Ok("Melee or Ranged Spell Attack"),
         );
+
+        check(
+            DefaultStringRenderer::render_attack_tag(vec!["mw,ms"]),
+            Ok("Weapon or Melee Spell Attack"),
+        );
     }
 
     #[test]
@@ -616,6 +621,11 @@ mod tests {
             DefaultStringRenderer::render_recharge_tag(vec![]),
             Ok("(Recharge 6)"),
         );
+
+        check(
+            DefaultStringRenderer::render_recharge_tag(vec!["0"]),
+            Ok("(Recharge 0-6)"),
+        );
     }
 
     #[test]
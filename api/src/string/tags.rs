@@ -4,6 +4,7 @@ use std::convert::TryFrom;
 use std::str::FromStr;
 use thiserror::Error;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum TagName {
     Bold,
     Italic,
@@ -0,0 +1,10 @@
+//! Rolling on tables whose first column is a die, e.g. a `d100` wild magic table, and
+//! generating names from name generator tables.
+
+mod error;
+mod name;
+mod rollable;
+
+pub use error::{Error, Result};
+pub use name::{name_generators, NameFilter, NameGenerator, NameGenerators};
+pub use rollable::{CoverageIssue, RollableRow, RollableTable, TableLookup, TableRoll, MAX_DEPTH};
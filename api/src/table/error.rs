@@ -0,0 +1,20 @@
+use crate::dice;
+use thiserror::Error as ErrorDerive;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(ErrorDerive, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("the table has no column labelled with a die")]
+    NoDie,
+    #[error("the table is not a name generator")]
+    NotANameGenerator,
+    #[error("row {row}: `{text}` is not a range of rolls")]
+    InvalidRange { row: usize, text: String },
+    #[error("no row matches a roll of {0}")]
+    NoRow(i64),
+    #[error("tables nested too deeply, at `{0}`")]
+    TooDeep(String),
+    #[error(transparent)]
+    Dice(#[from] dice::Error),
+}
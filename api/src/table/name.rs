@@ -0,0 +1,207 @@
+use super::{Error, Result, RollableTable};
+use crate::dice::Roller;
+use crate::entry::kinds::EntryTable;
+use crate::entry::Entry;
+use crate::string::{DefaultStringRenderer, RenderString};
+use rand::Rng;
+
+/// Generates names from a table flagged `isNameGenerator`, such as a table of elven first names
+/// and family names. Each column is rolled separately and the results are joined with spaces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameGenerator<'t, 'a> {
+    pub table: RollableTable<'t, 'a>,
+}
+
+/// The name generators among a list of tables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameGenerators<'t, 'a> {
+    pub generators: Vec<NameGenerator<'t, 'a>>,
+    /// Tables flagged as name generators which cannot be rolled on, and why.
+    pub errors: Vec<(&'t EntryTable<'a>, Error)>,
+}
+
+/// Selects name generator tables by name or caption, and by source.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NameFilter<'f> {
+    /// Matches tables whose name or caption contains this, ignoring case.
+    pub name: Option<&'f str>,
+    /// Matches tables from this source, ignoring case.
+    pub source: Option<&'f str>,
+}
+
+impl<'t, 'a: 't> NameGenerator<'t, 'a> {
+    pub fn new(table: &'t EntryTable<'a>) -> Result<Self> {
+        if table.is_name_generator != Some(true) {
+            return Err(Error::NotANameGenerator);
+        }
+
+        Ok(Self {
+            table: RollableTable::new(table)?,
+        })
+    }
+
+    pub fn generate<R: Rng>(&self, roller: &mut Roller<R>) -> Result<String> {
+        let columns = self.table.rows.iter().map(|row| row.result.len()).max();
+        let mut parts = Vec::new();
+
+        for column in 0..columns.unwrap_or_default() {
+            let roll = roller.roll(&self.table.dice)?.total;
+            let row = self.table.row_for(roll).ok_or(Error::NoRow(roll))?;
+
+            if let Some(part) = self.table.rows[row]
+                .result
+                .get(column)
+                .and_then(|cell| cell_name(cell))
+            {
+                parts.push(part);
+            }
+        }
+
+        Ok(parts.join(" "))
+    }
+
+    pub fn generate_many<R: Rng>(
+        &self,
+        roller: &mut Roller<R>,
+        count: usize,
+    ) -> Result<Vec<String>> {
+        (0..count).map(|_| self.generate(roller)).collect()
+    }
+}
+
+impl<'f> NameFilter<'f> {
+    pub fn matches(&self, table: &EntryTable) -> bool {
+        let matches_name = self.name.is_none_or(|name| {
+            let name = name.to_lowercase();
+            table
+                .base
+                .name
+                .iter()
+                .chain(table.caption.iter())
+                .any(|n| n.to_lowercase().contains(&name))
+        });
+        let matches_source = self.source.is_none_or(|source| {
+            table
+                .base
+                .source
+                .is_some_and(|s| s.eq_ignore_ascii_case(source))
+        });
+
+        matches_name && matches_source
+    }
+}
+
+/// Every table flagged as a name generator which matches the filter. Tables which cannot be
+/// rolled on are reported rather than failing the others.
+pub fn name_generators<'t, 'a: 't>(
+    tables: &'t [EntryTable<'a>],
+    filter: &NameFilter,
+) -> NameGenerators<'t, 'a> {
+    let mut found = NameGenerators {
+        generators: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for table in tables
+        .iter()
+        .filter(|table| table.is_name_generator == Some(true) && filter.matches(table))
+    {
+        match NameGenerator::new(table) {
+            Ok(generator) => found.generators.push(generator),
+            Err(e) => found.errors.push((table, e)),
+        }
+    }
+    found
+}
+
+/// The plain text of a cell, or `None` if it is empty or a dash.
+fn cell_name(cell: &Entry) -> Option<String> {
+    let text = match cell {
+        Entry::String(s) => DefaultStringRenderer.render(s).ok()?,
+        Entry::Integer(n) => n.to_string(),
+        Entry::Entry(_) => return None,
+    };
+    let text = text.trim();
+
+    match text {
+        "" | "-" | "–" | "—" => None,
+        _ => Some(text.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::EntryKind;
+
+    fn table(json: &str) -> EntryTable<'_> {
+        match serde_json::from_str(json).unwrap() {
+            Entry::Entry(EntryKind::Table(table)) => table,
+            entry => panic!("not a table: {:?}", entry),
+        }
+    }
+
+    #[test]
+    fn generate_names() {
+        let tables = vec![
+            table(
+                r#"{
+                    "type": "table",
+                    "name": "Dwarf Names",
+                    "source": "TST",
+                    "isNameGenerator": true,
+                    "colLabels": ["d4", "Given Name", "Clan Name"],
+                    "rows": [
+                        ["1", "Adrik", "Balderk"],
+                        ["2", "Eberk", "Dankil"],
+                        ["3", "{@i Rurik}", "Gorunn"],
+                        ["4", "Vondal", "—"]
+                    ]
+                }"#,
+            ),
+            table(
+                r#"{"type": "table", "name": "Dwarf Trinkets", "colLabels": ["d4", "x"], "rows": []}"#,
+            ),
+            table(
+                r#"{
+                    "type": "table",
+                    "name": "Dwarf Nicknames",
+                    "source": "TST",
+                    "isNameGenerator": true,
+                    "colLabels": ["Nickname"],
+                    "rows": [["Stonefist"]]
+                }"#,
+            ),
+        ];
+
+        let filter = NameFilter {
+            name: Some("dwarf"),
+            source: Some("tst"),
+        };
+        let found = name_generators(&tables, &filter);
+        let generators = found.generators;
+        assert_eq!(generators.len(), 1);
+        assert_eq!(found.errors.len(), 1);
+        assert_eq!(found.errors[0].0.base.name, Some("Dwarf Nicknames"));
+
+        let names = generators[0]
+            .generate_many(&mut Roller::seeded(5), 20)
+            .unwrap();
+        assert_eq!(
+            names,
+            generators[0]
+                .generate_many(&mut Roller::seeded(5), 20)
+                .unwrap()
+        );
+        for name in &names {
+            let first = name.split(' ').next().unwrap();
+            assert!(["Adrik", "Eberk", "Rurik", "Vondal"].contains(&first));
+            assert!(!name.contains('{') && !name.contains('—'));
+        }
+
+        assert_eq!(
+            NameGenerator::new(&tables[1]),
+            Err(Error::NotANameGenerator)
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use super::{Error, Result};
+use crate::dice::{Expression, Roller};
+use crate::entry::kinds::{EntryTable, EntryTableCellRoll, EntryTableRowKind};
+use crate::entry::{Entry, EntryKind};
+use crate::string::{tokenize, Lexeme};
+use rand::Rng;
+
+/// How many tables deep `{@table}` references are followed before giving up,
+/// which also guards against tables referring to each other.
+pub const MAX_DEPTH: usize = 8;
+
+/// A table with a die in one of its columns, each row covering a range of rolls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollableTable<'t, 'a> {
+    pub table: &'t EntryTable<'a>,
+    /// The dice rolled on the table, from the label of the roll column.
+    pub dice: Expression,
+    pub roll_column: usize,
+    pub min: i64,
+    pub max: i64,
+    pub rows: Vec<RollableRow<'t, 'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollableRow<'t, 'a> {
+    pub min: i64,
+    pub max: i64,
+    /// The row's cells other than the roll column.
+    pub result: Vec<&'t Entry<'a>>,
+}
+
+/// A problem with the ranges of a table's rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoverageIssue {
+    /// No row covers the rolls from `min` to `max`.
+    Gap { min: i64, max: i64 },
+    /// Two rows both cover the rolls from `min` to `max`.
+    Overlap {
+        rows: (usize, usize),
+        min: i64,
+        max: i64,
+    },
+    /// A row covers rolls the dice can never show.
+    OutOfRange { row: usize },
+}
+
+/// The outcome of rolling on a table, including any tables referenced by the chosen row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRoll<'t, 'a> {
+    pub table: &'t EntryTable<'a>,
+    pub roll: i64,
+    /// The index of the chosen row.
+    pub row: usize,
+    pub result: Vec<&'t Entry<'a>>,
+    pub nested: Vec<TableRoll<'t, 'a>>,
+    /// The `{@table}` references in the chosen row which could not be found.
+    pub unresolved: Vec<String>,
+}
+
+/// Finds the tables referenced by `{@table name|source}` tags.
+pub trait TableLookup<'t, 'a: 't> {
+    fn find_table(&self, name: &str, source: Option<&str>) -> Option<&'t EntryTable<'a>>;
+}
+
+impl<'t, 'a: 't, F> TableLookup<'t, 'a> for F
+where
+    F: Fn(&str, Option<&str>) -> Option<&'t EntryTable<'a>>,
+{
+    fn find_table(&self, name: &str, source: Option<&str>) -> Option<&'t EntryTable<'a>> {
+        self(name, source)
+    }
+}
+
+/// Matches the name or caption of a table, and its source if given, ignoring case.
+impl<'t, 'a: 't> TableLookup<'t, 'a> for &'t [EntryTable<'a>] {
+    fn find_table(&self, name: &str, source: Option<&str>) -> Option<&'t EntryTable<'a>> {
+        self.iter().find(|table| {
+            let names = table.base.name.iter().chain(table.caption.iter());
+            let matches_name = names.into_iter().any(|n| n.eq_ignore_ascii_case(name));
+            let matches_source = source.is_none_or(|source| {
+                table
+                    .base
+                    .source
+                    .is_some_and(|s| s.eq_ignore_ascii_case(source))
+            });
+            matches_name && matches_source
+        })
+    }
+}
+
+impl<'t, 'a: 't> RollableTable<'t, 'a> {
+    /// Finds the die in the table's column labels and reads every row's range.
+    pub fn new(table: &'t EntryTable<'a>) -> Result<Self> {
+        let labels = table.col_labels.as_deref().unwrap_or_default();
+        let (roll_column, dice) = labels
+            .iter()
+            .enumerate()
+            .find_map(|(i, label)| Some((i, parse_die_label(label)?)))
+            .ok_or(Error::NoDie)?;
+
+        let distribution = dice.distribution()?;
+        let (min, max) = (distribution.min(), distribution.max());
+
+        let rows = table
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let cells = row_cells(row);
+                let (row_min, row_max) = match cells.get(roll_column) {
+                    Some(cell) => parse_range(cell, min, max),
+                    None => None,
+                }
+                .ok_or_else(|| Error::InvalidRange {
+                    row: i,
+                    text: cells
+                        .get(roll_column)
+                        .map(|cell| cell_text(cell))
+                        .unwrap_or_default(),
+                })?;
+
+                let result = cells
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != roll_column)
+                    .map(|(_, cell)| *cell)
+                    .collect();
+
+                Ok(RollableRow {
+                    min: row_min,
+                    max: row_max,
+                    result,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            table,
+            dice,
+            roll_column,
+            min,
+            max,
+            rows,
+        })
+    }
+
+    /// The index of the row covering a roll.
+    pub fn row_for(&self, roll: i64) -> Option<usize> {
+        self.rows.iter().position(|row| row.contains(roll))
+    }
+
+    /// Checks that every possible roll is covered by exactly one row.
+    pub fn check_coverage(&self) -> Vec<CoverageIssue> {
+        let mut issues = Vec::new();
+
+        for (i, row) in self.rows.iter().enumerate() {
+            if row.min < self.min || row.max > self.max {
+                issues.push(CoverageIssue::OutOfRange { row: i });
+            }
+            for (j, other) in self.rows.iter().enumerate().skip(i + 1) {
+                let (min, max) = (row.min.max(other.min), row.max.min(other.max));
+                if min <= max {
+                    issues.push(CoverageIssue::Overlap {
+                        rows: (i, j),
+                        min,
+                        max,
+                    });
+                }
+            }
+        }
+
+        let mut gap_start = None;
+        for roll in self.min..=self.max + 1 {
+            let covered = roll > self.max || self.row_for(roll).is_some();
+            match (gap_start, covered) {
+                (None, false) => gap_start = Some(roll),
+                (Some(min), true) => {
+                    issues.push(CoverageIssue::Gap { min, max: roll - 1 });
+                    gap_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        issues
+    }
+
+    /// Rolls on the table without following `{@table}` references.
+    pub fn roll<R: Rng>(&self, roller: &mut Roller<R>) -> Result<TableRoll<'t, 'a>> {
+        let roll = roller.roll(&self.dice)?.total;
+        let row = self.row_for(roll).ok_or(Error::NoRow(roll))?;
+
+        Ok(TableRoll {
+            table: self.table,
+            roll,
+            row,
+            result: self.rows[row].result.clone(),
+            nested: Vec::new(),
+            unresolved: Vec::new(),
+        })
+    }
+
+    /// Rolls on the table, then on every table referenced by the chosen row, recursively.
+    pub fn roll_nested<R: Rng, L: TableLookup<'t, 'a>>(
+        &self,
+        roller: &mut Roller<R>,
+        lookup: &L,
+    ) -> Result<TableRoll<'t, 'a>> {
+        self.roll_at_depth(roller, lookup, 0)
+    }
+
+    fn roll_at_depth<R: Rng, L: TableLookup<'t, 'a>>(
+        &self,
+        roller: &mut Roller<R>,
+        lookup: &L,
+        depth: usize,
+    ) -> Result<TableRoll<'t, 'a>> {
+        if depth > MAX_DEPTH {
+            let name = self.table.base.name.or(self.table.caption);
+            return Err(Error::TooDeep(name.unwrap_or_default().to_owned()));
+        }
+
+        let mut result = self.roll(roller)?;
+        for (name, source) in table_references(&result.result) {
+            match lookup.find_table(&name, source.as_deref()) {
+                Some(table) => {
+                    let nested = RollableTable::new(table)?;
+                    result
+                        .nested
+                        .push(nested.roll_at_depth(roller, lookup, depth + 1)?);
+                }
+                None => result.unresolved.push(name),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl<'t, 'a> RollableRow<'t, 'a> {
+    pub fn contains(&self, roll: i64) -> bool {
+        (self.min..=self.max).contains(&roll)
+    }
+}
+
+fn row_cells<'t, 'a>(row: &'t EntryTableRowKind<'a>) -> Vec<&'t Entry<'a>> {
+    match row {
+        EntryTableRowKind::Entries(cells) => cells.iter().collect(),
+        EntryTableRowKind::__Row(row) => match row.as_ref() {
+            Entry::Entry(EntryKind::TableRow(row)) => row.row.iter().collect(),
+            entry => vec![entry],
+        },
+    }
+}
+
+/// Reads a die from a column label such as `d100`, `2d6` or `{@dice d8}`.
+fn parse_die_label(label: &str) -> Option<Expression> {
+    let text = match tokenize(label).ok()?.collect::<Vec<_>>().as_slice() {
+        [Lexeme::Tag(tag)] if tag.name == "dice" => tag.args.first()?.trim(),
+        _ => label.trim(),
+    };
+
+    let expression = text.parse::<Expression>().ok()?;
+    if expression.dice_groups().is_empty() {
+        None
+    } else {
+        Some(expression)
+    }
+}
+
+fn cell_text(cell: &Entry) -> String {
+    match cell {
+        Entry::String(s) => (*s).to_owned(),
+        Entry::Integer(n) => n.to_string(),
+        entry => serde_json::to_string(entry).unwrap_or_default(),
+    }
+}
+
+/// Reads the range of rolls a cell covers. Text such as `01-10`, `91-00`, `7+` and `3 or lower`
+/// is understood, with `00` standing for the highest roll of a `d100`.
+fn parse_range(cell: &Entry, min: i64, max: i64) -> Option<(i64, i64)> {
+    let text = match cell {
+        Entry::Integer(n) => return Some((*n, *n)),
+        Entry::Entry(EntryKind::TableCell(cell)) => {
+            return match cell.roll {
+                EntryTableCellRoll::Range { min, max, .. } => Some((min, max)),
+                EntryTableCellRoll::Exact { exact, .. } => Some((exact, exact)),
+            };
+        }
+        Entry::String(text) => text.trim().replace(&['–', '—', '−'][..], "-"),
+        _ => return None,
+    };
+
+    let number = |s: &str| -> Option<i64> {
+        let s = s.trim();
+        let n = s.parse::<i64>().ok()?;
+        // Percentile dice show 00 for 100, and a d10 shows 0 for 10
+        if n == 0 && min > 0 && s.chars().all(|c| c == '0') {
+            Some(max)
+        } else {
+            Some(n)
+        }
+    };
+
+    let lower = text.to_lowercase();
+    if let Some(n) = lower.strip_suffix('+') {
+        return Some((number(n)?, max));
+    }
+    for suffix in &[" or higher", " or more", " or above"] {
+        if let Some(n) = lower.strip_suffix(suffix) {
+            return Some((number(n)?, max));
+        }
+    }
+    for suffix in &[" or lower", " or less", " or below"] {
+        if let Some(n) = lower.strip_suffix(suffix) {
+            return Some((min, number(n)?));
+        }
+    }
+
+    match text.split_once('-') {
+        Some(("", _)) => None,
+        Some((start, end)) => Some((number(start)?, number(end)?)),
+        None => number(&text).map(|n| (n, n)),
+    }
+}
+
+/// The name and source of every `{@table}` tag in the cells, in order.
+fn table_references(cells: &[&Entry]) -> Vec<(String, Option<String>)> {
+    let texts = cells
+        .iter()
+        .flat_map(|cell| cell.strings())
+        .collect::<Vec<_>>();
+
+    let mut references = Vec::new();
+    for text in &texts {
+        let lexemes = match tokenize(text) {
+            Ok(lexemes) => lexemes,
+            Err(_) => continue,
+        };
+        for lexeme in lexemes {
+            if let Lexeme::Tag(tag) = lexeme {
+                if tag.name == "table" {
+                    let name = tag.args.first().map(|s| s.trim()).unwrap_or_default();
+                    let source = tag
+                        .args
+                        .get(1)
+                        .map(|s| s.trim())
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_owned);
+                    references.push((name.to_owned(), source));
+                }
+            }
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(json: &str) -> EntryTable<'_> {
+        match serde_json::from_str(json).unwrap() {
+            Entry::Entry(EntryKind::Table(table)) => table,
+            entry => panic!("not a table: {:?}", entry),
+        }
+    }
+
+    const TREASURE: &str = r#"{
+        "type": "table",
+        "name": "Treasure",
+        "source": "TST",
+        "colLabels": ["{@dice d100}", "Treasure"],
+        "rows": [
+            ["01-50", "Nothing"],
+            [{"type": "cell", "roll": {"min": 51, "max": 90}}, "Coins"],
+            ["91-00", "Roll on {@table Gems|TST}"]
+        ]
+    }"#;
+
+    const GEMS: &str = r#"{
+        "type": "table",
+        "name": "Gems",
+        "source": "TST",
+        "colLabels": ["d4", "Gem"],
+        "rows": [["1", "Ruby"], ["2–3", "Opal"], ["4", "Pearl"]]
+    }"#;
+
+    #[test]
+    fn rollable_ranges() {
+        let treasure = table(TREASURE);
+        let rollable = RollableTable::new(&treasure).unwrap();
+        assert_eq!((rollable.min, rollable.max), (1, 100));
+        assert_eq!(
+            rollable
+                .rows
+                .iter()
+                .map(|r| (r.min, r.max))
+                .collect::<Vec<_>>(),
+            vec![(1, 50), (51, 90), (91, 100)]
+        );
+        assert!(rollable.check_coverage().is_empty());
+
+        let broken = table(
+            r#"{"type": "table", "colLabels": ["d6", "x"], "rows": [["1-3", "a"], ["3", "b"], ["6+", "c"]]}"#,
+        );
+        let rollable = RollableTable::new(&broken).unwrap();
+        assert_eq!(
+            rollable.check_coverage(),
+            vec![
+                CoverageIssue::Overlap {
+                    rows: (0, 1),
+                    min: 3,
+                    max: 3
+                },
+                CoverageIssue::Gap { min: 4, max: 5 },
+            ]
+        );
+
+        let no_die = table(r#"{"type": "table", "colLabels": ["Name"], "rows": [["a"]]}"#);
+        assert_eq!(RollableTable::new(&no_die), Err(Error::NoDie));
+    }
+
+    #[test]
+    fn rollable_nested() {
+        let tables = vec![table(TREASURE), table(GEMS)];
+        let lookup = tables.as_slice();
+        let treasure = RollableTable::new(&tables[0]).unwrap();
+
+        let mut roller = Roller::seeded(0);
+        let mut nested = 0;
+        for _ in 0..200 {
+            let roll = treasure.roll_nested(&mut roller, &lookup).unwrap();
+            assert!(treasure.rows[roll.row].contains(roll.roll));
+            assert!(roll.unresolved.is_empty());
+            if roll.row == 2 {
+                assert_eq!(roll.nested.len(), 1);
+                assert_eq!(roll.nested[0].table.base.name, Some("Gems"));
+                nested += 1;
+            } else {
+                assert!(roll.nested.is_empty());
+            }
+        }
+        assert!(nested > 0);
+    }
+}
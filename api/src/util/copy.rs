@@ -65,6 +65,17 @@ pub enum CopyModifier<'a> {
         prop: &'a str,
         formula: &'a str,
     },
+    ScalarAddProp {
+        /// The property to modify, or "*" for every property
+        prop: &'a str,
+        scalar: f64,
+    },
+    ScalarMultProp {
+        /// The property to modify, or "*" for every property
+        prop: &'a str,
+        scalar: f64,
+        floor: Option<bool>,
+    },
     ReplaceSpells {
         /// [Value::Object]
         spells: Option<Value>,
@@ -14,22 +14,30 @@ pub struct GenericFluffArrayItemData<'a> {
 #[allow(dead_code)]
 pub type GenericFluffArray<'a> = Vec<GenericFluffArrayItem<'a>>;
 
-// TODO: Maybe figure out a better way to emulate the $$merge schema pre-processor tag?
+/// The `$$merge` schema pre-processor tag is emulated by flattening [GenericFluffArrayItemData].
+/// Records which copy other records are resolved by [crate::data::fluff].
 #[skip_serializing_none]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GenericFluffArrayItem<'a> {
+    Copied {
+        #[serde(flatten, borrow)]
+        data: GenericFluffArrayItemData<'a>,
+        _copy: super::copy::CopyBlock<'a>,
+    },
+    AppendCopied {
+        #[serde(flatten, borrow)]
+        data: GenericFluffArrayItemData<'a>,
+        /// The entries and images of this fluff are appended to those of the current fluff.
+        #[serde(rename = "_appendCopy")]
+        _append_copy: super::copy::CopyBlockTrait<'a>,
+    },
     Sourced {
         name: &'a str,
         source: &'a str,
         images: Option<Vec<EntryImage<'a>>>,
         entries: Option<Entries<'a>>,
     },
-    Copied {
-        #[serde(flatten)]
-        data: GenericFluffArrayItemData<'a>,
-        _copy: super::copy::CopyBlock<'a>,
-    },
 }
 
 #[skip_serializing_none]
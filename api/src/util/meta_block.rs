@@ -6,8 +6,38 @@ use std::collections::HashMap;
 #[serde(rename_all = "camelCase")]
 pub struct MetaBlock<'a> {
     #[serde(borrow)]
+    pub sources: Option<Vec<MetaBlockSource<'a>>>,
     pub dependencies: Option<MetaBlockDependencies<'a>>,
     pub other_sources: Option<MetaBlockOtherSources<'a>>,
+    /// Unix timestamp (in seconds)
+    pub date_added: Option<u64>,
+    /// Unix timestamp (in seconds)
+    pub date_last_modified: Option<u64>,
+}
+
+impl<'a> MetaBlock<'a> {
+    /// The `json` identifiers of every source declared by this block.
+    pub fn source_ids(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.sources.iter().flatten().map(|source| source.json)
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaBlockSource<'a> {
+    /// The identifier used in the `source` field of records, e.g. "MyHomebrew"
+    pub json: &'a str,
+    pub abbreviation: Option<&'a str>,
+    pub full: Option<&'a str>,
+    pub url: Option<&'a str>,
+    pub authors: Option<Vec<&'a str>>,
+    pub converted_by: Option<Vec<&'a str>>,
+    pub version: Option<&'a str>,
+    /// A hex color, without the leading "#"
+    pub color: Option<&'a str>,
+    /// ISO 8601 date, e.g. "2021-06-08"
+    pub date_released: Option<&'a str>,
 }
 
 #[skip_serializing_none]
@@ -0,0 +1,693 @@
+//! Structural validation of 5etools JSON.
+//!
+//! Deserializing through untagged enums such as [`Entry`](crate::entry::Entry) only reports that
+//! no variant matched. The validator instead walks a document against the shapes the crate's
+//! types accept and reports every problem it finds, each with the path to the offending value.
+
+mod schema;
+
+use schema::{EntryType, Field, Shape};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Snippets longer than this many characters are truncated.
+const SNIPPET_LEN: usize = 80;
+
+/// Written as a bare string or number in human-readable formats such as the JSON search index,
+/// and as a tagged enum in binary formats, which cannot tell the two apart on their own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl Serialize for PathSegment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Key(key) if serializer.is_human_readable() => serializer.serialize_str(key),
+            Self::Index(index) if serializer.is_human_readable() => {
+                serializer.serialize_u64(*index as u64)
+            }
+            Self::Key(key) => serializer.serialize_newtype_variant("PathSegment", 0, "Key", key),
+            Self::Index(index) => {
+                serializer.serialize_newtype_variant("PathSegment", 1, "Index", index)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PathSegment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Untagged {
+            Key(String),
+            Index(usize),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename = "PathSegment")]
+        enum Tagged {
+            Key(String),
+            Index(usize),
+        }
+
+        Ok(if deserializer.is_human_readable() {
+            match Untagged::deserialize(deserializer)? {
+                Untagged::Key(key) => Self::Key(key),
+                Untagged::Index(index) => Self::Index(index),
+            }
+        } else {
+            match Tagged::deserialize(deserializer)? {
+                Tagged::Key(key) => Self::Key(key),
+                Tagged::Index(index) => Self::Index(index),
+            }
+        })
+    }
+}
+
+/// The location of a value within a document, displayed as e.g. `entries[3].rows[1][0]`.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Path(Vec<PathSegment>);
+
+impl Path {
+    pub fn new(segments: Vec<PathSegment>) -> Self {
+        Self(segments)
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The path as a JSON pointer (RFC 6901), e.g. `/entries/3/rows/1/0`.
+    pub fn to_pointer(&self) -> String {
+        let mut pointer = String::new();
+        for segment in &self.0 {
+            pointer.push('/');
+            match segment {
+                PathSegment::Key(key) => {
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"))
+                }
+                PathSegment::Index(index) => pointer.push_str(&index.to_string()),
+            }
+        }
+        pointer
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("(root)");
+        }
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => f.write_str(key)?,
+                PathSegment::Key(key) => write!(f, ".{}", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How unrecognized data is treated.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Unknown entry types are errors, as they cannot be deserialized.
+    #[default]
+    Strict,
+    /// Unknown entry types are warnings, as they are kept as
+    /// [`EntryKind::Unknown`](crate::entry::EntryKind::Unknown).
+    Lenient,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: Path,
+    pub message: String,
+    /// The alternatives which would have been accepted at this path, if there are any.
+    pub expected: Vec<String>,
+    /// The offending value as compact JSON, truncated if it is long.
+    pub snippet: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// The result of lenient deserialization: the value, and any data in it which was not understood.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lenient<T> {
+    pub value: T,
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Validates a single entry, e.g. the contents of a `{"type": "entries", ...}` object.
+pub fn validate_entry(value: &Value) -> Vec<Diagnostic> {
+    validate_entry_with(value, Mode::Strict)
+}
+
+pub fn validate_entry_with(value: &Value, mode: Mode) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        mode,
+        ..Validator::default()
+    };
+    validator.entry(value, false);
+    validator.diagnostics
+}
+
+/// Validates an array of entries, e.g. the `entries` of a spell.
+pub fn validate_entries(value: &Value) -> Vec<Diagnostic> {
+    let mut validator = Validator::default();
+    validator.shape(value, &Shape::Entries);
+    validator.diagnostics
+}
+
+/// Validates a top-level record such as a spell or a monster.
+/// Only the properties whose shapes the crate knows about are checked; others are ignored.
+pub fn validate_record(value: &Value) -> Vec<Diagnostic> {
+    let mut validator = Validator::default();
+    validator.shape(value, &Shape::Object(schema::RECORD_FIELDS));
+    validator.diagnostics
+}
+
+/// Validates every record of a data file, e.g. `spells/spells-phb.json`.
+/// Properties starting with an underscore, such as `_meta`, are skipped.
+pub fn validate_document(value: &Value) -> Vec<Diagnostic> {
+    let mut validator = Validator::default();
+
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => {
+            validator.mismatch(value, "object");
+            return validator.diagnostics;
+        }
+    };
+
+    for (category, records) in object {
+        if category.starts_with('_') {
+            continue;
+        }
+        validator.at(PathSegment::Key(category.clone()), |v| {
+            v.shape(
+                records,
+                &Shape::Array(&Shape::Object(schema::RECORD_FIELDS)),
+            )
+        });
+    }
+
+    validator.diagnostics
+}
+
+#[derive(Default)]
+struct Validator {
+    mode: Mode,
+    path: Vec<PathSegment>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn at<F: FnOnce(&mut Self)>(&mut self, segment: PathSegment, f: F) {
+        self.path.push(segment);
+        f(self);
+        self.path.pop();
+    }
+
+    fn report(&mut self, value: &Value, message: String, expected: Vec<String>) {
+        self.push(Severity::Error, value, message, expected);
+    }
+
+    fn warn(&mut self, value: &Value, message: String, expected: Vec<String>) {
+        self.push(Severity::Warning, value, message, expected);
+    }
+
+    fn push(&mut self, severity: Severity, value: &Value, message: String, expected: Vec<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            path: Path(self.path.clone()),
+            message,
+            expected,
+            snippet: snippet(value),
+        });
+    }
+
+    fn mismatch(&mut self, value: &Value, expected: &str) {
+        self.report(
+            value,
+            format!("expected {}, found {}", expected, json_type(value)),
+            vec![expected.to_owned()],
+        );
+    }
+
+    fn shape(&mut self, value: &Value, shape: &Shape) {
+        match shape {
+            Shape::Any => {}
+            Shape::String | Shape::Integer | Shape::Number | Shape::Bool => {
+                if !shape.admits(value) {
+                    self.mismatch(value, &shape.describe());
+                }
+            }
+            Shape::Enum(values) => match value.as_str() {
+                Some(s) if values.contains(&s) => {}
+                Some(s) => self.report(
+                    value,
+                    format!("unknown value \"{}\"", s),
+                    values.iter().map(|v| (*v).to_owned()).collect(),
+                ),
+                None => self.mismatch(value, &shape.describe()),
+            },
+            Shape::Entry => self.entry(value, false),
+            Shape::EntryObject => self.entry(value, true),
+            Shape::Entries => match value.as_array() {
+                Some(items) => self.items(items, &Shape::Entry),
+                None => self.mismatch(value, &shape.describe()),
+            },
+            Shape::Array(inner) => match value.as_array() {
+                Some(items) => self.items(items, inner),
+                None => self.mismatch(value, &shape.describe()),
+            },
+            Shape::Map(inner) => match value.as_object() {
+                Some(object) => {
+                    for (key, item) in object {
+                        self.at(PathSegment::Key(key.clone()), |v| v.shape(item, inner));
+                    }
+                }
+                None => self.mismatch(value, &shape.describe()),
+            },
+            Shape::Object(fields) => match value.as_object() {
+                Some(object) => self.fields(value, object, fields, "object"),
+                None => self.mismatch(value, &shape.describe()),
+            },
+            Shape::Tagged(variants) => self.tagged(value, variants),
+            Shape::Either(shapes) => self.either(value, shapes),
+            Shape::TableRow => match value {
+                Value::Array(items) => self.items(items, &Shape::Entry),
+                Value::Object(_) => self.entry(value, true),
+                _ => self.mismatch(value, &shape.describe()),
+            },
+            Shape::CellRoll => self.cell_roll(value),
+        }
+    }
+
+    fn items(&mut self, items: &[Value], shape: &Shape) {
+        for (i, item) in items.iter().enumerate() {
+            self.at(PathSegment::Index(i), |v| v.shape(item, shape));
+        }
+    }
+
+    fn fields(
+        &mut self,
+        value: &Value,
+        object: &Map<String, Value>,
+        fields: &[Field],
+        owner: &str,
+    ) {
+        for field in fields {
+            match object.get(field.name) {
+                // Optional properties are deserialized as `Option`s, which accept null
+                Some(Value::Null) if !field.required => {}
+                Some(item) => {
+                    self.at(PathSegment::Key(field.name.to_owned()), |v| {
+                        v.shape(item, &field.shape)
+                    });
+                }
+                None if field.required => self.report(
+                    value,
+                    format!("{} requires `{}`", owner, field.name),
+                    vec![field.shape.describe()],
+                ),
+                None => {}
+            }
+        }
+    }
+
+    fn entry(&mut self, value: &Value, object_only: bool) {
+        let object = match value {
+            Value::String(_) | Value::Number(_) if !object_only && Shape::Entry.admits(value) => {
+                return
+            }
+            Value::Object(object) => object,
+            _ if object_only => return self.mismatch(value, "entry object"),
+            _ => return self.mismatch(value, "string | integer | entry object"),
+        };
+
+        let ty = match object.get("type") {
+            Some(Value::String(ty)) => ty,
+            Some(other) => {
+                return self.at(PathSegment::Key("type".to_owned()), |v| {
+                    v.mismatch(other, "string")
+                })
+            }
+            None => {
+                return self.report(
+                    value,
+                    "entry object requires `type`".to_owned(),
+                    entry_type_names(),
+                )
+            }
+        };
+
+        let entry_type = match schema::entry_type_of(ty) {
+            Some(entry_type) => entry_type,
+            None => {
+                let message = format!("unknown entry type \"{}\"", ty);
+                return match self.mode {
+                    Mode::Strict => self.report(value, message, entry_type_names()),
+                    Mode::Lenient => self.warn(value, message, entry_type_names()),
+                };
+            }
+        };
+
+        self.entry_fields(value, object, entry_type);
+    }
+
+    fn entry_fields(&mut self, value: &Value, object: &Map<String, Value>, entry_type: &EntryType) {
+        // Fields of the entry type take precedence over the base fields of the same name
+        let base = schema::BASE_FIELDS
+            .iter()
+            .filter(|base| entry_type.fields.iter().all(|f| f.name != base.name))
+            .copied()
+            .collect::<Vec<_>>();
+        self.fields(value, object, &base, entry_type.name);
+        self.fields(value, object, entry_type.fields, entry_type.name);
+
+        if !entry_type.open {
+            let known = |key: &str| {
+                key == "type"
+                    || base.iter().any(|f| f.name == key)
+                    || entry_type.fields.iter().any(|f| f.name == key)
+            };
+            for (key, item) in object.iter().filter(|(key, _)| !known(key)) {
+                self.at(PathSegment::Key(key.clone()), |v| {
+                    v.warn(
+                        item,
+                        format!("unknown property `{}` of {}", key, entry_type.name),
+                        Vec::new(),
+                    )
+                });
+            }
+        }
+
+        let any_of = entry_type.any_of;
+        if !any_of.is_empty() && any_of.iter().all(|f| !object.contains_key(*f)) {
+            let names = any_of
+                .iter()
+                .map(|f| format!("`{}`", f))
+                .collect::<Vec<_>>();
+            self.report(
+                value,
+                format!("{} requires {}", entry_type.name, names.join(" or ")),
+                any_of.iter().map(|f| (*f).to_owned()).collect(),
+            );
+        }
+    }
+
+    fn tagged(&mut self, value: &Value, variants: &[(&str, &[Field])]) {
+        let expected = || variants.iter().map(|(t, _)| (*t).to_owned()).collect();
+
+        let object = match value.as_object() {
+            Some(object) => object,
+            None => return self.mismatch(value, "object"),
+        };
+        let ty = match object.get("type").and_then(Value::as_str) {
+            Some(ty) => ty,
+            None => return self.report(value, "object requires `type`".to_owned(), expected()),
+        };
+
+        match variants.iter().find(|(t, _)| *t == ty) {
+            Some((_, fields)) => self.fields(value, object, fields, ty),
+            None => self.report(value, format!("unknown type \"{}\"", ty), expected()),
+        }
+    }
+
+    /// Untagged enums: if only one alternative could match the value's JSON type, its errors
+    /// are reported directly. Otherwise, the errors of the closest alternative are reported.
+    fn either(&mut self, value: &Value, shapes: &[Shape]) {
+        let candidates = shapes
+            .iter()
+            .filter(|s| s.admits(value))
+            .collect::<Vec<_>>();
+
+        match candidates.as_slice() {
+            [] => self.report(
+                value,
+                format!(
+                    "expected {}, found {}",
+                    shapes
+                        .iter()
+                        .map(Shape::describe)
+                        .collect::<Vec<_>>()
+                        .join(" | "),
+                    json_type(value)
+                ),
+                shapes.iter().map(Shape::describe).collect(),
+            ),
+            [shape] => self.shape(value, shape),
+            _ => {
+                let mut best: Option<Vec<Diagnostic>> = None;
+                for shape in candidates {
+                    let mut attempt = Validator {
+                        mode: self.mode,
+                        path: self.path.clone(),
+                        diagnostics: Vec::new(),
+                    };
+                    attempt.shape(value, shape);
+                    let errors = error_count(&attempt.diagnostics);
+                    if errors == 0 {
+                        self.diagnostics.extend(attempt.diagnostics);
+                        return;
+                    }
+                    if best.as_ref().is_none_or(|b| errors < error_count(b)) {
+                        best = Some(attempt.diagnostics);
+                    }
+                }
+                self.diagnostics.extend(best.unwrap_or_default());
+            }
+        }
+    }
+
+    fn cell_roll(&mut self, value: &Value) {
+        let object = match value.as_object() {
+            Some(object) => object,
+            None => return self.mismatch(value, "object"),
+        };
+
+        if object.contains_key("exact") {
+            self.fields(
+                value,
+                object,
+                &[
+                    Field {
+                        name: "exact",
+                        required: true,
+                        shape: Shape::Integer,
+                    },
+                    Field {
+                        name: "pad",
+                        required: false,
+                        shape: Shape::Bool,
+                    },
+                ],
+                "roll",
+            );
+        } else if object.contains_key("min") || object.contains_key("max") {
+            self.fields(
+                value,
+                object,
+                &[
+                    Field {
+                        name: "min",
+                        required: true,
+                        shape: Shape::Integer,
+                    },
+                    Field {
+                        name: "max",
+                        required: true,
+                        shape: Shape::Integer,
+                    },
+                    Field {
+                        name: "pad",
+                        required: false,
+                        shape: Shape::Bool,
+                    },
+                ],
+                "roll",
+            );
+        } else {
+            self.report(
+                value,
+                "roll requires either `exact` or `min` and `max`".to_owned(),
+                vec!["exact".to_owned(), "min and max".to_owned()],
+            );
+        }
+    }
+}
+
+fn error_count(diagnostics: &[Diagnostic]) -> usize {
+    diagnostics.iter().filter(|d| d.is_error()).count()
+}
+
+fn entry_type_names() -> Vec<String> {
+    schema::ENTRY_TYPES
+        .iter()
+        .map(|t| t.name.to_owned())
+        .collect()
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn snippet(value: &Value) -> String {
+    let json = value.to_string();
+    if json.chars().count() <= SNIPPET_LEN {
+        return json;
+    }
+
+    let mut snippet = json.chars().take(SNIPPET_LEN).collect::<String>();
+    snippet.push('…');
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn validate_reports_all_errors() {
+        let entry = json!({
+            "type": "entries",
+            "entries": [
+                "Some text",
+                { "type": "list", "items": [true] },
+                { "type": "foo" },
+                {
+                    "type": "table",
+                    "rows": [
+                        ["1", "One"],
+                        [{ "type": "cell", "width": 1 }, "Two"]
+                    ]
+                }
+            ]
+        });
+
+        let diagnostics = validate_entry(&entry);
+        assert_eq!(
+            messages(&diagnostics),
+            vec![
+                "entries[1].items[0]: expected string | integer | entry object, found boolean",
+                "entries[2]: unknown entry type \"foo\"",
+                "entries[3].rows[1][0]: cell requires `roll`",
+            ]
+        );
+        assert_eq!(diagnostics[2].path.to_pointer(), "/entries/3/rows/1/0");
+        assert_eq!(diagnostics[2].snippet, r#"{"type":"cell","width":1}"#);
+        assert!(diagnostics[1].expected.contains(&"entries".to_owned()));
+    }
+
+    /// The schema is written by hand, so it must list exactly the entry types serde reads.
+    #[test]
+    fn schema_covers_every_entry_kind() {
+        let kinds = crate::entry::EntryKind::types();
+        assert!(!kinds.is_empty());
+
+        for kind in kinds {
+            assert!(
+                schema::entry_type_of(kind).is_some(),
+                "entry type `{}` has no schema",
+                kind
+            );
+        }
+        for entry_type in schema::ENTRY_TYPES {
+            assert!(
+                kinds.contains(&entry_type.name),
+                "schema for `{}` has no entry kind",
+                entry_type.name
+            );
+        }
+    }
+
+    #[test]
+    fn validate_untagged() {
+        let record = json!({
+            "name": "Goblin",
+            "source": "MM",
+            "speed": { "walk": 30, "fly": { "number": 30 } },
+            "immune": ["fire", "cheese", { "special": 1 }]
+        });
+
+        assert_eq!(
+            messages(&validate_record(&record)),
+            vec![
+                "speed.fly: object requires `condition`",
+                "immune[1]: unknown value \"cheese\"",
+                "immune[2].special: expected string, found integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_document_records() {
+        let document = json!({
+            "_meta": { "sources": [] },
+            "spell": [
+                { "name": "Fireball", "source": "PHB", "entries": ["Boom."] },
+                { "name": "Broken", "source": "PHB", "entries": "Not an array" }
+            ]
+        });
+
+        assert_eq!(
+            messages(&validate_document(&document)),
+            vec!["spell[1].entries: expected array of entries, found string"]
+        );
+    }
+
+    #[test]
+    fn path_formats() {
+        let path = Path::new(vec![
+            PathSegment::Key("entries".to_owned()),
+            PathSegment::Index(3),
+        ]);
+
+        let json = serde_json::to_value(&path).unwrap();
+        assert_eq!(json, json!(["entries", 3]));
+        assert_eq!(serde_json::from_value::<Path>(json).unwrap(), path);
+
+        let bytes = bincode::serialize(&path).unwrap();
+        assert_eq!(bincode::deserialize::<Path>(&bytes).unwrap(), path);
+    }
+}
@@ -0,0 +1,565 @@
+//! The shapes the crate's data types accept, mirroring their serde definitions.
+
+use serde_json::Value;
+
+#[derive(Debug, Copy, Clone)]
+pub enum Shape {
+    Any,
+    String,
+    Integer,
+    Number,
+    Bool,
+    /// A string, an integer, or an object with a known `type`. See [`crate::entry::Entry`].
+    Entry,
+    /// An array of [`Shape::Entry`].
+    Entries,
+    /// An entry which must be an object, e.g. the blocks of a flowchart.
+    EntryObject,
+    /// One of a fixed set of strings.
+    Enum(&'static [&'static str]),
+    Array(&'static Shape),
+    /// An object with arbitrary keys, all of whose values have the same shape.
+    Map(&'static Shape),
+    Object(&'static [Field]),
+    /// An object whose shape depends on the value of its `type` property.
+    Tagged(&'static [(&'static str, &'static [Field])]),
+    /// Any of the given shapes, as for untagged enums.
+    Either(&'static [Shape]),
+    /// The contents of a single table row: either an array of entries or a `row` entry.
+    TableRow,
+    /// The `roll` of a table cell: either `exact`, or `min` and `max`.
+    CellRoll,
+}
+
+impl Shape {
+    /// A short, human-readable name for the shape, used in diagnostics.
+    pub fn describe(&self) -> String {
+        match self {
+            Shape::Any => "any value".to_owned(),
+            Shape::String => "string".to_owned(),
+            Shape::Integer => "integer".to_owned(),
+            Shape::Number => "number".to_owned(),
+            Shape::Bool => "boolean".to_owned(),
+            Shape::Entry => "entry".to_owned(),
+            Shape::Entries => "array of entries".to_owned(),
+            Shape::EntryObject => "entry object".to_owned(),
+            Shape::Enum(values) => values
+                .iter()
+                .map(|v| format!("\"{}\"", v))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            Shape::Array(shape) => format!("array of {}", shape.describe()),
+            Shape::Map(shape) => format!("map of {}", shape.describe()),
+            Shape::Object(_) | Shape::Tagged(_) | Shape::CellRoll => "object".to_owned(),
+            Shape::Either(shapes) => shapes
+                .iter()
+                .map(Shape::describe)
+                .collect::<Vec<_>>()
+                .join(" | "),
+            Shape::TableRow => "array of entries | row".to_owned(),
+        }
+    }
+
+    /// Whether a value could possibly have this shape, judging only by its JSON type.
+    pub fn admits(&self, value: &Value) -> bool {
+        match self {
+            Shape::Any => true,
+            Shape::String | Shape::Enum(_) => value.is_string(),
+            Shape::Integer => value.is_i64() || value.is_u64(),
+            Shape::Number => value.is_number(),
+            Shape::Bool => value.is_boolean(),
+            Shape::Entry => value.is_string() || value.is_i64() || value.is_object(),
+            Shape::Entries | Shape::Array(_) => value.is_array(),
+            Shape::EntryObject
+            | Shape::Map(_)
+            | Shape::Object(_)
+            | Shape::Tagged(_)
+            | Shape::CellRoll => value.is_object(),
+            Shape::Either(shapes) => shapes.iter().any(|s| s.admits(value)),
+            Shape::TableRow => value.is_array() || value.is_object(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Field {
+    pub name: &'static str,
+    pub required: bool,
+    pub shape: Shape,
+}
+
+const fn req(name: &'static str, shape: Shape) -> Field {
+    Field {
+        name,
+        required: true,
+        shape,
+    }
+}
+
+const fn opt(name: &'static str, shape: Shape) -> Field {
+    Field {
+        name,
+        required: false,
+        shape,
+    }
+}
+
+/// The fields of an entry object with a particular `type`.
+#[derive(Debug, Copy, Clone)]
+pub struct EntryType {
+    pub name: &'static str,
+    pub fields: &'static [Field],
+    /// If non-empty, at least one of these fields must be present.
+    pub any_of: &'static [&'static str],
+    /// Whether properties other than `fields` are expected, e.g. the amounts of an ingredient.
+    pub open: bool,
+}
+
+const fn entry_type(name: &'static str, fields: &'static [Field]) -> EntryType {
+    EntryType {
+        name,
+        fields,
+        any_of: &[],
+        open: false,
+    }
+}
+
+/// Fields shared by every entry. See [`crate::entry::EntryBaseData`].
+pub const BASE_FIELDS: &[Field] = &[
+    opt("name", Shape::String),
+    opt("source", Shape::String),
+    opt("data", Shape::Any),
+    opt("page", Shape::Integer),
+    opt("id", Shape::String),
+];
+
+pub const ABILITIES: &[&str] = &["str", "dex", "con", "int", "wis", "cha"];
+
+pub const DAMAGE_TYPES: &[&str] = &[
+    "acid",
+    "bludgeoning",
+    "cold",
+    "fire",
+    "force",
+    "lightning",
+    "necrotic",
+    "piercing",
+    "poison",
+    "psychic",
+    "radiant",
+    "slashing",
+    "thunder",
+];
+
+const ABILITY_ATTRIBUTES: Shape = Shape::Array(&Shape::Enum(&[
+    "str",
+    "dex",
+    "con",
+    "int",
+    "wis",
+    "cha",
+    "spellcasting",
+]));
+
+const STRINGS: Shape = Shape::Array(&Shape::String);
+
+const HREF: Shape = Shape::Tagged(&[
+    (
+        "internal",
+        &[
+            req("path", Shape::String),
+            opt("hash", Shape::String),
+            opt("hashPreEncoded", Shape::Bool),
+            opt("subhashes", Shape::Array(&Shape::Object(&[]))),
+        ],
+    ),
+    ("external", &[req("url", Shape::String)]),
+]);
+
+const LINK_HREF: Shape = Shape::Tagged(&[
+    (
+        "internal",
+        &[
+            req("path", Shape::String),
+            opt("hash", Shape::String),
+            opt("hashPreEncoded", Shape::Bool),
+            opt("subhashes", Shape::Array(&Shape::Object(&[]))),
+            opt(
+                "hover",
+                Shape::Object(&[
+                    req("page", Shape::String),
+                    req("source", Shape::String),
+                    opt("hash", Shape::String),
+                    opt("hashPreEncoded", Shape::Bool),
+                ]),
+            ),
+        ],
+    ),
+    ("external", &[req("url", Shape::String)]),
+]);
+
+const IMAGE_FIELDS: &[Field] = &[
+    req("href", HREF),
+    opt("hrefThumbnail", HREF),
+    opt("title", Shape::String),
+    opt("altText", Shape::String),
+    opt("imageType", Shape::Enum(&["map"])),
+    opt(
+        "mapRegions",
+        Shape::Array(&Shape::Object(&[
+            opt("area", Shape::String),
+            req("points", Shape::Array(&Shape::Any)),
+        ])),
+    ),
+    opt("width", Shape::Integer),
+    opt("height", Shape::Integer),
+    opt("maxWidth", Shape::Integer),
+    opt("maxHeight", Shape::Integer),
+    opt("maxWidthUnits", Shape::String),
+    opt("maxHeightUnits", Shape::String),
+    opt(
+        "style",
+        Shape::Enum(&["comic-speaker-left", "comic-speaker-right"]),
+    ),
+];
+
+const SPELL: Shape = Shape::Either(&[
+    Shape::String,
+    Shape::Object(&[req("entry", Shape::String), req("hidden", Shape::Bool)]),
+]);
+
+const SPELLS: Shape = Shape::Array(&SPELL);
+
+const SPELLCASTING_PROPERTIES: &[&str] = &[
+    "constant", "will", "rest", "daily", "weekly", "ritual", "spells",
+];
+
+const NAMED_ENTRIES: &[Field] = &[req("name", Shape::String), req("entries", Shape::Entries)];
+
+pub const ENTRY_TYPES: &[EntryType] = &[
+    entry_type(
+        "section",
+        &[opt("alias", STRINGS), req("entries", Shape::Entries)],
+    ),
+    entry_type(
+        "entries",
+        &[opt("alias", STRINGS), req("entries", Shape::Entries)],
+    ),
+    entry_type(
+        "homebrew",
+        &[
+            opt("entries", Shape::Entries),
+            opt("movedTo", Shape::Entry),
+            opt("oldEntries", Shape::Entries),
+        ],
+    ),
+    entry_type(
+        "quote",
+        &[
+            req("entries", Shape::Entries),
+            opt("by", Shape::String),
+            opt("from", Shape::String),
+            opt("skipMarks", Shape::Bool),
+        ],
+    ),
+    entry_type("inline", &[req("entries", Shape::Entries)]),
+    entry_type("inlineBlock", &[req("entries", Shape::Entries)]),
+    entry_type(
+        "options",
+        &[
+            opt("count", Shape::Integer),
+            opt("style", Shape::String),
+            req("entries", Shape::Entries),
+        ],
+    ),
+    entry_type(
+        "table",
+        &[
+            opt("caption", Shape::String),
+            opt("intro", Shape::Entries),
+            opt("outro", Shape::Entries),
+            opt("isStriped", Shape::Bool),
+            opt("isNameGenerator", Shape::Bool),
+            opt("style", Shape::String),
+            opt("colLabels", STRINGS),
+            opt("colStyles", STRINGS),
+            opt("rowLabels", STRINGS),
+            opt("rowStyles", STRINGS),
+            req("rows", Shape::Array(&Shape::TableRow)),
+            opt("footnotes", Shape::Entries),
+        ],
+    ),
+    entry_type("tableGroup", &[opt("tables", Shape::Entries)]),
+    entry_type(
+        "row",
+        &[opt("style", Shape::String), req("row", Shape::Entries)],
+    ),
+    entry_type(
+        "cell",
+        &[
+            opt("width", Shape::Integer),
+            req("roll", Shape::CellRoll),
+            opt("entry", Shape::Entry),
+        ],
+    ),
+    entry_type(
+        "list",
+        &[
+            opt("columns", Shape::Integer),
+            opt("style", Shape::String),
+            req("items", Shape::Entries),
+        ],
+    ),
+    entry_type("bonus", &[req("value", Shape::Integer)]),
+    entry_type("bonusSpeed", &[req("value", Shape::Integer)]),
+    entry_type(
+        "dice",
+        &[
+            opt(
+                "toRoll",
+                Shape::Array(&Shape::Object(&[
+                    req("number", Shape::Integer),
+                    req("faces", Shape::Integer),
+                    opt("modifier", Shape::Integer),
+                    opt("hideModifier", Shape::Bool),
+                ])),
+            ),
+            opt("rollable", Shape::Bool),
+        ],
+    ),
+    entry_type(
+        "abilityDc",
+        &[
+            req("name", Shape::String),
+            req("attributes", ABILITY_ATTRIBUTES),
+        ],
+    ),
+    entry_type(
+        "abilityAttackMod",
+        &[
+            req("name", Shape::String),
+            req("attributes", ABILITY_ATTRIBUTES),
+        ],
+    ),
+    entry_type(
+        "abilityGeneric",
+        &[
+            req("text", Shape::String),
+            opt("attributes", ABILITY_ATTRIBUTES),
+        ],
+    ),
+    entry_type(
+        "link",
+        &[req("text", Shape::String), req("href", LINK_HREF)],
+    ),
+    entry_type(
+        "optfeature",
+        &[
+            req("name", Shape::String),
+            opt("prerequisite", Shape::String),
+            opt("entries", Shape::Entries),
+        ],
+    ),
+    entry_type(
+        "inset",
+        &[req("entries", Shape::Entries), opt("style", Shape::String)],
+    ),
+    entry_type(
+        "insetReadaloud",
+        &[req("entries", Shape::Entries), opt("style", Shape::String)],
+    ),
+    entry_type(
+        "variant",
+        &[
+            req("name", Shape::String),
+            req("entries", Shape::Entries),
+            opt(
+                "variantSource",
+                Shape::Object(&[req("source", Shape::String), req("page", Shape::Integer)]),
+            ),
+        ],
+    ),
+    entry_type("variantInner", NAMED_ENTRIES),
+    entry_type("variantSub", NAMED_ENTRIES),
+    EntryType {
+        name: "item",
+        fields: &[
+            opt("style", Shape::String),
+            req("name", Shape::String),
+            opt("entry", Shape::Entry),
+            opt("entries", Shape::Entries),
+        ],
+        any_of: &["entry", "entries"],
+        open: false,
+    },
+    entry_type(
+        "itemSub",
+        &[req("name", Shape::String), req("entry", Shape::Entry)],
+    ),
+    entry_type(
+        "itemSpell",
+        &[req("name", Shape::String), req("entry", Shape::Entry)],
+    ),
+    entry_type("image", IMAGE_FIELDS),
+    entry_type(
+        "gallery",
+        &[req("images", Shape::Array(&Shape::Object(IMAGE_FIELDS)))],
+    ),
+    entry_type("actions", NAMED_ENTRIES),
+    entry_type(
+        "attack",
+        &[
+            req("attackType", Shape::Enum(&["MW", "RW"])),
+            req("attackEntries", Shape::Entries),
+            req("hitEntries", Shape::Entries),
+        ],
+    ),
+    entry_type(
+        "flowchart",
+        &[req("blocks", Shape::Array(&Shape::EntryObject))],
+    ),
+    entry_type("flowBlock", &[opt("entries", Shape::Entries)]),
+    EntryType {
+        name: "ingredient",
+        fields: &[req("entry", Shape::Entry)],
+        any_of: &[],
+        open: true,
+    },
+    entry_type("dataCreature", &[req("dataCreature", Shape::Any)]),
+    entry_type("dataSpell", &[req("dataSpell", Shape::Any)]),
+    entry_type("dataTrapHazard", &[req("dataTrapHazard", Shape::Any)]),
+    entry_type("dataObject", &[req("dataObject", Shape::Any)]),
+    entry_type("dataItem", &[req("dataItem", Shape::Any)]),
+    entry_type("refClassFeature", &[req("classFeature", Shape::String)]),
+    entry_type(
+        "refSubclassFeature",
+        &[req("subclassFeature", Shape::String)],
+    ),
+    entry_type(
+        "refOptionalfeature",
+        &[
+            req("optionalfeature", Shape::String),
+            opt("name", Shape::String),
+        ],
+    ),
+    entry_type("hr", &[]),
+    entry_type(
+        "spellcasting",
+        &[
+            req("name", Shape::String),
+            opt("headerEntries", Shape::Entries),
+            opt("constant", SPELLS),
+            opt("will", SPELLS),
+            opt("ritual", SPELLS),
+            opt("rest", Shape::Map(&SPELLS)),
+            opt("daily", Shape::Map(&SPELLS)),
+            opt("weekly", Shape::Map(&SPELLS)),
+            opt(
+                "spells",
+                Shape::Map(&Shape::Object(&[
+                    opt("lower", Shape::Number),
+                    opt("slots", Shape::Number),
+                    req("spells", STRINGS),
+                ])),
+            ),
+            opt(
+                "hidden",
+                Shape::Array(&Shape::Enum(SPELLCASTING_PROPERTIES)),
+            ),
+            opt("footerEntries", Shape::Entries),
+            opt("ability", Shape::Enum(ABILITIES)),
+            opt("displayAs", Shape::Enum(&["trait", "action"])),
+        ],
+    ),
+];
+
+pub fn entry_type_of(name: &str) -> Option<&'static EntryType> {
+    ENTRY_TYPES.iter().find(|t| t.name == name)
+}
+
+const SPEED_VALUE: Shape = Shape::Either(&[
+    Shape::Integer,
+    Shape::Object(&[
+        req("number", Shape::Integer),
+        req("condition", Shape::String),
+    ]),
+]);
+
+const SPEED_KINDS: &[&str] = &["walk", "burrow", "climb", "fly", "swim"];
+
+/// See [`crate::util::speed::Speed`].
+pub const SPEED: Shape = Shape::Either(&[
+    Shape::Integer,
+    Shape::Enum(&["varies"]),
+    Shape::Object(&[
+        opt("walk", SPEED_VALUE),
+        opt("burrow", SPEED_VALUE),
+        opt("climb", SPEED_VALUE),
+        opt("fly", SPEED_VALUE),
+        opt("swim", SPEED_VALUE),
+        opt("canHover", Shape::Bool),
+        opt(
+            "choose",
+            Shape::Object(&[
+                req("from", Shape::Array(&Shape::Enum(SPEED_KINDS))),
+                req("amount", Shape::Integer),
+                opt("note", Shape::String),
+            ]),
+        ),
+        opt("alternate", Shape::Map(&Shape::Array(&SPEED_VALUE))),
+    ]),
+]);
+
+macro_rules! damage_array {
+    ($name:ident, $key:literal) => {
+        /// See [`crate::util::damage_types`].
+        pub const $name: Shape = Shape::Array(&Shape::Either(&[
+            Shape::Enum(DAMAGE_TYPES),
+            Shape::Object(&[req("special", Shape::String)]),
+            Shape::Object(&[
+                opt("preNote", Shape::String),
+                req($key, Shape::Array(&Shape::Any)),
+                opt("note", Shape::String),
+                opt("cond", Shape::Bool),
+            ]),
+        ]));
+    };
+}
+
+damage_array!(DAMAGE_IMMUNITIES, "immune");
+damage_array!(DAMAGE_RESISTANCES, "resist");
+damage_array!(DAMAGE_VULNERABILITIES, "vulnerable");
+
+const NAMED_ENTRIES_ARRAY: Shape = Shape::Array(&Shape::Object(&[
+    opt("name", Shape::String),
+    req("entries", Shape::Entries),
+]));
+
+/// Properties of top-level records whose shape is known, regardless of category.
+pub const RECORD_FIELDS: &[Field] = &[
+    opt("name", Shape::String),
+    opt("source", Shape::String),
+    opt("page", Shape::Integer),
+    opt("entries", Shape::Entries),
+    opt("entriesHigherLevel", Shape::Entries),
+    opt("headerEntries", Shape::Entries),
+    opt("footerEntries", Shape::Entries),
+    opt("additionalEntries", Shape::Entries),
+    opt("speed", SPEED),
+    opt("immune", DAMAGE_IMMUNITIES),
+    opt("resist", DAMAGE_RESISTANCES),
+    opt("vulnerable", DAMAGE_VULNERABILITIES),
+    opt("trait", NAMED_ENTRIES_ARRAY),
+    opt("action", NAMED_ENTRIES_ARRAY),
+    opt("bonus", NAMED_ENTRIES_ARRAY),
+    opt("reaction", NAMED_ENTRIES_ARRAY),
+    opt("legendary", NAMED_ENTRIES_ARRAY),
+    opt("mythic", NAMED_ENTRIES_ARRAY),
+    opt(
+        "fluff",
+        Shape::Object(&[
+            opt("entries", Shape::Entries),
+            opt("images", Shape::Array(&Shape::Object(IMAGE_FIELDS))),
+        ]),
+    ),
+];
@@ -1,7 +1,43 @@
+pub mod attack {
+    pub use api::attack::*;
+}
+
+pub mod creature {
+    pub use api::creature::*;
+}
+
+pub mod data {
+    pub use api::data::*;
+}
+
+pub mod dice {
+    pub use api::dice::*;
+}
+
 pub mod entry {
     pub use api::entry::{kinds, Entry, EntryBaseData, EntryKind, MediaHref};
 }
 
+pub mod filter {
+    pub use api::filter::*;
+}
+
+pub mod mechanics {
+    pub use api::mechanics::*;
+}
+
+pub mod search {
+    pub use api::search::*;
+}
+
 pub mod string {
     pub use api::string::*;
 }
+
+pub mod table {
+    pub use api::table::*;
+}
+
+pub mod validate {
+    pub use api::validate::*;
+}